@@ -1,133 +1,273 @@
 
 
+use std::collections::BTreeMap;
+use std::sync::{Arc, LazyLock, Mutex};
+
+use ndarray::Axis;
+use rayon::prelude::*;
+
+use crate::analysis::features::labels::{self, LabelParams};
+use crate::analysis::features::pipeline::{DailyInputs, FeatureMatrix, FeaturePipeline, FeatureSource, FeatureSpec, Transform};
+use crate::analysis::features::sessions::{self, SessionStat};
+use crate::analysis::vol_spread::{VolSpreadParams, VolSpreadStat};
+use crate::analysis::volatility::RealizedVolEstimator;
+use crate::data::cache::{CacheKey, CacheWeight, LruCache};
+use crate::data::macro_series::MacroSeries;
 use crate::data::types::TickerData;
-use crate::analysis::{self};
-#[derive(Debug, Clone)]
-pub struct DirectionClassificationFeatures {
-    // Price-based features
-    pub prev_close_to_high_ratio: f32,
-    pub prev_close_to_low_ratio: f32,
-    pub daily_return: f32,
-    pub volatility_5d: f32,
-    pub volatility_20d: f32,
-    
-    // Volume features
-    pub volume_ratio_5d: f32,   // vs 5-day average
-    pub volume_ratio_20d: f32,  // vs 20-day average
-    // pub volume_price_trend: f32,
-    
-    // Technical indicators
-    pub rsi_14: f32,
-    pub sma_5: f32,
-    pub sma_20: f32,
-
-    pub macd_line: f32,
-    pub macd_signal: f32,
-    pub macd_histogram: f32,
-
-    pub direction: PriceDirection
-}
-
-#[derive(Debug, Clone, PartialEq)]
+
+/// Bump whenever `calculate_featureset` changes its output so cached feature sets are not reused.
+pub const FEATURE_VERSION: u32 = 9;
+
+/// Upper bound on the memory held by cached feature sets.
+pub const FEATURE_CACHE_BYTES: usize = 256 * 1024 * 1024;
+
+/// Series and the `Debug` form of the pipeline and label params that built the entry
+type FeatureCacheKey = (CacheKey, String);
+
+static FEATURE_CACHE: LazyLock<Mutex<LruCache<FeatureCacheKey, Arc<DirectionDataset>>>> = LazyLock::new(|| {
+    Mutex::new(LruCache::new(FEATURE_CACHE_BYTES))
+});
+
+/// Feature rows with the targets of the bar each row was computed at, one entry per row.
+#[derive(Debug, Clone, Default)]
+pub struct DirectionDataset {
+    pub features: FeatureMatrix,
+    pub direction: Vec<PriceDirection>,
+    pub forward_return: Vec<f64>,
+    pub forward_vol: Vec<f64>,
+    /// Last bar each row's label depends on
+    pub label_end: Vec<usize>,
+}
+
+impl DirectionDataset {
+    /// Rows from `bar` on to test with, and the earlier rows whose labels end before `bar` to
+    /// train with, so no training label overlaps the test period.
+    pub fn split_at_bar(&self, bar: usize) -> (Self, Self) {
+        let rows = 0..self.features.bars.len();
+        let train = rows.clone().filter(|&row| self.features.bars[row] < bar && self.label_end[row] < bar).collect::<Vec<_>>();
+        let test = rows.filter(|&row| self.features.bars[row] >= bar).collect::<Vec<_>>();
+
+        (self.select(&train), self.select(&test))
+    }
+
+    fn select(&self, rows: &[usize]) -> Self {
+        Self {
+            features: FeatureMatrix {
+                names: self.features.names.clone(),
+                values: self.features.values.select(Axis(0), rows),
+                bars: rows.iter().map(|&row| self.features.bars[row]).collect(),
+            },
+            direction: rows.iter().map(|&row| self.direction[row]).collect(),
+            forward_return: rows.iter().map(|&row| self.forward_return[row]).collect(),
+            forward_vol: rows.iter().map(|&row| self.forward_vol[row]).collect(),
+            label_end: rows.iter().map(|&row| self.label_end[row]).collect(),
+        }
+    }
+}
+
+impl CacheWeight for DirectionDataset {
+    fn weight(&self) -> usize {
+        self.features.weight()
+            + self.direction.len() * std::mem::size_of::<PriceDirection>()
+            + (self.forward_return.len() + self.forward_vol.len()) * std::mem::size_of::<f64>()
+            + self.label_end.len() * std::mem::size_of::<usize>()
+    }
+}
+
+/// Class of a forward return, see [`crate::analysis::features::labels`] for how it's thresholded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PriceDirection {
-    Up,    // Gap up > 1%
-    Down,  // Gap down < -1%
-    None,  // Normal opening ±1%
+    Up,    // Above the upper threshold, or take-profit touched first
+    Down,  // Below the lower threshold, or stop-loss touched first
+    None,  // In between, or the horizon ended first
 }
 
 impl PriceDirection {
-    pub fn to_usize(&self) -> usize {
+    pub fn to_usize(self) -> usize {
         match self {
             PriceDirection::Up => 2,
             PriceDirection::Down => 0,
             PriceDirection::None => 1,
         }
     }
-    pub fn to_string(&self) -> String {
+    pub fn to_string(self) -> String {
         match self {
             PriceDirection::Up => String::from("Up"),
             PriceDirection::Down => String::from("Down"),
             PriceDirection::None => String::from("None"),
         }
     }
-    pub fn calc(open: f32, close: f32) -> Self {
-        let percent_change = ((open - close) / close) * 100.0;
-        
-        if percent_change > 0.5 {
-            return Self::Up;
-        } else if percent_change < -0.5 {
-            return Self::Down;
-        }
+}
+
+/// Features the direction classifier is trained on.
+pub fn direction_pipeline() -> FeaturePipeline {
+    FeaturePipeline::new(vec![
+        FeatureSpec::new("prev_close_to_high_ratio", FeatureSource::CloseToHigh, 0).lag(1),
+        FeatureSpec::new("prev_close_to_low_ratio", FeatureSource::CloseToLow, 0).lag(1),
+        FeatureSpec::new("daily_return", FeatureSource::Return, 1),
+        FeatureSpec::new("volatility_5d", FeatureSource::RealizedVol(RealizedVolEstimator::CloseToClose), 5),
+        FeatureSpec::new("volatility_20d", FeatureSource::RealizedVol(RealizedVolEstimator::CloseToClose), 20),
+        FeatureSpec::new("volume_ratio_5d", FeatureSource::VolumeRatio, 5),
+        FeatureSpec::new("volume_ratio_20d", FeatureSource::VolumeRatio, 20),
+        FeatureSpec::indicator("rsi_14", "rsi", 14, &[], "rsi"),
+        FeatureSpec::indicator("sma_5", "sma", 5, &[], "sma").transform(Transform::RelativeToClose),
+        FeatureSpec::indicator("sma_20", "sma", 20, &[], "sma").transform(Transform::RelativeToClose),
+        FeatureSpec::indicator("macd_line", "macd", 12, &[26.0, 9.0], "line"),
+        FeatureSpec::indicator("macd_signal", "macd", 12, &[26.0, 9.0], "signal"),
+        FeatureSpec::indicator("macd_histogram", "macd", 12, &[26.0, 9.0], "histogram"),
+    ])
+}
+
+/// [`direction_pipeline`] with implied vs realized volatility columns, for [`DailyInputs`] holding
+/// daily implied vols such as [`crate::data::db_service::get_atm_iv_series`] or VIX. Only what is
+/// known at each bar is included; forward realized vol and the realized premium are targets.
+pub fn vol_spread_pipeline(params: VolSpreadParams) -> FeaturePipeline {
+    let spread = |name: &str, stat| FeatureSpec::new(name, FeatureSource::VolSpread { stat, params }, 0);
+
+    direction_pipeline()
+        .with(spread("implied_vol", VolSpreadStat::Implied))
+        .with(spread("expected_variance_premium", VolSpreadStat::ExpectedPremium))
+        .with(spread("iv_term_slope", VolSpreadStat::TermSlope))
+        .with(spread("iv_garch_spread", VolSpreadStat::GarchSpread))
+        .with(spread("iv_garch_term_slope", VolSpreadStat::TermSlopeOverGarch))
+        .with(spread("variance_premium_zscore", VolSpreadStat::PremiumZScore))
+}
 
-        Self::None
+/// [`direction_pipeline`] with a column per series in `macros`, for [`DailyInputs`] holding their
+/// observations.
+pub fn macro_pipeline(macros: &[MacroSeries]) -> FeaturePipeline {
+    with_macros(direction_pipeline(), macros)
+}
+
+fn with_macros(pipeline: FeaturePipeline, macros: &[MacroSeries]) -> FeaturePipeline {
+    macros
+        .iter()
+        .fold(pipeline, |pipeline, &series| pipeline.with(FeatureSpec::new(series.name(), FeatureSource::Macro(series), 0)))
+}
+
+/// Columns added to [`direction_pipeline`] when building the feature sets of a universe.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FeatureOptions {
+    /// Implied vs realized volatility columns of [`vol_spread_pipeline`], drawn from
+    /// [`DailyInputs::implied`] and [`DailyInputs::long_implied`]
+    pub vol_spread: Option<VolSpreadParams>,
+    /// Columns of [`macro_pipeline`], drawn from [`DailyInputs::macros`]
+    pub macros: Vec<MacroSeries>,
+}
+
+impl FeatureOptions {
+    pub fn pipeline(&self) -> FeaturePipeline {
+        let pipeline = match self.vol_spread {
+            Some(params) => vol_spread_pipeline(params),
+            None => direction_pipeline(),
+        };
+
+        with_macros(pipeline, &self.macros)
     }
 }
 
-pub fn calculate_featureset(data: &TickerData) -> Vec<DirectionClassificationFeatures> {
-    let mut features = Vec::new();
-
-    let raw_rsi_14 = analysis::moving_average::rsi_on_series(&data.price_data, 14);
-    let raw_sma_5 = analysis::moving_average::sma_on_series(&data.price_data, 5);
-    let raw_sma_20 = analysis::moving_average::sma_on_series(&data.price_data, 20);
-
-    let rsi_14 = analysis::normalization::normalize_series(raw_rsi_14, data.price_data.last().unwrap().close);
-    let sma_5 = analysis::normalization::normalize_series(raw_sma_5, data.price_data.last().unwrap().close);
-    let sma_20 = analysis::normalization::normalize_series(raw_sma_20, data.price_data.last().unwrap().close);
-    
-    let macd = analysis::moving_average::macd_on_series(&data.price_data, 12, 26);
-
-    println!("sma 20: {}", sma_20.len());
-    println!("sma 5: {}", sma_5.len());
-    println!("rsi 14: {}", rsi_14.len());
-    println!("macd: {}", macd.len());
-    println!("data: {}", data.price_data.len());
-
-    for (idx, el) in data.price_data.iter().enumerate() {
-        if idx == 0 {continue;}
-
-        let prev_5d = if idx > 5 {
-            data.price_data.get(idx-5..).unwrap()
-        } else {
-            data.price_data.get(..).unwrap()
-        }.to_vec();
-
-        let prev_20d = if idx > 20 {
-            data.price_data.get(idx-20..).unwrap()
-        } else {
-            data.price_data.get(..).unwrap()
-        }.to_vec();
-
-        let prev_frame = data.price_data.get(idx - 1).unwrap();
-
-        let prev_chr = prev_frame.close / prev_frame.high;
-        let prev_clr = prev_frame.close / prev_frame.low;
-
-        let daily_ret = (prev_frame.close - el.close) / el.close;
-
-        let volatility_5d = analysis::volatility::ticker_volatility_n_series(&prev_5d);
-        let volatility_20d = analysis::volatility::ticker_volatility_n_series(&prev_20d);
-
-        let vol_ratio_5d = analysis::normalization::volume_ratio(&prev_5d);
-        let vol_ratio_20d = analysis::normalization::volume_ratio(&prev_20d);
-
-        features.push(DirectionClassificationFeatures {
-            prev_close_to_high_ratio: prev_chr,
-            prev_close_to_low_ratio: prev_clr,
-            daily_return: daily_ret,
-            volatility_5d: volatility_5d,
-            volatility_20d: volatility_20d,
-            volume_ratio_5d: vol_ratio_5d,
-            volume_ratio_20d: vol_ratio_20d,
-            sma_5: *sma_5.get(idx).unwrap(),
-            rsi_14: *rsi_14.get(idx).unwrap(),
-            sma_20: *sma_20.get(idx).unwrap(),
-            macd_line: macd.get(idx).unwrap().macd,
-            macd_signal: macd.get(idx).unwrap().signal,
-            macd_histogram: macd.get(idx).unwrap().macd - macd.get(idx).unwrap().signal,
-            direction: PriceDirection::calc(el.open, el.close),
-        });
+/// Runs `pipeline` over `data` and `inputs` and labels each row by `labels`. Rows without a label,
+/// at the end of the series or while a threshold warms up, are dropped.
+pub fn calculate_featureset_with(
+    data: &TickerData,
+    pipeline: &FeaturePipeline,
+    inputs: &DailyInputs,
+    labels: LabelParams,
+) -> Result<DirectionDataset, String> {
+    let series = data.series();
+    let bar_labels = labels::label_series(series, labels);
+    let features = pipeline
+        .compute_with(series, inputs)?
+        .filter(|bar| bar_labels[bar].is_some_and(|label| label.direction.is_some()));
+
+    let row_labels = features.bars.iter().filter_map(|&bar| bar_labels[bar]).collect::<Vec<_>>();
+
+    Ok(DirectionDataset {
+        features,
+        direction: row_labels.iter().filter_map(|label| label.direction).collect(),
+        forward_return: row_labels.iter().map(|label| label.forward_return).collect(),
+        forward_vol: row_labels.iter().map(|label| label.forward_vol).collect(),
+        label_end: row_labels.iter().map(|label| label.end).collect(),
+    })
+}
+
+/// [`calculate_featureset_with`] the [`direction_pipeline`] and default labels.
+pub fn calculate_featureset(data: &TickerData) -> DirectionDataset {
+    calculate_featureset_with(data, &direction_pipeline(), &DailyInputs::default(), LabelParams::default())
+        .expect("direction pipeline only uses built-in indicators")
+}
+
+/// Features of the overnight gap classifier, for 10-minute bars. Rows are taken at each session's
+/// last regular bar, where the prior-session columns describe the session that just closed.
+pub fn gap_pipeline() -> FeaturePipeline {
+    FeaturePipeline::new(vec![
+        FeatureSpec::new("session_range", FeatureSource::PriorSession(SessionStat::Range), 0),
+        FeatureSpec::new("session_close_location", FeatureSource::PriorSession(SessionStat::CloseLocation), 0),
+        FeatureSpec::new("session_last_hour_return", FeatureSource::PriorSession(SessionStat::LastHourReturn), 0),
+        FeatureSpec::new("session_opening_hour_volume", FeatureSource::PriorSession(SessionStat::OpeningHourVolume), 0),
+        FeatureSpec::new("session_closing_hour_volume", FeatureSource::PriorSession(SessionStat::ClosingHourVolume), 0),
+        FeatureSpec::new("session_close_to_vwap", FeatureSource::PriorSession(SessionStat::CloseToVwap), 0),
+        FeatureSpec::new("session_range_change", FeatureSource::PriorSession(SessionStat::Range), 0).transform(Transform::PctChange),
+        FeatureSpec::new("volatility_39", FeatureSource::RealizedVol(RealizedVolEstimator::CloseToClose), 39),
+        FeatureSpec::indicator("rsi_14", "rsi", 14, &[], "rsi"),
+    ])
+}
+
+/// One row per session of intraday `data` that is followed by the next trading session, taken
+/// at its last regular bar and labeled with the overnight gap beyond `threshold` (a fraction).
+/// The gap is also the row's forward return and its label ends at the next session's first bar.
+pub fn calculate_gap_featureset(data: &TickerData, pipeline: &FeaturePipeline, threshold: f64) -> Result<DirectionDataset, String> {
+    let series = data.series();
+    let summaries = sessions::regular_sessions(series);
+    let gaps = sessions::overnight_gaps(series, &summaries, threshold)
+        .into_iter()
+        .map(|gap| (gap.from_bar, gap))
+        .collect::<BTreeMap<_, _>>();
+
+    let features = pipeline.compute(series)?.filter(|bar| gaps.contains_key(&bar));
+    let row_gaps = features.bars.iter().map(|bar| gaps[bar]).collect::<Vec<_>>();
+
+    Ok(DirectionDataset {
+        features,
+        direction: row_gaps.iter().map(|gap| gap.direction).collect(),
+        forward_return: row_gaps.iter().map(|gap| gap.gap).collect(),
+        forward_vol: vec![f64::NAN; row_gaps.len()],
+        label_end: row_gaps.iter().map(|gap| gap.to_bar).collect(),
+    })
+}
+
+/// Same as [`calculate_featureset`], but reuses the result for a series that was already
+/// featurized since its symbol was last written to the DB.
+pub fn calculate_featureset_cached(data: &TickerData) -> Arc<DirectionDataset> {
+    let (pipeline, labels) = (direction_pipeline(), LabelParams::default());
+    let key = (CacheKey::for_series(data).with_feature_version(FEATURE_VERSION), format!("{pipeline:?} {labels:?}"));
+
+    if let Some(cached) = FEATURE_CACHE.lock().unwrap().get(&key) {
+        return cached;
     }
 
+    let features = Arc::new(calculate_featureset(data));
+    FEATURE_CACHE.lock().unwrap().insert(key, features.clone());
+
     features
 }
 
+/// Drops the cached feature sets of `symbol`, see [`crate::data::cache::invalidate_symbol`].
+pub fn invalidate_symbol(symbol: &str) {
+    FEATURE_CACHE.lock().unwrap().retain(|(key, _)| key.symbol != symbol);
+}
+
+/// Feature set of every symbol of `universe` with the columns of `options`, one symbol per rayon
+/// task. The default options reuse [`calculate_featureset_cached`]; extra columns are computed
+/// from `inputs` and not cached, as the cache key doesn't cover them.
+pub fn calculate_featuresets(universe: &[TickerData], options: &FeatureOptions, inputs: &DailyInputs) -> Vec<Result<Arc<DirectionDataset>, String>> {
+    if *options == FeatureOptions::default() {
+        return universe.par_iter().map(|data| Ok(calculate_featureset_cached(data))).collect();
+    }
+
+    let pipeline = options.pipeline();
+    universe
+        .par_iter()
+        .map(|data| calculate_featureset_with(data, &pipeline, inputs, LabelParams::default()).map(Arc::new))
+        .collect()
+}
@@ -0,0 +1,246 @@
+use std::collections::VecDeque;
+
+use crate::analysis::{normalization, streaming::{vwap_session_day, StdDevStream}, volatility};
+use crate::analysis::features::featureset::PriceDirection;
+use crate::data::series::SeriesView;
+
+/// How far ahead of a bar its label looks. Every label starts at the bar's close, which is the
+/// last price its features can see.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Horizon {
+    NextBar,
+    /// To the close of the next New York session after the bar's own
+    NextSession,
+    Bars(usize),
+}
+
+/// How a forward return is split into up, down and flat.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Threshold {
+    /// Up above `+x`, down below `-x`, as a fraction
+    Fixed(f64),
+    /// `multiplier` times the trailing per-bar volatility of log returns over `window` bars,
+    /// scaled to the horizon's length
+    VolScaled { window: usize, multiplier: f64 },
+    /// The `lower` and `upper` quantiles of the last `window` forward returns that had already
+    /// ended at the bar
+    Quantile { window: usize, lower: f64, upper: f64 },
+}
+
+/// Profit-taking and stop-loss barriers around the bar's close, with the horizon as the
+/// vertical barrier. Widths are multiples of the trailing volatility scaled to the horizon.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TripleBarrier {
+    pub take_profit: f64,
+    pub stop_loss: f64,
+    /// Bars of log returns the trailing volatility is taken over
+    pub vol_window: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LabelRule {
+    Threshold(Threshold),
+    /// Up or down by whichever barrier the highs and lows touch first, flat if the horizon
+    /// ends first. A bar touching both counts as the stop.
+    TripleBarrier(TripleBarrier),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LabelParams {
+    pub horizon: Horizon,
+    pub rule: LabelRule,
+}
+
+impl Default for LabelParams {
+    fn default() -> Self {
+        Self { horizon: Horizon::NextBar, rule: LabelRule::Threshold(Threshold::Fixed(0.005)) }
+    }
+}
+
+/// Targets of one bar, all computed from bars after it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Label {
+    /// None while the threshold or barrier volatility warms up
+    pub direction: Option<PriceDirection>,
+    /// Last bar any of the targets depends on. Training rows whose `end` reaches into the test
+    /// period should be purged.
+    pub end: usize,
+    /// Bar the direction was decided at, before `end` when a barrier was touched first
+    pub decided_at: usize,
+    /// Simple return from the bar's close to the horizon's close
+    pub forward_return: f64,
+    /// Annualized realized volatility of the log returns within the horizon, around zero mean
+    pub forward_vol: f64,
+}
+
+/// Labels each bar of `series`, None where the horizon runs past the end of the series.
+pub fn label_series(series: SeriesView, params: LabelParams) -> Vec<Option<Label>> {
+    let n = series.len();
+    let ends = horizon_ends(series, params.horizon);
+    let periods_per_year = volatility::periods_per_year(series);
+
+    let log_returns = (0..n)
+        .map(|i| if i == 0 { f64::NAN } else { (series.close[i] / series.close[i - 1]).ln() })
+        .collect::<Vec<_>>();
+
+    // Prefix sums of squared log returns for the forward volatility of any span
+    let mut squared = vec![0.0; n + 1];
+    for i in 1..n {
+        squared[i + 1] = squared[i] + log_returns[i] * log_returns[i];
+    }
+
+    let forward_returns = (0..n)
+        .map(|i| ends[i].map_or(f64::NAN, |end| series.close[end] / series.close[i] - 1.0))
+        .collect::<Vec<_>>();
+
+    let vol_window = match params.rule {
+        LabelRule::Threshold(Threshold::VolScaled { window, .. }) => window,
+        LabelRule::TripleBarrier(barrier) => barrier.vol_window,
+        _ => 0,
+    };
+    let bar_vols = trailing_vol(&log_returns, vol_window);
+
+    let quantiles = match params.rule {
+        LabelRule::Threshold(Threshold::Quantile { window, lower, upper }) => {
+            trailing_quantiles(&forward_returns, &ends, window, lower, upper)
+        }
+        _ => Vec::new(),
+    };
+
+    (0..n)
+        .map(|i| {
+            let horizon_end = ends[i]?;
+            let bars = (horizon_end - i) as f64;
+            let horizon_vol = bar_vols[i] * bars.sqrt();
+            let forward_return = forward_returns[i];
+
+            let (direction, decided_at) = match params.rule {
+                LabelRule::Threshold(Threshold::Fixed(x)) => (classify(forward_return, -x, x), horizon_end),
+                LabelRule::Threshold(Threshold::VolScaled { multiplier, .. }) => {
+                    (classify(forward_return, -multiplier * horizon_vol, multiplier * horizon_vol), horizon_end)
+                }
+                LabelRule::Threshold(Threshold::Quantile { .. }) => {
+                    let (lower, upper) = quantiles[i];
+                    (classify(forward_return, lower, upper), horizon_end)
+                }
+                LabelRule::TripleBarrier(barrier) => {
+                    if horizon_vol.is_nan() {
+                        (None, horizon_end)
+                    } else {
+                        first_touch(series, i, horizon_end, barrier, horizon_vol)
+                    }
+                }
+            };
+
+            Some(Label {
+                direction,
+                end: horizon_end,
+                decided_at,
+                forward_return,
+                forward_vol: ((squared[horizon_end + 1] - squared[i + 1]) / bars * periods_per_year).sqrt(),
+            })
+        })
+        .collect()
+}
+
+/// Up above `upper`, down below `lower`, None if either bound is NaN
+pub(crate) fn classify(forward_return: f64, lower: f64, upper: f64) -> Option<PriceDirection> {
+    if lower.is_nan() || upper.is_nan() {
+        return None;
+    }
+
+    Some(if forward_return > upper {
+        PriceDirection::Up
+    } else if forward_return < lower {
+        PriceDirection::Down
+    } else {
+        PriceDirection::None
+    })
+}
+
+/// Direction and bar of the first barrier touched after bar `i`, the vertical barrier at `end`
+/// if neither is
+fn first_touch(series: SeriesView, i: usize, end: usize, barrier: TripleBarrier, horizon_vol: f64) -> (Option<PriceDirection>, usize) {
+    let upper = series.close[i] * (1.0 + barrier.take_profit * horizon_vol);
+    let lower = series.close[i] * (1.0 - barrier.stop_loss * horizon_vol);
+
+    for j in i + 1..=end {
+        if series.low[j] <= lower {
+            return (Some(PriceDirection::Down), j);
+        }
+        if series.high[j] >= upper {
+            return (Some(PriceDirection::Up), j);
+        }
+    }
+
+    (Some(PriceDirection::None), end)
+}
+
+/// Last bar of each bar's horizon, None where the series doesn't reach it
+fn horizon_ends(series: SeriesView, horizon: Horizon) -> Vec<Option<usize>> {
+    let n = series.len();
+
+    match horizon {
+        Horizon::NextBar => (0..n).map(|i| (i + 1 < n).then_some(i + 1)).collect(),
+        Horizon::Bars(bars) => (0..n).map(|i| (bars > 0 && i + bars < n).then_some(i + bars)).collect(),
+        Horizon::NextSession => {
+            let days = series.t.iter().map(|&t| vwap_session_day(t)).collect::<Vec<_>>();
+
+            // Last bar of every session, the final one only counts once a later bar shows it's complete
+            let session_ends = (0..n.saturating_sub(1)).filter(|&i| days[i] != days[i + 1]).collect::<Vec<_>>();
+
+            let mut session = 0;
+            (0..n)
+                .map(|i| {
+                    while session < session_ends.len() && session_ends[session] < i {
+                        session += 1;
+                    }
+                    // `session_ends[session]` closes the bar's own session, the next entry closes the following one
+                    session_ends.get(session + 1).copied()
+                })
+                .collect()
+        }
+    }
+}
+
+/// Standard deviation of the last `window` log returns up to each bar, NaN until the window is full
+fn trailing_vol(log_returns: &[f64], window: usize) -> Vec<f64> {
+    if window < 2 {
+        return vec![f64::NAN; log_returns.len()];
+    }
+
+    let mut std_dev = StdDevStream::new(window);
+    log_returns
+        .iter()
+        .map(|&r| if r.is_nan() { f64::NAN } else { std_dev.push(r) })
+        .collect()
+}
+
+/// `(lower, upper)` quantiles of the last `window` forward returns whose horizon had ended by
+/// each bar, NaN until `window` of them have
+fn trailing_quantiles(forward_returns: &[f64], ends: &[Option<usize>], window: usize, lower: f64, upper: f64) -> Vec<(f64, f64)> {
+    let mut completed = VecDeque::with_capacity(window + 1);
+    let mut next = 0;
+
+    (0..forward_returns.len())
+        .map(|i| {
+            // Horizons end in bar order, so the completed returns are always a prefix
+            while ends[next].is_some_and(|end| end <= i) {
+                completed.push_back(forward_returns[next]);
+                if completed.len() > window {
+                    completed.pop_front();
+                }
+                next += 1;
+            }
+
+            if window == 0 || completed.len() < window {
+                return (f64::NAN, f64::NAN);
+            }
+
+            let mut sorted = completed.iter().copied().collect::<Vec<_>>();
+            sorted.sort_by(f64::total_cmp);
+
+            (normalization::quantile(&sorted, lower), normalization::quantile(&sorted, upper))
+        })
+        .collect()
+}
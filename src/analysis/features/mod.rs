@@ -1 +1,4 @@
-pub mod featureset;
\ No newline at end of file
+pub mod featureset;
+pub mod labels;
+pub mod pipeline;
+pub mod sessions;
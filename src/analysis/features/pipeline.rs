@@ -0,0 +1,285 @@
+use std::collections::BTreeMap;
+
+use chrono::NaiveDate;
+use ndarray::{Array2, Axis};
+
+use crate::analysis::features::sessions::{self, SessionStat};
+use crate::analysis::vol_spread::{self, VolSpreadParams, VolSpreadStat};
+use crate::analysis::{indicator::IndicatorRegistry, normalization, volatility::{self, RealizedVolEstimator}};
+use crate::data::{cache::CacheWeight, macro_series::{self, MacroSeries}, series::SeriesView};
+
+/// Where a feature's raw values come from. Windowed sources use the spec's `window`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FeatureSource {
+    /// Output `output` of a registry indicator, built with the spec's `window` as its first
+    /// parameter followed by `params`. A `window` of 0 passes `params` alone.
+    Indicator { indicator: String, params: Vec<f64>, output: String },
+    Open,
+    High,
+    Low,
+    Close,
+    /// Close over the bar's high
+    CloseToHigh,
+    /// Close over the bar's low
+    CloseToLow,
+    /// Close-to-close return over `window` bars
+    Return,
+    /// Annualized realized volatility over `window` bars
+    RealizedVol(RealizedVolEstimator),
+    /// Up-bar over down-bar volume over the last `window` bars
+    VolumeRatio,
+    /// Summary of the latest complete regular session, for intraday bars
+    PriorSession(SessionStat),
+    /// Implied against realized volatility, from the implied vols in [`DailyInputs`]
+    VolSpread { stat: VolSpreadStat, params: VolSpreadParams },
+    /// Cross-asset series from [`DailyInputs`], as known at the bar's close
+    Macro(MacroSeries),
+}
+
+/// Daily observations from outside the price history that sources draw on, each as
+/// `(date, value)` known from that date's close.
+#[derive(Debug, Clone, Default)]
+pub struct DailyInputs {
+    /// Implied vol at the vol spread horizon, e.g. [`vol_spread::vix_implied`] VIX
+    pub implied: Vec<(NaiveDate, f64)>,
+    /// Implied vol at the long tenor, may be empty
+    pub long_implied: Vec<(NaiveDate, f64)>,
+    /// Observations of each fetched macro series
+    pub macros: Vec<(MacroSeries, Vec<(NaiveDate, f64)>)>,
+}
+
+/// Applied to a source's values before they are lagged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transform {
+    None,
+    /// Change from the previous bar
+    Diff,
+    /// Relative change from the previous bar
+    PctChange,
+    /// Natural log
+    Log,
+    /// Over the same bar's close, for price-level features such as moving averages
+    RelativeToClose,
+    /// Z-score against the trailing number of bars, see [`normalization::rolling_zscore_on_values`]
+    RollingZScore(usize),
+}
+
+/// One named column of the feature matrix.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeatureSpec {
+    pub name: String,
+    pub source: FeatureSource,
+    pub window: usize,
+    /// Bars to shift the values by, 1 uses the previous bar's value
+    pub lag: usize,
+    pub transform: Transform,
+}
+
+impl FeatureSpec {
+    pub fn new(name: impl ToString, source: FeatureSource, window: usize) -> Self {
+        Self { name: name.to_string(), source, window, lag: 0, transform: Transform::None }
+    }
+
+    /// Output `output` of the registry indicator `indicator`
+    pub fn indicator(name: impl ToString, indicator: &str, window: usize, params: &[f64], output: &str) -> Self {
+        let source = FeatureSource::Indicator {
+            indicator: indicator.to_string(),
+            params: params.to_vec(),
+            output: output.to_string(),
+        };
+
+        Self::new(name, source, window)
+    }
+
+    pub fn lag(mut self, lag: usize) -> Self {
+        self.lag = lag;
+        self
+    }
+
+    pub fn transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self
+    }
+}
+
+/// Feature rows for the bars where every column is defined.
+#[derive(Debug, Clone, Default)]
+pub struct FeatureMatrix {
+    pub names: Vec<String>,
+    /// One row per entry of `bars`, one column per entry of `names`
+    pub values: Array2<f64>,
+    /// Index into the source series of each row
+    pub bars: Vec<usize>,
+}
+
+impl FeatureMatrix {
+    /// Only the rows whose bar passes `keep`
+    pub fn filter(&self, keep: impl Fn(usize) -> bool) -> Self {
+        let rows = (0..self.bars.len()).filter(|&row| keep(self.bars[row])).collect::<Vec<_>>();
+
+        Self {
+            names: self.names.clone(),
+            values: self.values.select(Axis(0), &rows),
+            bars: rows.iter().map(|&row| self.bars[row]).collect(),
+        }
+    }
+}
+
+impl CacheWeight for FeatureMatrix {
+    fn weight(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.values.len() * std::mem::size_of::<f64>()
+            + self.bars.len() * std::mem::size_of::<usize>()
+            + self.names.iter().map(|name| name.len()).sum::<usize>()
+    }
+}
+
+/// Ordered list of feature specs. The matrix and its column names both come from this one
+/// definition, so adding a feature only means adding a spec.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FeaturePipeline {
+    pub specs: Vec<FeatureSpec>,
+}
+
+impl FeaturePipeline {
+    pub fn new(specs: Vec<FeatureSpec>) -> Self {
+        Self { specs }
+    }
+
+    pub fn with(mut self, spec: FeatureSpec) -> Self {
+        self.specs.push(spec);
+        self
+    }
+
+    pub fn column_names(&self) -> Vec<String> {
+        self.specs.iter().map(|spec| spec.name.clone()).collect()
+    }
+
+    /// [`FeaturePipeline::columns_with`] with no daily inputs, for pipelines built from prices alone
+    pub fn columns(&self, series: SeriesView) -> Result<Vec<Vec<f64>>, String> {
+        self.columns_with(series, &DailyInputs::default())
+    }
+
+    /// Every feature as a column aligned with the bars of `series`, NaN where it isn't defined.
+    /// Fails on an indicator the registry can't build or an output it doesn't have, and on a
+    /// source whose daily input is empty.
+    pub fn columns_with(&self, series: SeriesView, inputs: &DailyInputs) -> Result<Vec<Vec<f64>>, String> {
+        let registry = IndicatorRegistry::new();
+        let periods_per_year = volatility::periods_per_year(series);
+
+        // Indicators with several outputs used by several specs are only computed once, as are
+        // sessions and vol spreads
+        let mut computed: BTreeMap<String, (&'static [&'static str], Vec<Vec<f64>>)> = BTreeMap::new();
+        let mut summaries = None;
+        let mut spreads: Vec<(VolSpreadParams, Vec<vol_spread::VolSpreadPoint>)> = Vec::new();
+
+        self.specs
+            .iter()
+            .map(|spec| {
+                let w = spec.window;
+                let raw = match &spec.source {
+                    FeatureSource::Indicator { indicator, params, output } => {
+                        let params = if w > 0 { [&[w as f64], params.as_slice()].concat() } else { params.clone() };
+                        let built = registry
+                            .build(indicator, &params)
+                            .ok_or_else(|| format!("{}: can't build {indicator} from {params:?}", spec.name))?;
+
+                        let (outputs, values) = computed
+                            .entry(built.key())
+                            .or_insert_with(|| (built.outputs(), built.compute(series)));
+                        let idx = outputs
+                            .iter()
+                            .position(|el| el == output)
+                            .ok_or_else(|| format!("{}: {indicator} has no output {output}", spec.name))?;
+
+                        values[idx].clone()
+                    }
+                    FeatureSource::Open => series.open.to_vec(),
+                    FeatureSource::High => series.high.to_vec(),
+                    FeatureSource::Low => series.low.to_vec(),
+                    FeatureSource::Close => series.close.to_vec(),
+                    FeatureSource::CloseToHigh => series.close.iter().zip(series.high).map(|(c, h)| c / h).collect(),
+                    FeatureSource::CloseToLow => series.close.iter().zip(series.low).map(|(c, l)| c / l).collect(),
+                    FeatureSource::Return => (0..series.len())
+                        .map(|i| if w == 0 || i < w { f64::NAN } else { series.close[i] / series.close[i - w] - 1.0 })
+                        .collect(),
+                    FeatureSource::RealizedVol(estimator) => {
+                        volatility::realized_vol_on_series(series, w, *estimator, periods_per_year)
+                    }
+                    FeatureSource::VolumeRatio => normalization::volume_ratio_on_series(series, w),
+                    FeatureSource::PriorSession(stat) => {
+                        let summaries = summaries.get_or_insert_with(|| sessions::regular_sessions(series));
+                        sessions::prior_session_on_series(series, summaries, *stat)
+                    }
+                    FeatureSource::VolSpread { stat, params } => {
+                        if inputs.implied.is_empty() {
+                            return Err(format!("{}: no implied volatility loaded", spec.name));
+                        }
+
+                        let points = match spreads.iter().position(|(computed, _)| computed == params) {
+                            Some(idx) => &spreads[idx].1,
+                            None => {
+                                let points = vol_spread::vol_spread_on_series(series, &inputs.implied, &inputs.long_implied, *params)
+                                    .map_err(|e| format!("{}: {e}", spec.name))?;
+                                spreads.push((*params, points));
+                                &spreads.last().unwrap().1
+                            }
+                        };
+
+                        points.iter().map(|point| stat.of(point)).collect()
+                    }
+                    FeatureSource::Macro(macro_series) => {
+                        let (_, values) = inputs
+                            .macros
+                            .iter()
+                            .find(|(loaded, _)| loaded == macro_series)
+                            .ok_or_else(|| format!("{}: {} not loaded", spec.name, macro_series.name()))?;
+                        macro_series::align_daily(series, values)
+                    }
+                };
+
+                Ok(shift(&apply(spec.transform, &raw, series.close), spec.lag))
+            })
+            .collect()
+    }
+
+    /// [`FeaturePipeline::compute_with`] with no daily inputs
+    pub fn compute(&self, series: SeriesView) -> Result<FeatureMatrix, String> {
+        self.compute_with(series, &DailyInputs::default())
+    }
+
+    /// Feature matrix over the bars of `series` where no feature is NaN.
+    pub fn compute_with(&self, series: SeriesView, inputs: &DailyInputs) -> Result<FeatureMatrix, String> {
+        let columns = self.columns_with(series, inputs)?;
+
+        let bars = (0..series.len())
+            .filter(|&i| columns.iter().all(|column| !column[i].is_nan()))
+            .collect::<Vec<_>>();
+        let values = Array2::from_shape_fn((bars.len(), columns.len()), |(row, col)| columns[col][bars[row]]);
+
+        Ok(FeatureMatrix { names: self.column_names(), values, bars })
+    }
+}
+
+/// Only uses values up to each bar, so a transformed feature never sees later bars
+fn apply(transform: Transform, values: &[f64], close: &[f64]) -> Vec<f64> {
+    match transform {
+        Transform::None => values.to_vec(),
+        Transform::Log => values.iter().map(|v| v.ln()).collect(),
+        Transform::Diff => (0..values.len())
+            .map(|i| if i == 0 { f64::NAN } else { values[i] - values[i - 1] })
+            .collect(),
+        Transform::PctChange => (0..values.len())
+            .map(|i| if i == 0 { f64::NAN } else { values[i] / values[i - 1] - 1.0 })
+            .collect(),
+        Transform::RelativeToClose => values.iter().zip(close).map(|(v, c)| v / c).collect(),
+        Transform::RollingZScore(window) => normalization::rolling_zscore_on_values(values, window),
+    }
+}
+
+/// Value of `lag` bars earlier at each bar, NaN for the first `lag` bars
+fn shift(values: &[f64], lag: usize) -> Vec<f64> {
+    (0..values.len())
+        .map(|i| if i < lag { f64::NAN } else { values[i - lag] })
+        .collect()
+}
@@ -0,0 +1,180 @@
+use chrono::{NaiveDate, TimeDelta};
+
+use crate::analysis::features::{featureset::PriceDirection, labels};
+use crate::analysis::streaming::vwap_session_day;
+use crate::data::{calendar, series::SeriesView};
+
+/// Regular-hours summary of one trading day of intraday bars.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SessionSummary {
+    pub date: NaiveDate,
+    /// First and last regular-hours bar of the session
+    pub first_bar: usize,
+    pub last_bar: usize,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: i64,
+    /// High-low range over the open
+    pub range: f64,
+    /// Where the close sits in the range, 0 at the low and 1 at the high
+    pub close_location: f64,
+    /// Return over the final hour before the close
+    pub last_hour_return: f64,
+    /// Share of the session's volume traded in its first hour
+    pub opening_hour_volume: f64,
+    /// Share of the session's volume traded in its last hour
+    pub closing_hour_volume: f64,
+    /// Close over the session's VWAP, less one
+    pub close_to_vwap: f64,
+}
+
+/// [`SessionSummary`] field used as a feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionStat {
+    Range,
+    CloseLocation,
+    LastHourReturn,
+    OpeningHourVolume,
+    ClosingHourVolume,
+    CloseToVwap,
+}
+
+impl SessionStat {
+    pub fn of(self, session: &SessionSummary) -> f64 {
+        match self {
+            SessionStat::Range => session.range,
+            SessionStat::CloseLocation => session.close_location,
+            SessionStat::LastHourReturn => session.last_hour_return,
+            SessionStat::OpeningHourVolume => session.opening_hour_volume,
+            SessionStat::ClosingHourVolume => session.closing_hour_volume,
+            SessionStat::CloseToVwap => session.close_to_vwap,
+        }
+    }
+}
+
+/// Move from one session's regular close to the next trading session's regular open.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OvernightGap {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    /// Last regular bar of `from`, whose close the gap is measured from
+    pub from_bar: usize,
+    /// First regular bar of `to`, whose open the gap is measured to
+    pub to_bar: usize,
+    /// Next open over the prior close, less one
+    pub gap: f64,
+    pub direction: PriceDirection,
+}
+
+/// Summaries of the complete regular sessions in `series`, in order. Bars are assigned by the
+/// exchange calendar, so holidays are skipped and early-close days end at 13:00. A session is
+/// complete when its bars reach from the open to the close; days with missing bars at either end
+/// are left out. Daily or longer bars have no sessions.
+pub fn regular_sessions(series: SeriesView) -> Vec<SessionSummary> {
+    let mut sessions = Vec::new();
+    let mut i = 0;
+
+    while i < series.len() {
+        let date = vwap_session_day(series.t[i]);
+        let mut day_end = i;
+        while day_end + 1 < series.len() && vwap_session_day(series.t[day_end + 1]) == date {
+            day_end += 1;
+        }
+
+        if let Some(session) = summarize(series, date, i, day_end) {
+            sessions.push(session);
+        }
+        i = day_end + 1;
+    }
+
+    sessions
+}
+
+/// Summary of the regular-hours bars among `start..=end`, which all fall on `date`
+fn summarize(series: SeriesView, date: NaiveDate, start: usize, end: usize) -> Option<SessionSummary> {
+    let (open_t, close_t) = calendar::regular_session(date)?;
+    let (open_t, close_t) = (open_t.timestamp(), close_t.timestamp());
+
+    let first_bar = (start..=end).find(|&i| series.t[i] >= open_t)?;
+    let last_bar = (first_bar..=end).take_while(|&i| series.t[i] < close_t).last()?;
+    if last_bar == first_bar {
+        return None;
+    }
+
+    let spacing = (first_bar..last_bar).map(|i| series.t[i + 1] - series.t[i]).min()?;
+    if series.t[first_bar] - open_t >= spacing || series.t[last_bar] + spacing < close_t {
+        return None;
+    }
+
+    let bars = first_bar..=last_bar;
+    let high = bars.clone().map(|i| series.high[i]).fold(f64::MIN, f64::max);
+    let low = bars.clone().map(|i| series.low[i]).fold(f64::MAX, f64::min);
+    let (open, close) = (series.open[first_bar], series.close[last_bar]);
+
+    let volume = bars.clone().map(|i| series.vol[i]).sum::<i64>();
+    let volume_where = |keep: &dyn Fn(i64) -> bool| {
+        bars.clone().filter(|&i| keep(series.t[i])).map(|i| series.vol[i]).sum::<i64>() as f64 / volume as f64
+    };
+    let vwap = bars.clone().map(|i| series.vol_weighted[i] * series.vol[i] as f64).sum::<f64>() / volume as f64;
+
+    let hour = TimeDelta::hours(1).num_seconds();
+    let last_hour = bars.clone().find(|&i| series.t[i] >= close_t - hour).unwrap_or(last_bar);
+    let last_hour_base = if last_hour > first_bar { series.close[last_hour - 1] } else { series.open[last_hour] };
+
+    Some(SessionSummary {
+        date,
+        first_bar,
+        last_bar,
+        open,
+        high,
+        low,
+        close,
+        volume,
+        range: (high - low) / open,
+        close_location: if high > low { (close - low) / (high - low) } else { 0.5 },
+        last_hour_return: close / last_hour_base - 1.0,
+        opening_hour_volume: volume_where(&|t| t < open_t + hour),
+        closing_hour_volume: volume_where(&|t| t >= close_t - hour),
+        close_to_vwap: close / vwap - 1.0,
+    })
+}
+
+/// `stat` of the latest complete session as of each bar's close, NaN before the first one. A
+/// session counts from its last regular bar on, so its own closing bar already sees it.
+pub fn prior_session_on_series(series: SeriesView, sessions: &[SessionSummary], stat: SessionStat) -> Vec<f64> {
+    let mut next = 0;
+    let mut value = f64::NAN;
+
+    (0..series.len())
+        .map(|i| {
+            while next < sessions.len() && sessions[next].last_bar <= i {
+                value = stat.of(&sessions[next]);
+                next += 1;
+            }
+            value
+        })
+        .collect()
+}
+
+/// Gaps between consecutive trading sessions of `sessions`, up or down when the gap is beyond
+/// `threshold` as a fraction. Sessions whose next trading day is missing from the data have no gap.
+pub fn overnight_gaps(series: SeriesView, sessions: &[SessionSummary], threshold: f64) -> Vec<OvernightGap> {
+    sessions
+        .windows(2)
+        .filter(|pair| calendar::next_trading_day(pair[0].date) == pair[1].date)
+        .filter_map(|pair| {
+            let gap = series.open[pair[1].first_bar] / series.close[pair[0].last_bar] - 1.0;
+
+            Some(OvernightGap {
+                from: pair[0].date,
+                to: pair[1].date,
+                from_bar: pair[0].last_bar,
+                to_bar: pair[1].first_bar,
+                gap,
+                direction: labels::classify(gap, -threshold, threshold)?,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,355 @@
+use std::f64::consts::PI;
+
+use crate::analysis::{optimize, volatility::{self, RealizedVolEstimator}};
+use crate::data::series::SeriesView;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GarchKind {
+    /// Symmetric GARCH(1,1)
+    Garch,
+    /// GJR-GARCH(1,1), negative returns add `gamma` to the ARCH coefficient
+    GjrGarch,
+}
+
+/// `σ²[t+1] = omega + (alpha + gamma * 1[ε[t] < 0]) * ε[t]² + beta * σ²[t]`, with `ε = r - mu`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GarchParams {
+    pub mu: f64,
+    pub omega: f64,
+    pub alpha: f64,
+    /// Always 0 for [`GarchKind::Garch`]
+    pub gamma: f64,
+    pub beta: f64,
+}
+
+impl GarchParams {
+    /// How much of today's variance shock carries over to tomorrow, < 1 for a stationary model.
+    /// Assumes symmetric returns, so half the shocks are negative.
+    pub fn persistence(&self) -> f64 {
+        self.alpha + self.gamma / 2.0 + self.beta
+    }
+
+    /// Per-bar variance forecasts converge to this
+    pub fn long_run_variance(&self) -> f64 {
+        self.omega / (1.0 - self.persistence())
+    }
+
+    /// Bars for a variance shock to decay by half
+    pub fn half_life(&self) -> f64 {
+        (0.5f64).ln() / self.persistence().ln()
+    }
+
+    /// Conditional variance of each return given everything before it, plus the one-step
+    /// forecast past the last return. The recursion starts from the sample variance.
+    pub fn filter(&self, returns: &[f64]) -> Vec<f64> {
+        self.filter_from(sample_variance(returns), returns)
+    }
+
+    /// Same as [`GarchParams::filter`], starting the recursion from `initial` instead
+    pub fn filter_from(&self, initial: f64, returns: &[f64]) -> Vec<f64> {
+        let mut variance = Vec::with_capacity(returns.len() + 1);
+        variance.push(initial);
+
+        for r in returns {
+            let prev = *variance.last().unwrap();
+            let shock = r - self.mu;
+            let arch = if shock < 0.0 { self.alpha + self.gamma } else { self.alpha };
+
+            variance.push(self.omega + arch * shock * shock + self.beta * prev);
+        }
+
+        variance
+    }
+
+    /// Gaussian log-likelihood of `returns`
+    pub fn log_likelihood(&self, returns: &[f64]) -> f64 {
+        self.filter(returns)
+            .iter()
+            .zip(returns)
+            .map(|(var, r)| -0.5 * ((2.0 * PI).ln() + var.ln() + (r - self.mu).powi(2) / var))
+            .sum()
+    }
+
+    /// Variance forecasts `1..=horizon` bars ahead, starting from the one-step forecast `next`.
+    pub fn forecast_from(&self, next: f64, horizon: usize) -> Vec<f64> {
+        let long_run = self.long_run_variance();
+        let persistence = self.persistence();
+
+        (0..horizon)
+            .map(|k| long_run + persistence.powi(k as i32) * (next - long_run))
+            .collect()
+    }
+
+    /// Annualized volatility expected over the `horizon` bars after the one-step forecast `next`
+    pub fn forecast_volatility_from(&self, next: f64, horizon: usize, periods_per_year: f64) -> f64 {
+        let forecast = self.forecast_from(next, horizon);
+        (forecast.iter().sum::<f64>() / horizon as f64 * periods_per_year).sqrt()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GarchModel {
+    pub kind: GarchKind,
+    pub params: GarchParams,
+    pub log_likelihood: f64,
+    /// Conditional variance of each fitted return plus the one-step forecast, see [`GarchParams::filter`]
+    pub conditional_variance: Vec<f64>,
+    /// `(r - mu) / σ` for each fitted return
+    pub std_residuals: Vec<f64>,
+    pub converged: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct GarchDiagnostics {
+    pub observations: usize,
+    pub log_likelihood: f64,
+    pub aic: f64,
+    pub bic: f64,
+    pub persistence: f64,
+    pub half_life: f64,
+    /// Standardized residuals should have mean 0, variance 1 and, if returns were normal, kurtosis 3
+    pub residual_mean: f64,
+    pub residual_variance: f64,
+    pub residual_kurtosis: f64,
+    /// Ljung-Box Q on squared standardized residuals. Large values mean volatility clustering
+    /// the model did not capture, compare against chi-squared with `ljung_box_lags` dof.
+    pub ljung_box_squared: f64,
+    pub ljung_box_lags: usize,
+}
+
+/// Accuracy of annualized volatility forecasts against what was realized over the same bars.
+#[derive(Debug, Clone)]
+pub struct VolForecastComparison {
+    pub observations: usize,
+    pub mean_forecast: f64,
+    pub mean_realized: f64,
+    /// Mean of forecast - realized
+    pub bias: f64,
+    pub mae: f64,
+    pub rmse: f64,
+    pub correlation: f64,
+}
+
+/// Log returns between consecutive closes, one shorter than the series.
+pub fn log_returns(series: SeriesView) -> Vec<f64> {
+    series.close.windows(2).map(|w| (w[1] / w[0]).ln()).collect()
+}
+
+fn sample_variance(values: &[f64]) -> f64 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (values.len() - 1) as f64
+}
+
+fn logistic(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+fn logit(p: f64) -> f64 {
+    (p / (1.0 - p)).ln()
+}
+
+impl GarchModel {
+    /// Fits all parameters, the mean included, by maximum likelihood with Gaussian innovations.
+    /// Returns `None` for fewer than 50 returns or a degenerate (constant) series.
+    pub fn fit(returns: &[f64], kind: GarchKind) -> Option<Self> {
+        let variance = sample_variance(returns);
+        if returns.len() < 50 || variance <= 0.0 {
+            return None;
+        }
+
+        let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+        let mean_error = (variance / returns.len() as f64).sqrt();
+
+        // Search in an unconstrained space: log omega, logistic alpha/beta/gamma and mu in standard
+        // errors from the sample mean, with non-stationary points rejected
+        let params = |x: &[f64]| GarchParams {
+            mu: mean + x[3] * mean_error,
+            omega: x[0].exp(),
+            alpha: logistic(x[1]),
+            gamma: if kind == GarchKind::GjrGarch { logistic(x[4]) } else { 0.0 },
+            beta: logistic(x[2]),
+        };
+        let objective = |x: &[f64]| {
+            let p = params(x);
+            if p.persistence() >= 1.0 {
+                return f64::NAN;
+            }
+
+            -p.log_likelihood(returns)
+        };
+
+        let (alpha, gamma, beta) = match kind {
+            GarchKind::Garch => (0.05, 0.0, 0.90),
+            GarchKind::GjrGarch => (0.03, 0.06, 0.90),
+        };
+        let omega = variance * (1.0 - alpha - gamma / 2.0 - beta);
+
+        let mut x0 = vec![omega.ln(), logit(alpha), logit(beta), 0.0];
+        if kind == GarchKind::GjrGarch {
+            x0.push(logit(gamma));
+        }
+
+        let minimum = optimize::nelder_mead(objective, &x0, 0.5, 1e-10, 5000);
+        if !minimum.value.is_finite() {
+            return None;
+        }
+
+        let params = params(&minimum.x);
+        let conditional_variance = params.filter(returns);
+        let std_residuals = returns
+            .iter()
+            .zip(&conditional_variance)
+            .map(|(r, var)| (r - params.mu) / var.sqrt())
+            .collect();
+
+        Some(Self {
+            kind,
+            params,
+            log_likelihood: -minimum.value,
+            conditional_variance,
+            std_residuals,
+            converged: minimum.converged,
+        })
+    }
+
+    /// Fits on the close-to-close log returns of `series`, e.g. a stored daily history.
+    pub fn fit_on_series(series: SeriesView, kind: GarchKind) -> Option<Self> {
+        Self::fit(&log_returns(series), kind)
+    }
+
+    /// Per-bar variance forecasts `1..=horizon` bars past the last fitted return.
+    pub fn forecast(&self, horizon: usize) -> Vec<f64> {
+        self.params.forecast_from(*self.conditional_variance.last().unwrap(), horizon)
+    }
+
+    /// Annualized volatility expected over the next `horizon` bars.
+    pub fn forecast_volatility(&self, horizon: usize, periods_per_year: f64) -> f64 {
+        self.params.forecast_volatility_from(*self.conditional_variance.last().unwrap(), horizon, periods_per_year)
+    }
+
+    pub fn diagnostics(&self) -> GarchDiagnostics {
+        let n = self.std_residuals.len();
+        let k = match self.kind {
+            GarchKind::Garch => 4.0,
+            GarchKind::GjrGarch => 5.0,
+        };
+
+        let residual_mean = self.std_residuals.iter().sum::<f64>() / n as f64;
+        let residual_variance = self.std_residuals.iter().map(|z| (z - residual_mean).powi(2)).sum::<f64>() / n as f64;
+        let residual_kurtosis = self.std_residuals.iter().map(|z| (z - residual_mean).powi(4)).sum::<f64>()
+            / n as f64
+            / residual_variance.powi(2);
+
+        let lags = 10.min(n.saturating_sub(1));
+        let squared = self.std_residuals.iter().map(|z| z * z).collect::<Vec<_>>();
+
+        GarchDiagnostics {
+            observations: n,
+            log_likelihood: self.log_likelihood,
+            aic: 2.0 * k - 2.0 * self.log_likelihood,
+            bic: k * (n as f64).ln() - 2.0 * self.log_likelihood,
+            persistence: self.params.persistence(),
+            half_life: self.params.half_life(),
+            residual_mean,
+            residual_variance,
+            residual_kurtosis,
+            ljung_box_squared: ljung_box(&squared, lags),
+            ljung_box_lags: lags,
+        }
+    }
+
+    /// Compares, at every bar, the forecast average volatility over the next `horizon` bars with
+    /// the volatility `estimator` realized over those bars. Uses this model's parameters on
+    /// all of `series`, so it is only out-of-sample for bars after the fitted returns.
+    pub fn compare_with_realized(
+        &self,
+        series: SeriesView,
+        horizon: usize,
+        estimator: RealizedVolEstimator,
+        periods_per_year: f64,
+    ) -> VolForecastComparison {
+        let variance = self.params.filter(&log_returns(series));
+        let realized = volatility::realized_vol_on_series(series, horizon, estimator, periods_per_year);
+
+        // variance[i] is the forecast for the return from bar i to i + 1, known at bar i
+        let pairs = (0..series.len().saturating_sub(horizon))
+            .filter_map(|i| {
+                let forecast = self.params.forecast_volatility_from(variance[i], horizon, periods_per_year);
+
+                (forecast.is_finite() && realized[i + horizon].is_finite()).then_some((forecast, realized[i + horizon]))
+            })
+            .collect::<Vec<_>>();
+
+        let n = pairs.len() as f64;
+        let mean_forecast = pairs.iter().map(|(f, _)| f).sum::<f64>() / n;
+        let mean_realized = pairs.iter().map(|(_, r)| r).sum::<f64>() / n;
+
+        let covariance = pairs.iter().map(|(f, r)| (f - mean_forecast) * (r - mean_realized)).sum::<f64>();
+        let forecast_ss = pairs.iter().map(|(f, _)| (f - mean_forecast).powi(2)).sum::<f64>();
+        let realized_ss = pairs.iter().map(|(_, r)| (r - mean_realized).powi(2)).sum::<f64>();
+
+        VolForecastComparison {
+            observations: pairs.len(),
+            mean_forecast,
+            mean_realized,
+            bias: mean_forecast - mean_realized,
+            mae: pairs.iter().map(|(f, r)| (f - r).abs()).sum::<f64>() / n,
+            rmse: (pairs.iter().map(|(f, r)| (f - r).powi(2)).sum::<f64>() / n).sqrt(),
+            correlation: covariance / (forecast_ss * realized_ss).sqrt(),
+        }
+    }
+}
+
+/// Ljung-Box Q statistic of `values` over the first `lags` autocorrelations.
+fn ljung_box(values: &[f64], lags: usize) -> f64 {
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let denom = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>();
+
+    (1..=lags)
+        .map(|lag| {
+            let autocov = values.iter().zip(&values[lag..]).map(|(a, b)| (a - mean) * (b - mean)).sum::<f64>();
+            (autocov / denom).powi(2) / (n - lag as f64)
+        })
+        .sum::<f64>()
+        * n
+        * (n + 2.0)
+}
+
+/// Annualized one-step-ahead GARCH volatility at every bar. Parameters are fitted on the first
+/// `fit_bars` returns only and then run forward, so values are NaN until bar `fit_bars` and
+/// never depend on later bars.
+pub fn garch_vol_on_series(series: SeriesView, kind: GarchKind, fit_bars: usize) -> Vec<f64> {
+    garch_forecast_on_series(series, kind, fit_bars, &[1]).swap_remove(0)
+}
+
+/// Annualized GARCH volatility expected over each of `horizons` bars ahead of every bar, one
+/// vector per horizon: the model's volatility term structure as known at each close. Fitted and
+/// run forward like [`garch_vol_on_series`], so values are NaN until bar `fit_bars`.
+pub fn garch_forecast_on_series(series: SeriesView, kind: GarchKind, fit_bars: usize, horizons: &[usize]) -> Vec<Vec<f64>> {
+    let mut ret = vec![vec![f64::NAN; series.len()]; horizons.len()];
+
+    let returns = log_returns(series);
+    if returns.len() < fit_bars {
+        return ret;
+    }
+
+    let Some(model) = GarchModel::fit_on_series(series.slice(..=fit_bars), kind) else {
+        return ret;
+    };
+
+    let periods_per_year = volatility::periods_per_year(series.slice(..=fit_bars));
+    let variance = model.params.filter_from(model.conditional_variance[0], &returns);
+
+    for (forecasts, &horizon) in ret.iter_mut().zip(horizons) {
+        for i in fit_bars..series.len() {
+            forecasts[i] = model.params.forecast_volatility_from(variance[i], horizon.max(1), periods_per_year);
+        }
+    }
+
+    ret
+}
@@ -0,0 +1,649 @@
+use std::collections::BTreeMap;
+
+use crate::{analysis::{garch::{self, GarchKind}, momentum, moving_average, streaming::VwapAnchor, trend, volatility::{self, RealizedVolEstimator}, volume}, data::series::SeriesView};
+
+/// A technical indicator computed over a price series.
+///
+/// Every output has one value per bar and is NaN while the indicator is still warming up,
+/// so outputs always line up with the bars they were computed from.
+pub trait Indicator: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Named parameters, e.g. `[("period", 14.0)]`
+    fn params(&self) -> Vec<(&'static str, f64)>;
+
+    /// Names of the series returned by [`Indicator::compute`], in order
+    fn outputs(&self) -> &'static [&'static str];
+
+    /// Number of leading bars for which at least one output is NaN
+    fn warm_up(&self) -> usize;
+
+    fn compute(&self, series: SeriesView) -> Vec<Vec<f64>>;
+
+    /// Whether `output` is a price that can be drawn on top of the price chart
+    fn on_price_scale(&self, _output: &str) -> bool {
+        false
+    }
+
+    /// Registry key, e.g. `sma(20)` or `psar(0.02,0.2)`
+    fn key(&self) -> String {
+        let params = self.params()
+            .iter()
+            .map(|(_, v)| v.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+
+        format!("{}({params})", self.name())
+    }
+
+    /// Flat column names for persistence and feature matrices, e.g. `sma_20` or `psar_0p02_0p2`
+    fn column_names(&self) -> Vec<String> {
+        let mut base = self.name().to_string();
+        for (_, v) in self.params() {
+            base.push_str(&format!("_{}", v.to_string().replace('.', "p")));
+        }
+
+        match self.outputs() {
+            [_] => vec![base],
+            outputs => outputs.iter().map(|out| format!("{base}_{out}")).collect(),
+        }
+    }
+}
+
+pub struct Sma {
+    pub period: usize,
+}
+
+impl Indicator for Sma {
+    fn name(&self) -> &'static str { "sma" }
+    fn params(&self) -> Vec<(&'static str, f64)> { vec![("period", self.period as f64)] }
+    fn outputs(&self) -> &'static [&'static str] { &["sma"] }
+    fn warm_up(&self) -> usize { self.period.saturating_sub(1) }
+    fn on_price_scale(&self, _output: &str) -> bool { true }
+
+    fn compute(&self, series: SeriesView) -> Vec<Vec<f64>> {
+        vec![moving_average::sma_on_series(series, self.period as i32)]
+    }
+}
+
+pub struct Ema {
+    pub period: usize,
+}
+
+impl Indicator for Ema {
+    fn name(&self) -> &'static str { "ema" }
+    fn params(&self) -> Vec<(&'static str, f64)> { vec![("period", self.period as f64)] }
+    fn outputs(&self) -> &'static [&'static str] { &["ema"] }
+    fn warm_up(&self) -> usize { self.period.saturating_sub(1) }
+    fn on_price_scale(&self, _output: &str) -> bool { true }
+
+    fn compute(&self, series: SeriesView) -> Vec<Vec<f64>> {
+        vec![moving_average::ema_on_values(series.close, self.period)]
+    }
+}
+
+pub struct Rsi {
+    pub period: usize,
+}
+
+impl Indicator for Rsi {
+    fn name(&self) -> &'static str { "rsi" }
+    fn params(&self) -> Vec<(&'static str, f64)> { vec![("period", self.period as f64)] }
+    fn outputs(&self) -> &'static [&'static str] { &["rsi"] }
+    fn warm_up(&self) -> usize { self.period }
+
+    fn compute(&self, series: SeriesView) -> Vec<Vec<f64>> {
+        vec![moving_average::rsi_on_series(series, self.period)]
+    }
+}
+
+pub struct Macd {
+    pub short: usize,
+    pub long: usize,
+    pub signal: usize,
+}
+
+impl Indicator for Macd {
+    fn name(&self) -> &'static str { "macd" }
+    fn params(&self) -> Vec<(&'static str, f64)> {
+        vec![("short", self.short as f64), ("long", self.long as f64), ("signal", self.signal as f64)]
+    }
+    fn outputs(&self) -> &'static [&'static str] { &["line", "signal", "histogram"] }
+    fn warm_up(&self) -> usize { self.long.saturating_sub(1) + self.signal.saturating_sub(1) }
+
+    fn compute(&self, series: SeriesView) -> Vec<Vec<f64>> {
+        let macd = moving_average::macd_on_series(series, self.short as i32, self.long as i32, self.signal as i32);
+
+        vec![
+            macd.iter().map(|el| el.macd).collect(),
+            macd.iter().map(|el| el.signal).collect(),
+            macd.iter().map(|el| el.macd - el.signal).collect(),
+        ]
+    }
+}
+
+pub struct Bollinger {
+    pub period: usize,
+    pub num_std: f64,
+}
+
+impl Indicator for Bollinger {
+    fn name(&self) -> &'static str { "bollinger" }
+    fn params(&self) -> Vec<(&'static str, f64)> { vec![("period", self.period as f64), ("num_std", self.num_std)] }
+    fn outputs(&self) -> &'static [&'static str] { &["middle", "upper", "lower", "percent_b", "bandwidth"] }
+    fn warm_up(&self) -> usize { self.period.saturating_sub(1) }
+    fn on_price_scale(&self, output: &str) -> bool { matches!(output, "middle" | "upper" | "lower") }
+
+    fn compute(&self, series: SeriesView) -> Vec<Vec<f64>> {
+        let bands = volatility::bollinger_on_series(series, self.period, self.num_std);
+
+        vec![
+            bands.iter().map(|el| el.middle).collect(),
+            bands.iter().map(|el| el.upper).collect(),
+            bands.iter().map(|el| el.lower).collect(),
+            bands.iter().map(|el| el.percent_b).collect(),
+            bands.iter().map(|el| el.bandwidth).collect(),
+        ]
+    }
+}
+
+pub struct Atr {
+    pub period: usize,
+}
+
+impl Indicator for Atr {
+    fn name(&self) -> &'static str { "atr" }
+    fn params(&self) -> Vec<(&'static str, f64)> { vec![("period", self.period as f64)] }
+    fn outputs(&self) -> &'static [&'static str] { &["atr"] }
+    fn warm_up(&self) -> usize { self.period.saturating_sub(1) }
+
+    fn compute(&self, series: SeriesView) -> Vec<Vec<f64>> {
+        vec![volatility::atr_on_series(series, self.period)]
+    }
+}
+
+pub struct Keltner {
+    pub ema_period: usize,
+    pub atr_period: usize,
+    pub multiplier: f64,
+}
+
+impl Indicator for Keltner {
+    fn name(&self) -> &'static str { "keltner" }
+    fn params(&self) -> Vec<(&'static str, f64)> {
+        vec![("ema_period", self.ema_period as f64), ("atr_period", self.atr_period as f64), ("multiplier", self.multiplier)]
+    }
+    fn outputs(&self) -> &'static [&'static str] { &["middle", "upper", "lower"] }
+    fn warm_up(&self) -> usize { self.ema_period.max(self.atr_period).saturating_sub(1) }
+    fn on_price_scale(&self, _output: &str) -> bool { true }
+
+    fn compute(&self, series: SeriesView) -> Vec<Vec<f64>> {
+        let bands = volatility::keltner_on_series(series, self.ema_period, self.atr_period, self.multiplier);
+
+        vec![
+            bands.iter().map(|el| el.middle).collect(),
+            bands.iter().map(|el| el.upper).collect(),
+            bands.iter().map(|el| el.lower).collect(),
+        ]
+    }
+}
+
+pub struct Adx {
+    pub period: usize,
+}
+
+impl Indicator for Adx {
+    fn name(&self) -> &'static str { "adx" }
+    fn params(&self) -> Vec<(&'static str, f64)> { vec![("period", self.period as f64)] }
+    fn outputs(&self) -> &'static [&'static str] { &["plus_di", "minus_di", "adx"] }
+    fn warm_up(&self) -> usize { (2 * self.period).saturating_sub(1) }
+
+    fn compute(&self, series: SeriesView) -> Vec<Vec<f64>> {
+        let dmi = trend::dmi_on_series(series, self.period);
+
+        vec![
+            dmi.iter().map(|el| el.plus_di).collect(),
+            dmi.iter().map(|el| el.minus_di).collect(),
+            dmi.iter().map(|el| el.adx).collect(),
+        ]
+    }
+}
+
+pub struct Donchian {
+    pub period: usize,
+}
+
+impl Indicator for Donchian {
+    fn name(&self) -> &'static str { "donchian" }
+    fn params(&self) -> Vec<(&'static str, f64)> { vec![("period", self.period as f64)] }
+    fn outputs(&self) -> &'static [&'static str] { &["upper", "middle", "lower"] }
+    fn warm_up(&self) -> usize { self.period.saturating_sub(1) }
+    fn on_price_scale(&self, _output: &str) -> bool { true }
+
+    fn compute(&self, series: SeriesView) -> Vec<Vec<f64>> {
+        let channel = volatility::donchian_on_series(series, self.period);
+
+        vec![
+            channel.iter().map(|el| el.upper).collect(),
+            channel.iter().map(|el| el.middle).collect(),
+            channel.iter().map(|el| el.lower).collect(),
+        ]
+    }
+}
+
+pub struct Psar {
+    pub step: f64,
+    pub max_step: f64,
+}
+
+impl Indicator for Psar {
+    fn name(&self) -> &'static str { "psar" }
+    fn params(&self) -> Vec<(&'static str, f64)> { vec![("step", self.step), ("max_step", self.max_step)] }
+    fn outputs(&self) -> &'static [&'static str] { &["psar"] }
+    fn warm_up(&self) -> usize { 1 }
+    fn on_price_scale(&self, _output: &str) -> bool { true }
+
+    fn compute(&self, series: SeriesView) -> Vec<Vec<f64>> {
+        vec![trend::psar_on_series(series, self.step, self.max_step)]
+    }
+}
+
+pub struct Obv;
+
+impl Indicator for Obv {
+    fn name(&self) -> &'static str { "obv" }
+    fn params(&self) -> Vec<(&'static str, f64)> { Vec::new() }
+    fn outputs(&self) -> &'static [&'static str] { &["obv"] }
+    fn warm_up(&self) -> usize { 0 }
+
+    fn compute(&self, series: SeriesView) -> Vec<Vec<f64>> {
+        vec![volume::obv_on_series(series)]
+    }
+}
+
+pub struct Cmf {
+    pub period: usize,
+}
+
+impl Indicator for Cmf {
+    fn name(&self) -> &'static str { "cmf" }
+    fn params(&self) -> Vec<(&'static str, f64)> { vec![("period", self.period as f64)] }
+    fn outputs(&self) -> &'static [&'static str] { &["cmf"] }
+    fn warm_up(&self) -> usize { self.period.saturating_sub(1) }
+
+    fn compute(&self, series: SeriesView) -> Vec<Vec<f64>> {
+        vec![volume::cmf_on_series(series, self.period)]
+    }
+}
+
+pub struct Mfi {
+    pub period: usize,
+}
+
+impl Indicator for Mfi {
+    fn name(&self) -> &'static str { "mfi" }
+    fn params(&self) -> Vec<(&'static str, f64)> { vec![("period", self.period as f64)] }
+    fn outputs(&self) -> &'static [&'static str] { &["mfi"] }
+    fn warm_up(&self) -> usize { self.period }
+
+    fn compute(&self, series: SeriesView) -> Vec<Vec<f64>> {
+        vec![volume::mfi_on_series(series, self.period)]
+    }
+}
+
+pub struct Stochastic {
+    pub k_period: usize,
+    pub d_period: usize,
+}
+
+impl Indicator for Stochastic {
+    fn name(&self) -> &'static str { "stoch" }
+    fn params(&self) -> Vec<(&'static str, f64)> { vec![("k_period", self.k_period as f64), ("d_period", self.d_period as f64)] }
+    fn outputs(&self) -> &'static [&'static str] { &["k", "d"] }
+    fn warm_up(&self) -> usize { (self.k_period + self.d_period).saturating_sub(2) }
+
+    fn compute(&self, series: SeriesView) -> Vec<Vec<f64>> {
+        let stoch = momentum::stochastic_on_series(series, self.k_period, self.d_period);
+
+        vec![
+            stoch.iter().map(|el| el.k).collect(),
+            stoch.iter().map(|el| el.d).collect(),
+        ]
+    }
+}
+
+pub struct WilliamsR {
+    pub period: usize,
+}
+
+impl Indicator for WilliamsR {
+    fn name(&self) -> &'static str { "willr" }
+    fn params(&self) -> Vec<(&'static str, f64)> { vec![("period", self.period as f64)] }
+    fn outputs(&self) -> &'static [&'static str] { &["willr"] }
+    fn warm_up(&self) -> usize { self.period.saturating_sub(1) }
+
+    fn compute(&self, series: SeriesView) -> Vec<Vec<f64>> {
+        vec![momentum::williams_r_on_series(series, self.period)]
+    }
+}
+
+pub struct Cci {
+    pub period: usize,
+}
+
+impl Indicator for Cci {
+    fn name(&self) -> &'static str { "cci" }
+    fn params(&self) -> Vec<(&'static str, f64)> { vec![("period", self.period as f64)] }
+    fn outputs(&self) -> &'static [&'static str] { &["cci"] }
+    fn warm_up(&self) -> usize { self.period.saturating_sub(1) }
+
+    fn compute(&self, series: SeriesView) -> Vec<Vec<f64>> {
+        vec![momentum::cci_on_series(series, self.period)]
+    }
+}
+
+pub struct Roc {
+    pub period: usize,
+}
+
+impl Indicator for Roc {
+    fn name(&self) -> &'static str { "roc" }
+    fn params(&self) -> Vec<(&'static str, f64)> { vec![("period", self.period as f64)] }
+    fn outputs(&self) -> &'static [&'static str] { &["roc"] }
+    fn warm_up(&self) -> usize { self.period }
+
+    fn compute(&self, series: SeriesView) -> Vec<Vec<f64>> {
+        vec![momentum::roc_on_series(series, self.period)]
+    }
+}
+
+/// Session VWAP (`vwap`) or VWAP anchored at a unix time (`avwap`), with deviation bands.
+pub struct Vwap {
+    pub anchor: VwapAnchor,
+    pub num_std: f64,
+}
+
+impl Indicator for Vwap {
+    fn name(&self) -> &'static str {
+        match self.anchor {
+            VwapAnchor::Session => "vwap",
+            VwapAnchor::Start | VwapAnchor::At(_) => "avwap",
+        }
+    }
+    fn params(&self) -> Vec<(&'static str, f64)> {
+        match self.anchor {
+            VwapAnchor::Session => vec![("num_std", self.num_std)],
+            VwapAnchor::Start => vec![("anchor", 0.0), ("num_std", self.num_std)],
+            VwapAnchor::At(t) => vec![("anchor", t as f64), ("num_std", self.num_std)],
+        }
+    }
+    fn outputs(&self) -> &'static [&'static str] { &["vwap", "upper", "lower"] }
+    fn warm_up(&self) -> usize { 0 }
+    fn on_price_scale(&self, _output: &str) -> bool { true }
+
+    fn compute(&self, series: SeriesView) -> Vec<Vec<f64>> {
+        let bands = volume::vwap_bands_on_series(series, self.anchor, self.num_std);
+
+        vec![
+            bands.iter().map(|el| el.vwap).collect(),
+            bands.iter().map(|el| el.upper).collect(),
+            bands.iter().map(|el| el.lower).collect(),
+        ]
+    }
+}
+
+/// Annualized realized volatility, one indicator name per estimator.
+pub struct RealizedVol {
+    pub period: usize,
+    pub estimator: RealizedVolEstimator,
+}
+
+impl Indicator for RealizedVol {
+    fn name(&self) -> &'static str {
+        match self.estimator {
+            RealizedVolEstimator::CloseToClose => "rvol_cc",
+            RealizedVolEstimator::Parkinson => "rvol_parkinson",
+            RealizedVolEstimator::GarmanKlass => "rvol_gk",
+            RealizedVolEstimator::RogersSatchell => "rvol_rs",
+            RealizedVolEstimator::YangZhang => "rvol_yz",
+        }
+    }
+    fn params(&self) -> Vec<(&'static str, f64)> { vec![("period", self.period as f64)] }
+    fn outputs(&self) -> &'static [&'static str] { &["rvol"] }
+    fn warm_up(&self) -> usize { self.period }
+
+    fn compute(&self, series: SeriesView) -> Vec<Vec<f64>> {
+        let periods_per_year = volatility::periods_per_year(series);
+        vec![volatility::realized_vol_on_series(series, self.period, self.estimator, periods_per_year)]
+    }
+}
+
+/// Annualized GARCH volatility forecast, fitted on the first `fit_bars` returns.
+pub struct Garch {
+    pub kind: GarchKind,
+    pub fit_bars: usize,
+}
+
+impl Indicator for Garch {
+    fn name(&self) -> &'static str {
+        match self.kind {
+            GarchKind::Garch => "garch",
+            GarchKind::GjrGarch => "gjr_garch",
+        }
+    }
+    fn params(&self) -> Vec<(&'static str, f64)> { vec![("fit_bars", self.fit_bars as f64)] }
+    fn outputs(&self) -> &'static [&'static str] { &["vol"] }
+    fn warm_up(&self) -> usize { self.fit_bars }
+
+    fn compute(&self, series: SeriesView) -> Vec<Vec<f64>> {
+        vec![garch::garch_vol_on_series(series, self.kind, self.fit_bars)]
+    }
+}
+
+/// Whole number of bars from a float parameter.
+fn bars(value: f64) -> Option<usize> {
+    (value >= 1.0 && value.fract() == 0.0).then_some(value as usize)
+}
+
+/// Builds an indicator from positional parameters, returning `None` if they don't fit.
+pub type IndicatorFactory = fn(&[f64]) -> Option<Box<dyn Indicator>>;
+
+/// Configured indicators keyed by name and parameters, plus the factories used to create new ones.
+pub struct IndicatorRegistry {
+    factories: BTreeMap<&'static str, IndicatorFactory>,
+    indicators: BTreeMap<String, Box<dyn Indicator>>,
+}
+
+impl Default for IndicatorRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IndicatorRegistry {
+    /// Registry that knows every built-in indicator but has none configured.
+    pub fn new() -> Self {
+        let mut factories: BTreeMap<&'static str, IndicatorFactory> = BTreeMap::new();
+        factories.insert("sma", |p| match p {
+            [period] => Some(Box::new(Sma { period: bars(*period)? })),
+            _ => None,
+        });
+        factories.insert("ema", |p| match p {
+            [period] => Some(Box::new(Ema { period: bars(*period)? })),
+            _ => None,
+        });
+        factories.insert("rsi", |p| match p {
+            [period] => Some(Box::new(Rsi { period: bars(*period)? })),
+            _ => None,
+        });
+        factories.insert("macd", |p| match p {
+            [short, long, signal] if short < long => {
+                Some(Box::new(Macd { short: bars(*short)?, long: bars(*long)?, signal: bars(*signal)? }))
+            }
+            _ => None,
+        });
+        factories.insert("bollinger", |p| match p {
+            [period, num_std] if *num_std > 0.0 => Some(Box::new(Bollinger { period: bars(*period)?, num_std: *num_std })),
+            _ => None,
+        });
+        factories.insert("atr", |p| match p {
+            [period] => Some(Box::new(Atr { period: bars(*period)? })),
+            _ => None,
+        });
+        factories.insert("keltner", |p| match p {
+            [ema_period, atr_period, multiplier] if *multiplier > 0.0 => Some(Box::new(Keltner {
+                ema_period: bars(*ema_period)?,
+                atr_period: bars(*atr_period)?,
+                multiplier: *multiplier,
+            })),
+            _ => None,
+        });
+        factories.insert("adx", |p| match p {
+            [period] => Some(Box::new(Adx { period: bars(*period)? })),
+            _ => None,
+        });
+        factories.insert("donchian", |p| match p {
+            [period] => Some(Box::new(Donchian { period: bars(*period)? })),
+            _ => None,
+        });
+        factories.insert("psar", |p| match p {
+            [step, max_step] if 0.0 < *step && step <= max_step => Some(Box::new(Psar { step: *step, max_step: *max_step })),
+            _ => None,
+        });
+        factories.insert("obv", |p| match p {
+            [] => Some(Box::new(Obv)),
+            _ => None,
+        });
+        factories.insert("cmf", |p| match p {
+            [period] => Some(Box::new(Cmf { period: bars(*period)? })),
+            _ => None,
+        });
+        factories.insert("mfi", |p| match p {
+            [period] => Some(Box::new(Mfi { period: bars(*period)? })),
+            _ => None,
+        });
+        factories.insert("stoch", |p| match p {
+            [k_period, d_period] => Some(Box::new(Stochastic { k_period: bars(*k_period)?, d_period: bars(*d_period)? })),
+            _ => None,
+        });
+        factories.insert("willr", |p| match p {
+            [period] => Some(Box::new(WilliamsR { period: bars(*period)? })),
+            _ => None,
+        });
+        factories.insert("cci", |p| match p {
+            [period] => Some(Box::new(Cci { period: bars(*period)? })),
+            _ => None,
+        });
+        factories.insert("roc", |p| match p {
+            [period] => Some(Box::new(Roc { period: bars(*period)? })),
+            _ => None,
+        });
+        factories.insert("vwap", |p| match p {
+            [num_std] if *num_std >= 0.0 => Some(Box::new(Vwap { anchor: VwapAnchor::Session, num_std: *num_std })),
+            _ => None,
+        });
+        factories.insert("avwap", |p| match p {
+            [anchor, num_std] if *num_std >= 0.0 => Some(Box::new(Vwap { anchor: VwapAnchor::At(*anchor as i64), num_std: *num_std })),
+            _ => None,
+        });
+        factories.insert("rvol_cc", |p| match p {
+            [period] => Some(Box::new(RealizedVol { period: bars(*period)?, estimator: RealizedVolEstimator::CloseToClose })),
+            _ => None,
+        });
+        factories.insert("rvol_parkinson", |p| match p {
+            [period] => Some(Box::new(RealizedVol { period: bars(*period)?, estimator: RealizedVolEstimator::Parkinson })),
+            _ => None,
+        });
+        factories.insert("rvol_gk", |p| match p {
+            [period] => Some(Box::new(RealizedVol { period: bars(*period)?, estimator: RealizedVolEstimator::GarmanKlass })),
+            _ => None,
+        });
+        factories.insert("rvol_rs", |p| match p {
+            [period] => Some(Box::new(RealizedVol { period: bars(*period)?, estimator: RealizedVolEstimator::RogersSatchell })),
+            _ => None,
+        });
+        factories.insert("rvol_yz", |p| match p {
+            [period] => Some(Box::new(RealizedVol { period: bars(*period)?, estimator: RealizedVolEstimator::YangZhang })),
+            _ => None,
+        });
+        factories.insert("garch", |p| match p {
+            [fit_bars] => Some(Box::new(Garch { kind: GarchKind::Garch, fit_bars: bars(*fit_bars)? })),
+            _ => None,
+        });
+        factories.insert("gjr_garch", |p| match p {
+            [fit_bars] => Some(Box::new(Garch { kind: GarchKind::GjrGarch, fit_bars: bars(*fit_bars)? })),
+            _ => None,
+        });
+
+        Self { factories, indicators: BTreeMap::new() }
+    }
+
+    /// The indicators we chart and persist by default.
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::new();
+        registry.configure("sma", &[5.0]);
+        registry.configure("sma", &[20.0]);
+        registry.configure("sma", &[50.0]);
+        registry.configure("sma", &[100.0]);
+        registry.configure("rsi", &[14.0]);
+        registry.configure("macd", &[12.0, 26.0, 9.0]);
+        registry.configure("bollinger", &[20.0, 2.0]);
+        registry.configure("atr", &[14.0]);
+        registry.configure("keltner", &[20.0, 10.0, 2.0]);
+        registry.configure("adx", &[14.0]);
+        registry.configure("donchian", &[20.0]);
+        registry.configure("psar", &[0.02, 0.2]);
+        registry.configure("obv", &[]);
+        registry.configure("cmf", &[20.0]);
+        registry.configure("mfi", &[14.0]);
+        registry.configure("stoch", &[14.0, 3.0]);
+        registry.configure("willr", &[14.0]);
+        registry.configure("cci", &[20.0]);
+        registry.configure("roc", &[12.0]);
+        registry.configure("vwap", &[2.0]);
+        registry.configure("rvol_cc", &[20.0]);
+        registry.configure("rvol_yz", &[20.0]);
+        registry.configure("garch", &[500.0]);
+
+        registry
+    }
+
+    pub fn add_factory(&mut self, name: &'static str, factory: IndicatorFactory) {
+        self.factories.insert(name, factory);
+    }
+
+    /// Creates an indicator by name without registering it.
+    pub fn build(&self, name: &str, params: &[f64]) -> Option<Box<dyn Indicator>> {
+        self.factories.get(name)?(params)
+    }
+
+    /// Creates and registers an indicator by name, returning its key.
+    pub fn configure(&mut self, name: &str, params: &[f64]) -> Option<String> {
+        let indicator = self.build(name, params)?;
+        Some(self.register(indicator))
+    }
+
+    pub fn register(&mut self, indicator: Box<dyn Indicator>) -> String {
+        let key = indicator.key();
+        self.indicators.insert(key.clone(), indicator);
+
+        key
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Box<dyn Indicator>> {
+        self.indicators.remove(key)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&dyn Indicator> {
+        self.indicators.get(key).map(|el| el.as_ref())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &dyn Indicator)> {
+        self.indicators.iter().map(|(k, v)| (k.as_str(), v.as_ref()))
+    }
+
+    /// Names of every indicator type that can be passed to [`IndicatorRegistry::configure`]
+    pub fn available(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.factories.keys().copied()
+    }
+}
@@ -1,5 +1,15 @@
 pub mod moving_average;
+pub mod indicator;
+pub mod streaming;
 pub mod volatility;
+pub mod garch;
+pub mod surface;
+pub mod vol_spread;
+pub mod options;
+pub mod optimize;
+pub mod trend;
+pub mod momentum;
+pub mod volume;
 pub mod normalization;
 pub mod strategies;
 pub mod features;
\ No newline at end of file
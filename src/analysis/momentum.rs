@@ -0,0 +1,153 @@
+use crate::{analysis::streaming::{RollingExtremes, RollingSum, SmaStream}, data::series::SeriesView};
+
+#[derive(Debug, Clone, Copy)]
+pub struct StochasticPoint {
+    pub k: f64,
+    pub d: f64,
+}
+
+/// Stochastic oscillator. %K is NaN for the first `k_period - 1` bars and %D, the SMA of %K over
+/// `d_period` bars, for a further `d_period - 1`.
+pub fn stochastic_on_series(series: SeriesView, k_period: usize, d_period: usize) -> Vec<StochasticPoint> {
+    let mut extremes = RollingExtremes::new(k_period);
+    let mut d = SmaStream::new(d_period.max(1));
+
+    (0..series.len())
+        .map(|i| {
+            extremes.push(series.high[i], series.low[i]);
+            if !extremes.is_full() {
+                return StochasticPoint { k: f64::NAN, d: f64::NAN };
+            }
+
+            let (highest, lowest) = (extremes.highest(), extremes.lowest());
+            let k = if highest == lowest {
+                50.0
+            } else {
+                100.0 * (series.close[i] - lowest) / (highest - lowest)
+            };
+
+            // %D only averages bars that have a %K
+            let d = d.push(k);
+            StochasticPoint { k, d: if d_period == 0 { f64::NAN } else { d } }
+        })
+        .collect()
+}
+
+/// Williams %R, from 0 at the period high down to -100 at the period low. NaN for the first `period - 1` bars.
+pub fn williams_r_on_series(series: SeriesView, period: usize) -> Vec<f64> {
+    let mut extremes = RollingExtremes::new(period);
+
+    (0..series.len())
+        .map(|i| {
+            extremes.push(series.high[i], series.low[i]);
+            if !extremes.is_full() {
+                return f64::NAN;
+            }
+
+            let (highest, lowest) = (extremes.highest(), extremes.lowest());
+            if highest == lowest {
+                -50.0
+            } else {
+                -100.0 * (highest - series.close[i]) / (highest - lowest)
+            }
+        })
+        .collect()
+}
+
+/// Commodity Channel Index over the typical price. NaN for the first `period - 1` bars.
+///
+/// The mean deviation is taken around each window's own mean, so it can't be kept as a plain
+/// rolling sum. Instead the window's typical prices are kept in Fenwick trees of counts and
+/// sums over the sorted distinct prices: the deviations below the mean add up to
+/// `count_below * mean - sum_below` and those above to `sum_above - count_above * mean`, which
+/// makes each bar O(log n).
+pub fn cci_on_series(series: SeriesView, period: usize) -> Vec<f64> {
+    let n = series.len();
+    let typical_price = (0..n)
+        .map(|i| (series.high[i] + series.low[i] + series.close[i]) / 3.0)
+        .collect::<Vec<_>>();
+
+    let mut prices = typical_price.clone();
+    prices.sort_by(f64::total_cmp);
+    prices.dedup();
+    let rank = |price: f64| prices.partition_point(|&p| p < price);
+
+    let mut counts = Fenwick::new(prices.len());
+    let mut sums = Fenwick::new(prices.len());
+    let mut sum = RollingSum::new(period.max(1));
+
+    (0..n)
+        .map(|i| {
+            sum.push(typical_price[i]);
+            counts.add(rank(typical_price[i]), 1.0);
+            sums.add(rank(typical_price[i]), typical_price[i]);
+            if period > 0 && i >= period {
+                let dropped = typical_price[i - period];
+                counts.add(rank(dropped), -1.0);
+                sums.add(rank(dropped), -dropped);
+            }
+
+            if period == 0 || i + 1 < period {
+                return f64::NAN;
+            }
+
+            let mean = sum.sum / period as f64;
+            // Window prices strictly below the mean
+            let below = rank(mean);
+            let (count_below, sum_below) = (counts.prefix(below), sums.prefix(below));
+            let (count_above, sum_above) = (period as f64 - count_below, sum.sum - sum_below);
+            let mean_deviation = ((count_below * mean - sum_below) + (sum_above - count_above * mean)).max(0.0) / period as f64;
+
+            // A flat window only leaves rounding error behind
+            if mean_deviation <= 1e-12 * mean.abs() {
+                0.0
+            } else {
+                (typical_price[i] - mean) / (0.015 * mean_deviation)
+            }
+        })
+        .collect()
+}
+
+/// Fenwick (binary indexed) tree of running totals over ranks `0..len`.
+struct Fenwick {
+    tree: Vec<f64>,
+}
+
+impl Fenwick {
+    fn new(len: usize) -> Self {
+        Self { tree: vec![0.0; len + 1] }
+    }
+
+    fn add(&mut self, rank: usize, value: f64) {
+        let mut i = rank + 1;
+        while i < self.tree.len() {
+            self.tree[i] += value;
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    /// Total over ranks `0..rank`
+    fn prefix(&self, rank: usize) -> f64 {
+        let mut i = rank;
+        let mut total = 0.0;
+        while i > 0 {
+            total += self.tree[i];
+            i -= i & i.wrapping_neg();
+        }
+        total
+    }
+}
+
+/// Percent change of the close over `period` bars. NaN for the first `period` bars.
+pub fn roc_on_series(series: SeriesView, period: usize) -> Vec<f64> {
+    (0..series.len())
+        .map(|i| {
+            if period == 0 || i < period {
+                return f64::NAN;
+            }
+
+            let base = series.close[i - period];
+            100.0 * (series.close[i] - base) / base
+        })
+        .collect()
+}
@@ -1,167 +1,96 @@
 
-use crate::data::types::TickerDataframe;
+use crate::{analysis::streaming::SmaStream, data::series::SeriesView};
 
+#[derive(Debug, Clone, Copy)]
 pub struct MacdPoint {
-    pub signal: f32,
-    pub macd: f32,
+    pub signal: f64,
+    pub macd: f64,
 }
 
+/// Simple moving average of closes. NaN for the first `sma_period_days - 1` bars.
+pub fn sma_on_series(series: SeriesView, sma_period_days: i32) -> Vec<f64> {
+    let mut sma = SmaStream::new(sma_period_days.max(1) as usize);
 
-pub fn sma_on_series(series: &Vec<TickerDataframe>, sma_period_days: i32) -> Vec<f32> {
-    let mut ret = Vec::new();
+    series.close
+        .iter()
+        .map(|&close| sma.push(close))
+        .collect()
+}
 
-    let recip_k = 1.0 / sma_period_days as f32;
-    let n = series.len();
-    let frames = n / sma_period_days as usize;
-
-    // if series.len() < sma_period_days as usize {
-    //     let sum: f32 = series.iter()
-    //         .map(|el| el.close)
-    //         .sum();
-    //     ret.push(
-    //         sum / sma_period_days as f32
-    //     );
-    //     return ret;
-    // }
-
-    for i in 0..sma_period_days - 1 {
-        let sum: f32 = series.iter().take(i as usize).map(|el| el.close).sum();
-        ret.push(
-            sum / i as f32
-        )
-    }
-    if series.len() < (sma_period_days - 1) as usize {
+/// Exponential moving average seeded with the SMA of the first `period` values.
+/// NaN until `period` values are available.
+pub fn ema_on_values(values: &[f64], period: usize) -> Vec<f64> {
+    let n = values.len();
+    let mut ret = vec![f64::NAN; n];
+
+    if period == 0 || n < period {
         return ret;
     }
-    series.iter()
-        .as_slice()
-        .windows(sma_period_days as usize)
-        .for_each(|el| {
-            let window_sum: f32 = el.iter().map(|v| v.close).sum();
-            ret.push(window_sum * recip_k);
-        });
 
-    ret
-}
+    let smoothing = 2.0 / (period as f64 + 1.0);
 
-pub fn macd_on_series(series: &Vec<TickerDataframe>, period_short: i32, period_long: i32) -> Vec<MacdPoint> {
-    let smoothing_short = 2.0 / (period_short as f32 + 1.0);
-    let smoothing_long = 2.0 / (period_long as f32 + 1.0);
-    let smoothing_signal = 2.0 / (9.0 + 1.0);
+    let mut ema = values.iter().take(period).sum::<f64>() / period as f64;
+    ret[period - 1] = ema;
 
-    let n = series.len();
-    let mut macd_series = Vec::with_capacity(n);
-
-    // Fill initial values with zeros (or some default value)
-    for i in 0..period_long as usize - 1 {
-        macd_series.push(MacdPoint { macd: 0.0, signal: 0.0 });
-    }
-
-    if n < period_long as usize {
-        return macd_series;
+    for i in period..n {
+        ema = (values[i] * smoothing) + (ema * (1.0 - smoothing));
+        ret[i] = ema;
     }
 
-    // Calculate initial SMA for short and long periods
-    let initial_sma_short: f32 = series.iter()
-        .take(period_short as usize)
-        .map(|df| df.close)
-        .sum::<f32>() / period_short as f32;
-
-    let initial_sma_long: f32 = series.iter()
-        .take(period_long as usize)
-        .map(|df| df.close)
-        .sum::<f32>() / period_long as f32;
-
-    // EMA vectors for short and long
-    let mut ema_short = Vec::with_capacity(n);
-    let mut ema_long = Vec::with_capacity(n);
-
-    for i in 0..n {
-        let current_price = series[i].close;
-
-        // Short EMA
-        let ema_short_value = if i < period_short as usize - 1 {
-            0.0
-        } else if i == period_short as usize - 1 {
-            initial_sma_short
-        } else {
-            (current_price * smoothing_short) + (ema_short[i - 1] * (1.0 - smoothing_short))
-        };
-        ema_short.push(ema_short_value);
-
-        // Long EMA
-        let ema_long_value = if i < period_long as usize - 1 {
-            0.0
-        } else if i == period_long as usize - 1 {
-            initial_sma_long
-        } else {
-            (current_price * smoothing_long) + (ema_long[i - 1] * (1.0 - smoothing_long))
-        };
-        ema_long.push(ema_long_value);
-    }
+    ret
+}
 
-    // Calculate MACD line
-    let mut macd_line = Vec::with_capacity(n);
-    for i in 0..n {
-        let macd_value = ema_short[i] - ema_long[i];
-        macd_line.push(macd_value);
-    }
+/// MACD line and signal line. The MACD line is NaN for the first `period_long - 1` bars and the
+/// signal line for a further `period_signal - 1` bars.
+pub fn macd_on_series(series: SeriesView, period_short: i32, period_long: i32, period_signal: i32) -> Vec<MacdPoint> {
+    let n = series.len();
 
-    // Calculate initial signal line as SMA of first 9 MACD values
-    let mut signal_line = Vec::with_capacity(n);
-    for i in 0..8 {
-        signal_line.push(0.0);
-    }
-    if n >= 9 {
-        let initial_signal = macd_line.iter().take(9).sum::<f32>() / 9.0;
-        signal_line.push(initial_signal);
+    let ema_short = ema_on_values(series.close, period_short.max(1) as usize);
+    let ema_long = ema_on_values(series.close, period_long.max(1) as usize);
 
-        for i in 9..n {
-            let signal_val = (macd_line[i] * smoothing_signal) + (signal_line[i - 1] * (1.0 - smoothing_signal));
-            signal_line.push(signal_val);
-        }
-    }
+    let macd_line = (0..n)
+        .map(|i| ema_short[i] - ema_long[i])
+        .collect::<Vec<_>>();
 
-    // Fill output vector to match input length
-    for i in 0..n {
-        let macd = macd_line[i];
-        let signal = if i < signal_line.len() { signal_line[i] } else { 0.0 };
-        if i >= macd_series.len() {
-            macd_series.push(MacdPoint { macd, signal });
-        } else {
-            macd_series[i] = MacdPoint { macd, signal };
-        }
-    }
+    // Signal line only starts once the MACD line itself is valid
+    let first_valid = (period_long.max(1) as usize - 1).min(n);
+    let mut signal_line = vec![f64::NAN; first_valid];
+    signal_line.extend(ema_on_values(&macd_line[first_valid..], period_signal.max(1) as usize));
 
-    macd_series
+    (0..n)
+        .map(|i| MacdPoint { macd: macd_line[i], signal: signal_line[i] })
+        .collect()
 }
 
-pub fn rsi_on_series(series: &Vec<TickerDataframe>, period: usize) -> Vec<f32> {
-    let mut rsis = Vec::new();
-    
-    if series.len() < 2 {
+/// Wilder RSI. NaN for the first `period` bars.
+pub fn rsi_on_series(series: SeriesView, period: usize) -> Vec<f64> {
+    let n = series.len();
+    let mut rsis = Vec::with_capacity(n);
+
+    if period == 0 || n <= period {
+        rsis.resize(n, f64::NAN);
         return rsis;
     }
 
     // Calculate price differences
-    let price_changes: Vec<f32> = series.windows(2)
-        .map(|window| window[1].close - window[0].close)
+    let price_changes: Vec<f64> = series.close.windows(2)
+        .map(|window| window[1] - window[0])
         .collect();
 
     // Initialize with first possible value
     let mut avg_gain = price_changes.iter()
         .take(period)
         .map(|&x| if x > 0.0 { x } else { 0.0 })
-        .sum::<f32>() / period as f32;
+        .sum::<f64>() / period as f64;
 
     let mut avg_loss = price_changes.iter()
         .take(period)
         .map(|&x| if x < 0.0 { -x } else { 0.0 })
-        .sum::<f32>() / period as f32;
+        .sum::<f64>() / period as f64;
 
-    // Fill initial values with None
+    // Not enough price changes yet
     for _ in 0..period {
-        rsis.push(50.0);
+        rsis.push(f64::NAN);
     }
 
     // Calculate first RSI
@@ -177,8 +106,8 @@ pub fn rsi_on_series(series: &Vec<TickerDataframe>, period: usize) -> Vec<f32> {
         let gain = if price_changes[i] > 0.0 { price_changes[i] } else { 0.0 };
         let loss = if price_changes[i] < 0.0 { -price_changes[i] } else { 0.0 };
 
-        avg_gain = (avg_gain * (period as f32 - 1.0) + gain) / period as f32;
-        avg_loss = (avg_loss * (period as f32 - 1.0) + loss) / period as f32;
+        avg_gain = (avg_gain * (period as f64 - 1.0) + gain) / period as f64;
+        avg_loss = (avg_loss * (period as f64 - 1.0) + loss) / period as f64;
 
         if avg_loss == 0.0 {
             rsis.push(100.0);
@@ -1,24 +1,171 @@
-use crate::data::types::TickerDataframe;
+use serde::{Deserialize, Serialize};
 
-pub fn volume_ratio(data: &Vec<TickerDataframe>) -> f32 {
+use crate::analysis::{features::pipeline::FeatureMatrix, streaming::RollingMoments};
+use crate::data::series::SeriesView;
+
+pub fn volume_ratio(data: SeriesView) -> f64 {
     let mut up_total = 0;
     let mut down_total = 0;
 
-    for el in data {
-        if el.open > el.close {
-            down_total += el.vol;
+    for i in 0..data.len() {
+        if data.open[i] > data.close[i] {
+            down_total += data.vol[i];
         } else {
-            up_total += el.vol;
+            up_total += data.vol[i];
         }
     }
 
-    up_total as f32 / down_total as f32
+    up_total as f64 / down_total as f64
+}
+
+/// [`volume_ratio`] over a rolling window of `period` bars, NaN until the first window is full.
+pub fn volume_ratio_on_series(series: SeriesView, period: usize) -> Vec<f64> {
+    let volumes = |i: usize| if series.open[i] > series.close[i] { (0, series.vol[i]) } else { (series.vol[i], 0) };
+    let (mut up_total, mut down_total) = (0, 0);
+
+    (0..series.len())
+        .map(|i| {
+            let (up, down) = volumes(i);
+            up_total += up;
+            down_total += down;
+
+            if i >= period {
+                let (up, down) = volumes(i - period);
+                up_total -= up;
+                down_total -= down;
+            }
+
+            if period == 0 || i + 1 < period { f64::NAN } else { up_total as f64 / down_total as f64 }
+        })
+        .collect()
+}
+
+/// How a [`ColumnScaler`] centers and scales a feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Scaling {
+    /// Mean and standard deviation
+    ZScore,
+    /// Into `[0, 1]` over the fitted range, values outside it extend past the bounds
+    MinMax,
+    /// Median and interquartile range, unaffected by a few extreme values
+    Robust,
+}
+
+/// `(x - center) / scale` with `center` and `scale` taken from the data it was fit on.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ColumnScaler {
+    pub scaling: Scaling,
+    pub center: f64,
+    pub scale: f64,
+}
+
+impl ColumnScaler {
+    /// Fits on the non-NaN `values`. A constant or empty column gets a scale of 1 so it
+    /// transforms to finite values.
+    pub fn fit(scaling: Scaling, values: &[f64]) -> Self {
+        let mut sorted = values.iter().copied().filter(|v| !v.is_nan()).collect::<Vec<_>>();
+        sorted.sort_by(f64::total_cmp);
+
+        let (center, scale) = match (scaling, sorted.len()) {
+            (_, 0) => (0.0, 1.0),
+            (Scaling::ZScore, n) => {
+                let mean = sorted.iter().sum::<f64>() / n as f64;
+                let variance = sorted.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n.max(2) - 1) as f64;
+                (mean, variance.sqrt())
+            }
+            (Scaling::MinMax, n) => (sorted[0], sorted[n - 1] - sorted[0]),
+            (Scaling::Robust, _) => (quantile(&sorted, 0.5), quantile(&sorted, 0.75) - quantile(&sorted, 0.25)),
+        };
+
+        let scale = if scale > 0.0 && scale.is_finite() { scale } else { 1.0 };
+
+        Self { scaling, center, scale }
+    }
+
+    pub fn transform(&self, value: f64) -> f64 {
+        (value - self.center) / self.scale
+    }
+
+    pub fn inverse(&self, value: f64) -> f64 {
+        value * self.scale + self.center
+    }
+}
+
+/// Per-column scalers fit on a training [`FeatureMatrix`] and applied unchanged to test and
+/// live features, so no row is scaled with statistics from bars after it. Serialize it next to
+/// the model it was trained with.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Normalizer {
+    pub names: Vec<String>,
+    pub columns: Vec<ColumnScaler>,
 }
 
-pub fn normalize_series(series: Vec<f32>, cur_value: f32) -> Vec<f32> {
-    let ret = series.iter().map(|el| {
-        el / cur_value
-    }).collect::<Vec<f32>>();
+impl Normalizer {
+    pub fn fit(scaling: Scaling, train: &FeatureMatrix) -> Self {
+        let columns = train.values
+            .columns()
+            .into_iter()
+            .map(|column| ColumnScaler::fit(scaling, &column.to_vec()))
+            .collect();
 
-    ret
-}
\ No newline at end of file
+        Self { names: train.names.clone(), columns }
+    }
+
+    /// Scales `features` with the fitted state. Fails if its columns differ from the ones
+    /// the normalizer was fit on.
+    pub fn transform(&self, features: &FeatureMatrix) -> Result<FeatureMatrix, String> {
+        if features.names != self.names {
+            return Err(format!("normalizer was fit on {:?}, got {:?}", self.names, features.names));
+        }
+
+        let mut scaled = features.clone();
+        for (mut column, scaler) in scaled.values.columns_mut().into_iter().zip(&self.columns) {
+            column.mapv_inplace(|v| scaler.transform(v));
+        }
+
+        Ok(scaled)
+    }
+
+    pub fn fit_transform(scaling: Scaling, train: &FeatureMatrix) -> (Self, FeatureMatrix) {
+        let normalizer = Self::fit(scaling, train);
+        let scaled = normalizer.transform(train).expect("fit on the same columns");
+
+        (normalizer, scaled)
+    }
+}
+
+/// Z-score of each value against the last `window` values up to and including it, so it
+/// only uses data available at that bar. NaN until the window holds `window` non-NaN values.
+pub fn rolling_zscore_on_values(values: &[f64], window: usize) -> Vec<f64> {
+    let mut moments = RollingMoments::unbounded();
+
+    (0..values.len())
+        .map(|i| {
+            if !values[i].is_nan() {
+                moments.push(values[i]);
+            }
+            if i >= window && !values[i - window].is_nan() {
+                moments.pop();
+            }
+
+            if window < 2 || moments.len() < window {
+                return f64::NAN;
+            }
+
+            let std = moments.sample_variance().sqrt();
+            if std > 0.0 { (values[i] - moments.mean()) / std } else { 0.0 }
+        })
+        .collect()
+}
+
+/// Linearly interpolated `q` quantile of ascending `sorted` values
+pub(crate) fn quantile(sorted: &[f64], q: f64) -> f64 {
+    if sorted.is_empty() {
+        return f64::NAN;
+    }
+
+    let pos = q.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let (lo, hi) = (pos.floor() as usize, pos.ceil() as usize);
+
+    sorted[lo] + (sorted[hi] - sorted[lo]) * (pos - lo as f64)
+}
@@ -0,0 +1,95 @@
+/// Result of a derivative-free minimization.
+#[derive(Debug, Clone)]
+pub struct Minimum {
+    pub x: Vec<f64>,
+    pub value: f64,
+    pub iterations: usize,
+    pub converged: bool,
+}
+
+/// Nelder-Mead simplex minimization of `f` starting from `x0`, with an initial simplex spread
+/// of `step` along each axis. Stops once the spread of function values across the simplex falls
+/// below `tolerance` or after `max_iter` iterations.
+///
+/// Non-finite values are treated as +inf, so `f` can reject infeasible points by returning NaN.
+pub fn nelder_mead(mut f: impl FnMut(&[f64]) -> f64, x0: &[f64], step: f64, tolerance: f64, max_iter: usize) -> Minimum {
+    let dim = x0.len();
+    let mut eval = |x: &[f64]| {
+        let value = f(x);
+        if value.is_finite() { value } else { f64::INFINITY }
+    };
+
+    let mut simplex = vec![x0.to_vec()];
+    for i in 0..dim {
+        let mut vertex = x0.to_vec();
+        vertex[i] += if vertex[i] == 0.0 { step } else { step * vertex[i].abs().max(1.0) };
+        simplex.push(vertex);
+    }
+    let mut values = simplex.iter().map(|x| eval(x)).collect::<Vec<_>>();
+
+    let mut iterations = 0;
+    let mut converged = false;
+
+    while iterations < max_iter {
+        iterations += 1;
+
+        let mut order = (0..=dim).collect::<Vec<_>>();
+        order.sort_by(|&a, &b| values[a].total_cmp(&values[b]));
+        simplex = order.iter().map(|&i| simplex[i].clone()).collect();
+        values = order.iter().map(|&i| values[i]).collect();
+
+        if (values[dim] - values[0]).abs() <= tolerance * (values[0].abs() + tolerance) {
+            converged = true;
+            break;
+        }
+
+        let centroid = (0..dim)
+            .map(|j| simplex[..dim].iter().map(|x| x[j]).sum::<f64>() / dim as f64)
+            .collect::<Vec<_>>();
+        let towards = |coef: f64| {
+            centroid.iter().zip(&simplex[dim]).map(|(c, w)| c + coef * (w - c)).collect::<Vec<_>>()
+        };
+
+        let reflected = towards(-1.0);
+        let reflected_value = eval(&reflected);
+
+        if reflected_value < values[0] {
+            let expanded = towards(-2.0);
+            let expanded_value = eval(&expanded);
+
+            if expanded_value < reflected_value {
+                simplex[dim] = expanded;
+                values[dim] = expanded_value;
+            } else {
+                simplex[dim] = reflected;
+                values[dim] = reflected_value;
+            }
+            continue;
+        }
+
+        if reflected_value < values[dim - 1] {
+            simplex[dim] = reflected;
+            values[dim] = reflected_value;
+            continue;
+        }
+
+        let contracted = if reflected_value < values[dim] { towards(-0.5) } else { towards(0.5) };
+        let contracted_value = eval(&contracted);
+
+        if contracted_value < values[dim].min(reflected_value) {
+            simplex[dim] = contracted;
+            values[dim] = contracted_value;
+            continue;
+        }
+
+        // Shrink everything towards the best vertex
+        for i in 1..=dim {
+            simplex[i] = simplex[0].iter().zip(&simplex[i]).map(|(b, x)| b + 0.5 * (x - b)).collect();
+            values[i] = eval(&simplex[i]);
+        }
+    }
+
+    let best = (0..=dim).min_by(|&a, &b| values[a].total_cmp(&values[b])).unwrap();
+
+    Minimum { x: simplex[best].clone(), value: values[best], iterations, converged }
+}
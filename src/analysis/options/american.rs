@@ -0,0 +1,225 @@
+use chrono::NaiveDate;
+
+use crate::analysis::options::black_scholes::{self, Greeks};
+use crate::analysis::surface::DAYS_PER_YEAR;
+use crate::data::types::{OptionType, StockOption};
+
+/// Steps used when a caller doesn't need to trade speed for accuracy
+pub const DEFAULT_STEPS: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeMethod {
+    /// Cox-Ross-Rubinstein binomial tree
+    Binomial,
+    /// Kamrad-Ritchken style trinomial tree on log price, smoother in the number of steps
+    Trinomial,
+}
+
+/// Cash dividend going ex `time` years from now.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dividend {
+    pub time: f64,
+    pub amount: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct AmericanOption {
+    pub option_type: OptionType,
+    pub spot: f64,
+    pub strike: f64,
+    /// Years
+    pub time_to_expiry: f64,
+    /// Continuously compounded risk-free rate
+    pub rate: f64,
+    /// Continuous dividend yield, on top of any discrete `dividends`
+    pub dividend_yield: f64,
+    pub dividends: Vec<Dividend>,
+}
+
+impl AmericanOption {
+    /// Option on a stock quoted on `quote_date`, with time in calendar days / 365 and
+    /// `dividends` given as ex-dates and cash amounts.
+    pub fn from_quote(option: &StockOption, quote_date: NaiveDate, spot: f64, rate: f64, dividends: &[(NaiveDate, f64)]) -> Self {
+        let years = |date: NaiveDate| (date - quote_date).num_days() as f64 / DAYS_PER_YEAR;
+
+        Self {
+            option_type: option.option_type,
+            spot,
+            strike: option.strike_price,
+            time_to_expiry: years(option.expiry_date),
+            rate,
+            dividend_yield: 0.0,
+            dividends: dividends
+                .iter()
+                .map(|&(date, amount)| Dividend { time: years(date), amount })
+                .collect(),
+        }
+    }
+
+    fn payoff(&self, underlying: f64) -> f64 {
+        match self.option_type {
+            OptionType::Call => (underlying - self.strike).max(0.0),
+            OptionType::Put => (self.strike - underlying).max(0.0),
+        }
+    }
+
+    /// Present value at time `t` of the dividends going ex after `t` and before expiry
+    fn dividends_after(&self, t: f64) -> f64 {
+        self.dividends
+            .iter()
+            .filter(|d| d.time > t && d.time <= self.time_to_expiry)
+            .map(|d| d.amount * (-self.rate * (d.time - t)).exp())
+            .sum()
+    }
+
+    /// Price on a recombining tree of `steps` steps. Discrete dividends use the escrowed model:
+    /// the tree is built on spot less the PV of dividends, which are added back at every node
+    /// before checking early exercise.
+    pub fn price(&self, vol: f64, steps: usize, method: TreeMethod) -> f64 {
+        if self.time_to_expiry <= 0.0 {
+            return self.payoff(self.spot);
+        }
+
+        self.roll_back(vol, steps, method).0
+    }
+
+    /// Rolls the tree back to the root. Also returns `(underlying, value)` of the first three
+    /// nodes of a single step, step 2 of a binomial tree or step 1 of a trinomial one, and
+    /// the time of that step, which the greeks are read off of. Steps are raised to the fewest
+    /// that still roll back through that step.
+    fn roll_back(&self, vol: f64, steps: usize, method: TreeMethod) -> (f64, [(f64, f64); 3], f64) {
+        let steps = match method {
+            TreeMethod::Binomial => steps.max(3),
+            TreeMethod::Trinomial => steps.max(2),
+        };
+        let dt = self.time_to_expiry / steps as f64;
+        let discount = (-self.rate * dt).exp();
+        let base = self.spot - self.dividends_after(0.0);
+        let vol = vol.max(1e-6);
+
+        let mut early = [(0.0, 0.0); 3];
+
+        match method {
+            TreeMethod::Binomial => {
+                let up = (vol * dt.sqrt()).exp();
+                let down = 1.0 / up;
+                let p = ((((self.rate - self.dividend_yield) * dt).exp() - down) / (up - down)).clamp(0.0, 1.0);
+
+                let node = |i: usize, j: usize| base * up.powi(j as i32) * down.powi((i - j) as i32);
+
+                let mut values = (0..=steps)
+                    .map(|j| self.payoff(node(steps, j) + self.dividends_after(self.time_to_expiry)))
+                    .collect::<Vec<_>>();
+
+                for i in (0..steps).rev() {
+                    let pending = self.dividends_after(i as f64 * dt);
+
+                    for j in 0..=i {
+                        let continuation = discount * (p * values[j + 1] + (1.0 - p) * values[j]);
+                        values[j] = continuation.max(self.payoff(node(i, j) + pending));
+                    }
+
+                    if i == 2 {
+                        early = [0, 1, 2].map(|j| (node(2, j) + pending, values[j]));
+                    }
+                }
+
+                (values[0], early, 2.0 * dt)
+            }
+            TreeMethod::Trinomial => {
+                let dx = vol * (3.0 * dt).sqrt();
+                let drift = self.rate - self.dividend_yield - vol * vol / 2.0;
+                let spread = (vol * vol * dt + drift * drift * dt * dt) / (dx * dx);
+
+                let pu = (0.5 * (spread + drift * dt / dx)).clamp(0.0, 1.0);
+                let pd = (0.5 * (spread - drift * dt / dx)).clamp(0.0, 1.0);
+                let pm = 1.0 - pu - pd;
+
+                // Node j of step i sits at base * exp((j - i) * dx)
+                let node = |i: usize, j: usize| base * ((j as f64 - i as f64) * dx).exp();
+
+                let mut values = (0..=2 * steps)
+                    .map(|j| self.payoff(node(steps, j) + self.dividends_after(self.time_to_expiry)))
+                    .collect::<Vec<_>>();
+
+                for i in (0..steps).rev() {
+                    let pending = self.dividends_after(i as f64 * dt);
+
+                    for j in 0..=2 * i {
+                        let continuation = discount * (pu * values[j + 2] + pm * values[j + 1] + pd * values[j]);
+                        values[j] = continuation.max(self.payoff(node(i, j) + pending));
+                    }
+
+                    if i == 1 {
+                        early = [0, 1, 2].map(|j| (node(1, j) + pending, values[j]));
+                    }
+                }
+
+                (values[0], early, dt)
+            }
+        }
+    }
+
+    /// Delta, gamma and theta are read off the tree's early nodes, vega and rho are bump and
+    /// reprice. Same units as [`black_scholes::Greeks`].
+    pub fn greeks(&self, vol: f64, steps: usize, method: TreeMethod) -> Greeks {
+        if self.time_to_expiry <= 0.0 {
+            return Greeks::default();
+        }
+
+        let (price, [(s_down, v_down), (s_mid, v_mid), (s_up, v_up)], elapsed) = self.roll_back(vol, steps, method);
+
+        let slope_up = (v_up - v_mid) / (s_up - s_mid);
+        let slope_down = (v_mid - v_down) / (s_mid - s_down);
+
+        let bumped = |change: &dyn Fn(&mut AmericanOption)| {
+            let mut option = self.clone();
+            change(&mut option);
+            option
+        };
+
+        let dv = 0.01;
+        let dr = 1e-3;
+        let vol_down = (vol - dv).max(1e-6);
+
+        Greeks {
+            delta: (v_up - v_down) / (s_up - s_down),
+            gamma: (slope_up - slope_down) / ((s_up - s_down) / 2.0),
+            theta: (v_mid - price) / elapsed / DAYS_PER_YEAR,
+            vega: (self.price(vol + dv, steps, method) - self.price(vol_down, steps, method)) / (vol + dv - vol_down) / 100.0,
+            rho: (bumped(&|o| o.rate += dr).price(vol, steps, method) - bumped(&|o| o.rate -= dr).price(vol, steps, method)) / (2.0 * dr) / 100.0,
+        }
+    }
+
+    /// Volatility at which the tree reproduces `price`, `None` if the price is below
+    /// immediate exercise value or above the underlying (calls) / strike (puts).
+    pub fn implied_vol(&self, price: f64, steps: usize, method: TreeMethod) -> Option<f64> {
+        let upper = match self.option_type {
+            OptionType::Call => self.spot,
+            OptionType::Put => self.strike,
+        };
+        let vega = |vol: f64| (self.price(vol + 1e-4, steps, method) - self.price(vol - 1e-4, steps, method)) / 2e-4;
+
+        black_scholes::implied_vol_by(|vol| self.price(vol, steps, method), vega, (self.payoff(self.spot), upper), price, self.time_to_expiry)
+    }
+}
+
+/// American implied vol of every row from its mid price, `None` for rows without a two-sided
+/// quote or a price outside the no-arbitrage bounds.
+pub fn chain_implied_vols(
+    options: &[StockOption],
+    quote_date: NaiveDate,
+    spot: f64,
+    rate: f64,
+    dividend_yield: f64,
+    dividends: &[(NaiveDate, f64)],
+    method: TreeMethod,
+) -> Vec<Option<f64>> {
+    options
+        .iter()
+        .map(|option| {
+            let american = AmericanOption { dividend_yield, ..AmericanOption::from_quote(option, quote_date, spot, rate, dividends) };
+            black_scholes::mid_price(option).and_then(|mid| american.implied_vol(mid, DEFAULT_STEPS, method))
+        })
+        .collect()
+}
@@ -0,0 +1,253 @@
+use chrono::NaiveDate;
+
+use crate::analysis::surface::DAYS_PER_YEAR;
+use crate::data::types::{OptionType, StockOption};
+
+/// Edges up to this are rounding in the bounds, not arbitrage
+const MIN_EDGE: f64 = 1e-9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExerciseStyle {
+    /// Parity must hold exactly
+    European,
+    /// Early exercise turns parity into a band: `S - D - K <= C - P <= S - K e^(-rT)`
+    American,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViolationKind {
+    PutCallParity,
+    /// A lower strike call (higher strike put) offered below a bid on the other strike
+    VerticalMonotonicity,
+    /// A vertical spread bid above the distance between its strikes
+    VerticalWidth,
+    ButterflyConvexity,
+    /// A longer dated option offered below a bid on the nearer one at the same strike
+    CalendarOrdering,
+}
+
+/// Market inputs the checks need on top of the chain itself.
+#[derive(Debug, Clone)]
+pub struct ScanParams {
+    pub quote_date: NaiveDate,
+    pub spot: f64,
+    /// Continuously compounded risk-free rate
+    pub rate: f64,
+    /// Continuous dividend yield
+    pub dividend_yield: f64,
+    /// Cash dividends as ex-date and amount
+    pub dividends: Vec<(NaiveDate, f64)>,
+    pub style: ExerciseStyle,
+    /// Commission and fees per option contract, per share of the contract
+    pub cost_per_contract: f64,
+    /// Commission and fees per share of the underlying, paid on the stock leg of parity trades
+    pub cost_per_share: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ArbitrageViolation {
+    pub kind: ViolationKind,
+    pub expiry: NaiveDate,
+    /// Contract ids of the legs
+    pub contracts: Vec<String>,
+    /// Trade that captures the violation, e.g. `buy 100C, sell 105C`
+    pub trade: String,
+    /// Locked-in profit per share at quoted bid/ask, before costs
+    pub edge: f64,
+    pub edge_after_costs: f64,
+}
+
+/// Bid and ask of a contract, `None` unless it can be bought
+fn quote(option: &StockOption) -> Option<(f64, f64)> {
+    (option.ask > 0.0 && option.ask >= option.bid).then_some((option.bid.max(0.0), option.ask))
+}
+
+fn label(option: &StockOption) -> String {
+    let kind = match option.option_type {
+        OptionType::Call => "C",
+        OptionType::Put => "P",
+    };
+
+    format!("{}{kind} {}", option.strike_price, option.expiry_date)
+}
+
+impl ScanParams {
+    fn years(&self, date: NaiveDate) -> f64 {
+        (date - self.quote_date).num_days() as f64 / DAYS_PER_YEAR
+    }
+
+    /// PV of cash dividends going ex before `expiry`
+    fn dividends_before(&self, expiry: NaiveDate) -> f64 {
+        self.dividends
+            .iter()
+            .filter(|(date, _)| *date > self.quote_date && *date <= expiry)
+            .map(|(date, amount)| amount * (-self.rate * self.years(*date)).exp())
+            .sum()
+    }
+
+    /// `stock` is the shares of the underlying traded per share of the option legs, positive to buy
+    fn violation(&self, kind: ViolationKind, legs: &[(f64, &StockOption)], stock: f64, edge: f64) -> Option<ArbitrageViolation> {
+        if edge <= MIN_EDGE {
+            return None;
+        }
+
+        let contracts = legs.iter().map(|(size, _)| size.abs()).sum::<f64>();
+        let mut trade = legs
+            .iter()
+            .map(|(size, option)| {
+                let side = if *size > 0.0 { "buy" } else { "sell" };
+                if (size.abs() - 1.0).abs() < 1e-9 {
+                    format!("{side} {}", label(option))
+                } else {
+                    format!("{side} {:.3} {}", size.abs(), label(option))
+                }
+            })
+            .collect::<Vec<_>>();
+        if stock != 0.0 {
+            trade.push(String::from(if stock > 0.0 { "buy stock" } else { "short stock" }));
+        }
+
+        Some(ArbitrageViolation {
+            kind,
+            expiry: legs[0].1.expiry_date,
+            contracts: legs.iter().map(|(_, option)| option.contract_id.clone()).collect(),
+            trade: trade.join(", "),
+            edge,
+            edge_after_costs: edge - contracts * self.cost_per_contract - stock.abs() * self.cost_per_share,
+        })
+    }
+}
+
+/// Scans one chain snapshot for static arbitrage at executable prices: buys at the ask and
+/// sells at the bid. Only adjacent strikes and expiries are compared, so each violation is
+/// reported once rather than for every pair it implies. Sorted by edge after costs.
+pub fn scan_chain(options: &[StockOption], params: &ScanParams) -> Vec<ArbitrageViolation> {
+    let mut quoted = options
+        .iter()
+        .filter(|option| option.expiry_date > params.quote_date && quote(option).is_some())
+        .collect::<Vec<_>>();
+    quoted.sort_by(|a, b| {
+        a.expiry_date
+            .cmp(&b.expiry_date)
+            .then((a.option_type as u8).cmp(&(b.option_type as u8)))
+            .then(a.strike_price.total_cmp(&b.strike_price))
+    });
+
+    let mut violations = Vec::new();
+
+    for expiry in quoted.chunk_by(|a, b| a.expiry_date == b.expiry_date) {
+        put_call_parity(expiry, params, &mut violations);
+
+        for strikes in expiry.chunk_by(|a, b| a.option_type == b.option_type) {
+            verticals(strikes, params, &mut violations);
+            butterflies(strikes, params, &mut violations);
+        }
+    }
+
+    calendars(&quoted, params, &mut violations);
+
+    violations.sort_by(|a, b| b.edge_after_costs.total_cmp(&a.edge_after_costs));
+    violations
+}
+
+fn put_call_parity(expiry: &[&StockOption], params: &ScanParams, violations: &mut Vec<ArbitrageViolation>) {
+    let t = params.years(expiry[0].expiry_date);
+    let dividends = params.dividends_before(expiry[0].expiry_date);
+    let stock = params.spot * (-params.dividend_yield * t).exp() - dividends;
+
+    for call in expiry.iter().filter(|o| o.option_type == OptionType::Call) {
+        let Some(put) = expiry.iter().find(|o| o.option_type == OptionType::Put && o.strike_price == call.strike_price) else {
+            continue;
+        };
+
+        let (call_bid, call_ask) = quote(call).unwrap();
+        let (put_bid, put_ask) = quote(put).unwrap();
+        let strike = call.strike_price;
+        let discounted_strike = strike * (-params.rate * t).exp();
+
+        // Synthetic stock is too rich: sell call, buy put, buy stock. The short call can be
+        // exercised early, before the dividends are collected, so American parity only
+        // guarantees the stock without them
+        let upper = match params.style {
+            ExerciseStyle::European => stock - discounted_strike,
+            ExerciseStyle::American => params.spot - discounted_strike,
+        };
+        let upper_edge = call_bid - put_ask - upper;
+
+        // Synthetic stock is too cheap: buy call, sell put, short stock. Early exercise of the
+        // short put can force paying the strike early, so American parity only guarantees
+        // the undiscounted strike
+        let lower = match params.style {
+            ExerciseStyle::European => upper,
+            ExerciseStyle::American => stock - strike,
+        };
+        let lower_edge = lower - (call_ask - put_bid);
+
+        violations.extend(params.violation(ViolationKind::PutCallParity, &[(-1.0, call), (1.0, put)], 1.0, upper_edge));
+        violations.extend(params.violation(ViolationKind::PutCallParity, &[(1.0, call), (-1.0, put)], -1.0, lower_edge));
+    }
+}
+
+/// `strikes` are one expiry and type, sorted by strike
+fn verticals(strikes: &[&StockOption], params: &ScanParams, violations: &mut Vec<ArbitrageViolation>) {
+    for pair in strikes.windows(2) {
+        let (low, high) = (pair[0], pair[1]);
+        let (low_bid, low_ask) = quote(low).unwrap();
+        let (high_bid, high_ask) = quote(high).unwrap();
+        let width = high.strike_price - low.strike_price;
+
+        match low.option_type {
+            OptionType::Call => {
+                violations.extend(params.violation(ViolationKind::VerticalMonotonicity, &[(1.0, low), (-1.0, high)], 0.0, high_bid - low_ask));
+                violations.extend(params.violation(ViolationKind::VerticalWidth, &[(-1.0, low), (1.0, high)], 0.0, low_bid - high_ask - width));
+            }
+            OptionType::Put => {
+                violations.extend(params.violation(ViolationKind::VerticalMonotonicity, &[(1.0, high), (-1.0, low)], 0.0, low_bid - high_ask));
+                violations.extend(params.violation(ViolationKind::VerticalWidth, &[(-1.0, high), (1.0, low)], 0.0, high_bid - low_ask - width));
+            }
+        }
+    }
+}
+
+/// Price must be convex in strike: a long butterfly can never cost less than nothing
+fn butterflies(strikes: &[&StockOption], params: &ScanParams, violations: &mut Vec<ArbitrageViolation>) {
+    for wing in strikes.windows(3) {
+        let (low, mid, high) = (wing[0], wing[1], wing[2]);
+        let weight = (high.strike_price - mid.strike_price) / (high.strike_price - low.strike_price);
+
+        let cost = weight * quote(low).unwrap().1 + (1.0 - weight) * quote(high).unwrap().1 - quote(mid).unwrap().0;
+
+        violations.extend(params.violation(
+            ViolationKind::ButterflyConvexity,
+            &[(weight, low), (-1.0, mid), (1.0 - weight, high)],
+            0.0,
+            -cost,
+        ));
+    }
+}
+
+/// American options are worth at least as much with more time left. For European ones this only
+/// holds for calls without dividends, so puts are skipped.
+fn calendars(quoted: &[&StockOption], params: &ScanParams, violations: &mut Vec<ArbitrageViolation>) {
+    let mut by_strike = quoted.to_vec();
+    by_strike.sort_by(|a, b| {
+        (a.option_type as u8)
+            .cmp(&(b.option_type as u8))
+            .then(a.strike_price.total_cmp(&b.strike_price))
+            .then(a.expiry_date.cmp(&b.expiry_date))
+    });
+
+    for pair in by_strike.windows(2) {
+        let (near, far) = (pair[0], pair[1]);
+        if near.option_type != far.option_type || near.strike_price != far.strike_price || near.expiry_date == far.expiry_date {
+            continue;
+        }
+        let pays_dividends = params.dividend_yield > 0.0 || !params.dividends.is_empty();
+        if params.style == ExerciseStyle::European && (near.option_type == OptionType::Put || pays_dividends) {
+            continue;
+        }
+
+        let edge = quote(near).unwrap().0 - quote(far).unwrap().1;
+        violations.extend(params.violation(ViolationKind::CalendarOrdering, &[(1.0, far), (-1.0, near)], 0.0, edge));
+    }
+}
@@ -0,0 +1,325 @@
+use std::f64::consts::PI;
+
+use chrono::NaiveDate;
+
+use crate::analysis::surface::DAYS_PER_YEAR;
+use crate::data::types::{OptionType, StockOption};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PricingModel {
+    /// Underlying is a spot price paying a continuous dividend yield
+    BlackScholes,
+    /// Underlying is a forward or futures price, dividends are already in it
+    Black76,
+}
+
+/// Everything needed to price a European option besides its volatility.
+#[derive(Debug, Clone, Copy)]
+pub struct EuropeanOption {
+    pub option_type: OptionType,
+    pub model: PricingModel,
+    /// Spot for Black-Scholes, forward for Black-76
+    pub underlying: f64,
+    pub strike: f64,
+    /// Years
+    pub time_to_expiry: f64,
+    /// Continuously compounded risk-free rate
+    pub rate: f64,
+    /// Continuous dividend yield, ignored by Black-76
+    pub dividend_yield: f64,
+}
+
+/// Sensitivities in the units vendors quote them in: theta per calendar day, vega and rho per
+/// percentage point.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Greeks {
+    pub delta: f64,
+    pub gamma: f64,
+    pub theta: f64,
+    pub vega: f64,
+    pub rho: f64,
+}
+
+/// Standard normal density
+pub fn norm_pdf(x: f64) -> f64 {
+    (-0.5 * x * x).exp() / (2.0 * PI).sqrt()
+}
+
+/// Standard normal CDF, accurate to double precision (Hart's algorithm as given by West, 2005).
+pub fn norm_cdf(x: f64) -> f64 {
+    let z = x.abs();
+
+    let tail = if z > 37.0 {
+        0.0
+    } else {
+        let e = (-z * z / 2.0).exp();
+
+        if z < 7.071_067_811_865_47 {
+            let num = ((((((0.035_262_496_599_891_1 * z + 0.700_383_064_443_688) * z + 6.373_962_203_531_65) * z
+                + 33.912_866_078_383) * z + 112.079_291_497_871) * z + 221.213_596_169_931) * z
+                + 220.206_867_912_376)
+                * e;
+            let den = ((((((0.088_388_347_648_318_4 * z + 1.755_667_163_182_64) * z + 16.064_177_579_207) * z
+                + 86.780_732_202_946_1) * z + 296.564_248_779_674) * z + 637.333_633_378_831) * z
+                + 793.826_512_519_948) * z
+                + 440.413_735_824_752;
+
+            num / den
+        } else {
+            let b = z + 1.0 / (z + 2.0 / (z + 3.0 / (z + 4.0 / (z + 0.65))));
+            e / b / 2.506_628_274_631
+        }
+    };
+
+    if x > 0.0 { 1.0 - tail } else { tail }
+}
+
+impl EuropeanOption {
+    /// Option on a stock quoted on `quote_date`, with time to expiry in calendar days / 365.
+    pub fn from_quote(option: &StockOption, quote_date: NaiveDate, spot: f64, rate: f64, dividend_yield: f64) -> Self {
+        Self {
+            option_type: option.option_type,
+            model: PricingModel::BlackScholes,
+            underlying: spot,
+            strike: option.strike_price,
+            time_to_expiry: (option.expiry_date - quote_date).num_days() as f64 / DAYS_PER_YEAR,
+            rate,
+            dividend_yield,
+        }
+    }
+
+    /// Cost of carry: what holding the underlying earns over the risk-free rate is `carry - rate`
+    fn carry(&self) -> f64 {
+        match self.model {
+            PricingModel::BlackScholes => self.rate - self.dividend_yield,
+            PricingModel::Black76 => 0.0,
+        }
+    }
+
+    fn d1_d2(&self, vol: f64) -> (f64, f64) {
+        let vol_sqrt_t = vol * self.time_to_expiry.sqrt();
+        let d1 = ((self.underlying / self.strike).ln() + (self.carry() + vol * vol / 2.0) * self.time_to_expiry) / vol_sqrt_t;
+
+        (d1, d1 - vol_sqrt_t)
+    }
+
+    /// Value if exercised immediately
+    pub fn intrinsic(&self) -> f64 {
+        match self.option_type {
+            OptionType::Call => (self.underlying - self.strike).max(0.0),
+            OptionType::Put => (self.strike - self.underlying).max(0.0),
+        }
+    }
+
+    /// No-arbitrage price range, the price at zero and at infinite volatility
+    pub fn price_bounds(&self) -> (f64, f64) {
+        let t = self.time_to_expiry;
+        let underlying = self.underlying * ((self.carry() - self.rate) * t).exp();
+        let strike = self.strike * (-self.rate * t).exp();
+
+        match self.option_type {
+            OptionType::Call => ((underlying - strike).max(0.0), underlying),
+            OptionType::Put => ((strike - underlying).max(0.0), strike),
+        }
+    }
+
+    pub fn price(&self, vol: f64) -> f64 {
+        if self.time_to_expiry <= 0.0 {
+            return self.intrinsic();
+        }
+        if vol <= 0.0 {
+            return self.price_bounds().0;
+        }
+
+        let t = self.time_to_expiry;
+        let (d1, d2) = self.d1_d2(vol);
+        let underlying = self.underlying * ((self.carry() - self.rate) * t).exp();
+        let strike = self.strike * (-self.rate * t).exp();
+
+        match self.option_type {
+            OptionType::Call => underlying * norm_cdf(d1) - strike * norm_cdf(d2),
+            OptionType::Put => strike * norm_cdf(-d2) - underlying * norm_cdf(-d1),
+        }
+    }
+
+    /// Price sensitivity to volatility per unit of vol, as used by the IV solver
+    fn raw_vega(&self, vol: f64) -> f64 {
+        let t = self.time_to_expiry;
+        let (d1, _) = self.d1_d2(vol);
+
+        self.underlying * ((self.carry() - self.rate) * t).exp() * norm_pdf(d1) * t.sqrt()
+    }
+
+    pub fn greeks(&self, vol: f64) -> Greeks {
+        if self.time_to_expiry <= 0.0 || vol <= 0.0 {
+            return Greeks::default();
+        }
+
+        let t = self.time_to_expiry;
+        let (d1, d2) = self.d1_d2(vol);
+        let carry_discount = ((self.carry() - self.rate) * t).exp();
+        let discount = (-self.rate * t).exp();
+        let decay = -self.underlying * carry_discount * norm_pdf(d1) * vol / (2.0 * t.sqrt());
+        let carry_gain = (self.carry() - self.rate) * self.underlying * carry_discount;
+
+        let (delta, theta, rho) = match self.option_type {
+            OptionType::Call => (
+                carry_discount * norm_cdf(d1),
+                decay - carry_gain * norm_cdf(d1) - self.rate * self.strike * discount * norm_cdf(d2),
+                self.strike * t * discount * norm_cdf(d2),
+            ),
+            OptionType::Put => (
+                carry_discount * (norm_cdf(d1) - 1.0),
+                decay + carry_gain * norm_cdf(-d1) + self.rate * self.strike * discount * norm_cdf(-d2),
+                -self.strike * t * discount * norm_cdf(-d2),
+            ),
+        };
+
+        // A forward doesn't move with the rate, only the discounting does
+        let rho = match self.model {
+            PricingModel::BlackScholes => rho,
+            PricingModel::Black76 => -t * self.price(vol),
+        };
+
+        Greeks {
+            delta,
+            gamma: carry_discount * norm_pdf(d1) / (self.underlying * vol * t.sqrt()),
+            theta: theta / DAYS_PER_YEAR,
+            vega: self.raw_vega(vol) / 100.0,
+            rho: rho / 100.0,
+        }
+    }
+
+    /// Volatility that reproduces `price`. Newton steps, falling back to bisection whenever a
+    /// step would leave the bracket known to contain the root. `None` if the price is outside
+    /// the no-arbitrage bounds.
+    pub fn implied_vol(&self, price: f64) -> Option<f64> {
+        implied_vol_by(|vol| self.price(vol), |vol| self.raw_vega(vol), self.price_bounds(), price, self.time_to_expiry)
+    }
+}
+
+/// Newton/bisection implied volatility solver shared by every pricer. `vega` may return NaN if
+/// the model has no analytic vega, in which case only bisection steps are taken.
+pub fn implied_vol_by(
+    price_at: impl Fn(f64) -> f64,
+    vega: impl Fn(f64) -> f64,
+    (lower, upper): (f64, f64),
+    price: f64,
+    time_to_expiry: f64,
+) -> Option<f64> {
+    const TOLERANCE: f64 = 1e-10;
+    const MAX_VOL: f64 = 10.0;
+
+    if time_to_expiry <= 0.0 || !price.is_finite() || price <= lower || price >= upper {
+        return None;
+    }
+
+    let (mut lo, mut hi) = (1e-6, MAX_VOL);
+    if price_at(hi) < price {
+        return None;
+    }
+
+    // Brenner-Subrahmanyam at-the-money approximation as a starting point
+    let mut vol = (price / upper * (2.0 * PI / time_to_expiry).sqrt()).clamp(0.01, 2.0);
+
+    for _ in 0..100 {
+        let diff = price_at(vol) - price;
+        if diff.abs() < TOLERANCE {
+            return Some(vol);
+        }
+
+        if diff > 0.0 {
+            hi = vol;
+        } else {
+            lo = vol;
+        }
+
+        let step = diff / vega(vol);
+        let next = vol - step;
+
+        vol = if step.is_finite() && next > lo && next < hi { next } else { (lo + hi) / 2.0 };
+
+        if hi - lo < 1e-12 {
+            return Some(vol);
+        }
+    }
+
+    Some(vol)
+}
+
+/// Midpoint of the bid/ask, `None` unless both sides are quoted.
+pub fn mid_price(option: &StockOption) -> Option<f64> {
+    (option.bid > 0.0 && option.ask >= option.bid).then(|| (option.bid + option.ask) / 2.0)
+}
+
+/// How far vendor figures may be from ours before a row is flagged.
+#[derive(Debug, Clone, Copy)]
+pub struct DiscrepancyTolerance {
+    /// Absolute, in vol points as a fraction (0.01 = 1 vol point)
+    pub implied_volatility: f64,
+    /// Absolute
+    pub delta: f64,
+    /// Relative, for gamma, theta, vega and rho
+    pub relative: f64,
+}
+
+impl Default for DiscrepancyTolerance {
+    fn default() -> Self {
+        Self { implied_volatility: 0.01, delta: 0.02, relative: 0.1 }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Discrepancy {
+    pub contract_id: String,
+    pub field: &'static str,
+    pub vendor: f64,
+    pub computed: f64,
+}
+
+/// Recomputes IV from the mid price and greeks at the vendor's own IV, and lists every value
+/// that differs from the vendor's beyond `tolerance`. Greeks are checked at the vendor IV so a
+/// bad IV isn't reported again as five bad greeks. Rows without a two-sided quote only have
+/// their greeks checked.
+pub fn vendor_discrepancies(
+    options: &[StockOption],
+    quote_date: NaiveDate,
+    spot: f64,
+    rate: f64,
+    dividend_yield: f64,
+    tolerance: DiscrepancyTolerance,
+) -> Vec<Discrepancy> {
+    let mut report = Vec::new();
+
+    for option in options {
+        let european = EuropeanOption::from_quote(option, quote_date, spot, rate, dividend_yield);
+        if european.time_to_expiry <= 0.0 {
+            continue;
+        }
+
+        let mut flag = |field: &'static str, vendor: f64, computed: f64, allowed: f64| {
+            if (vendor - computed).abs() > allowed {
+                report.push(Discrepancy { contract_id: option.contract_id.clone(), field, vendor, computed });
+            }
+        };
+
+        if let Some(iv) = mid_price(option).and_then(|mid| european.implied_vol(mid)) {
+            flag("implied_volatility", option.implied_volatility, iv, tolerance.implied_volatility);
+        }
+
+        if option.implied_volatility <= 0.0 {
+            continue;
+        }
+
+        let greeks = european.greeks(option.implied_volatility);
+        let relative = |computed: f64| tolerance.relative * computed.abs() + 1e-4;
+
+        flag("delta", option.delta, greeks.delta, tolerance.delta);
+        flag("gamma", option.gamma, greeks.gamma, relative(greeks.gamma));
+        flag("theta", option.theta, greeks.theta, relative(greeks.theta));
+        flag("vega", option.vega, greeks.vega, relative(greeks.vega));
+        flag("rho", option.rho, greeks.rho, relative(greeks.rho));
+    }
+
+    report
+}
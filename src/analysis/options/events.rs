@@ -0,0 +1,239 @@
+use std::str::FromStr;
+
+use chrono::NaiveDate;
+
+use crate::analysis::{options::black_scholes, streaming::vwap_session_day, surface::DAYS_PER_YEAR, volatility};
+use crate::data::{calendar, series::SeriesView, types::{OptionChain, OptionType, StockOption}};
+
+/// When an event is announced relative to the session on its date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventTiming {
+    BeforeOpen,
+    AfterClose,
+}
+
+/// A known event date, e.g. an earnings release.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EarningsEvent {
+    pub date: NaiveDate,
+    pub timing: EventTiming,
+}
+
+impl FromStr for EarningsEvent {
+    type Err = String;
+
+    /// Parses `2024-04-25 bmo` or `2024-04-25 amc`. A bare date is taken as after the close.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split_whitespace();
+        let date = parts.next().ok_or("empty event")?;
+        let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|e| format!("{date}: {e}"))?;
+
+        let timing = match parts.next().map(|el| el.to_ascii_lowercase()).as_deref() {
+            None | Some("amc") => EventTiming::AfterClose,
+            Some("bmo") => EventTiming::BeforeOpen,
+            Some(other) => return Err(format!("unknown event timing {other}, expected bmo or amc")),
+        };
+
+        Ok(Self { date, timing })
+    }
+}
+
+impl EarningsEvent {
+    /// Last session whose close comes before the announcement
+    pub fn pre_date(&self) -> NaiveDate {
+        match self.timing {
+            EventTiming::BeforeOpen => calendar::previous_trading_day(self.date),
+            EventTiming::AfterClose => self.date,
+        }
+    }
+
+    /// First session whose close comes after the announcement
+    pub fn post_date(&self) -> NaiveDate {
+        match self.timing {
+            EventTiming::BeforeOpen => self.date,
+            EventTiming::AfterClose => calendar::next_trading_day(self.date),
+        }
+    }
+}
+
+/// Move priced in by the at-the-money straddle of one expiry.
+#[derive(Debug, Clone, Copy)]
+pub struct ExpectedMove {
+    pub expiry: NaiveDate,
+    /// Strike where call and put mids are closest, the forward by put-call parity
+    pub strike: f64,
+    pub straddle: f64,
+    /// Straddle price as a fraction of spot
+    pub move_fraction: f64,
+    /// Average vendor IV of the two legs
+    pub implied_volatility: f64,
+}
+
+/// At-the-money straddle of every expiry after `quote_date`, nearest expiry first.
+pub fn expected_moves(options: &[StockOption], quote_date: NaiveDate, spot: f64) -> Vec<ExpectedMove> {
+    let mut expiries = options
+        .iter()
+        .map(|option| option.expiry_date)
+        .filter(|expiry| *expiry > quote_date)
+        .collect::<Vec<_>>();
+    expiries.sort();
+    expiries.dedup();
+
+    expiries
+        .into_iter()
+        .filter_map(|expiry| {
+            let legs = |option_type: OptionType| {
+                options.iter().filter(move |option| option.expiry_date == expiry && option.option_type == option_type)
+            };
+
+            legs(OptionType::Call)
+                .filter_map(|call| {
+                    let put = legs(OptionType::Put).find(|put| put.strike_price == call.strike_price)?;
+                    let (call_mid, put_mid) = (black_scholes::mid_price(call)?, black_scholes::mid_price(put)?);
+
+                    let straddle = ExpectedMove {
+                        expiry,
+                        strike: call.strike_price,
+                        straddle: call_mid + put_mid,
+                        move_fraction: (call_mid + put_mid) / spot,
+                        implied_volatility: (call.implied_volatility + put.implied_volatility) / 2.0,
+                    };
+                    Some(((call_mid - put_mid).abs(), straddle))
+                })
+                .min_by(|a, b| a.0.total_cmp(&b.0))
+                .map(|(_, straddle)| straddle)
+        })
+        .collect()
+}
+
+/// Priced against realized move of one event, and the implied vol it took out of the front expiry.
+#[derive(Debug, Clone)]
+pub struct EventMove {
+    pub event: EarningsEvent,
+    pub pre_date: NaiveDate,
+    pub post_date: NaiveDate,
+    /// First expiry after the post-event session, the one pricing the event
+    pub expiry: NaiveDate,
+    pub pre_spot: f64,
+    pub post_spot: f64,
+    /// Straddle implied move of `expiry` before the event, as a fraction of spot
+    pub expected_move: f64,
+    /// `post_spot / pre_spot - 1`
+    pub realized_move: f64,
+    /// At-the-money IV of `expiry` before and after the event
+    pub pre_iv: f64,
+    pub post_iv: f64,
+    /// Every expiry of the pre-event chain
+    pub expected_moves: Vec<ExpectedMove>,
+}
+
+impl EventMove {
+    /// Realized over expected move size, above 1 when the straddle was too cheap
+    pub fn move_ratio(&self) -> f64 {
+        self.realized_move.abs() / self.expected_move
+    }
+
+    /// IV drop in vol points as a fraction, positive when IV fell
+    pub fn iv_crush(&self) -> f64 {
+        self.pre_iv - self.post_iv
+    }
+
+    /// IV drop relative to the pre-event IV
+    pub fn relative_iv_crush(&self) -> f64 {
+        self.iv_crush() / self.pre_iv
+    }
+}
+
+/// Close of the last bar on or before `date`, with the New York day it's from.
+fn close_on(series: SeriesView, date: NaiveDate) -> Option<(NaiveDate, f64)> {
+    (0..series.len())
+        .rev()
+        .map(|i| (vwap_session_day(series.t[i]), series.close[i]))
+        .find(|(day, _)| *day <= date)
+}
+
+/// At-the-money IV of `expiry` from the fitted surface, or from the straddle legs when the
+/// chain is too thin to fit one.
+fn atm_iv(chain: &OptionChain, quote_date: NaiveDate, spot: f64, rate: f64, dividend_yield: f64, expiry: NaiveDate) -> f64 {
+    let time_to_expiry = (expiry - quote_date).num_days() as f64 / DAYS_PER_YEAR;
+
+    volatility::calculate_volatility_surface(chain, quote_date, spot, rate, dividend_yield)
+        .map(|surface| surface.implied_vol_at(0.0, time_to_expiry))
+        .filter(|iv| iv.is_finite() && *iv > 0.0)
+        .or_else(|| {
+            expected_moves(&chain.data, quote_date, spot)
+                .iter()
+                .find(|straddle| straddle.expiry == expiry)
+                .map(|straddle| straddle.implied_volatility)
+        })
+        .unwrap_or(f64::NAN)
+}
+
+/// Compares the chain quoted before `event` with the one quoted after it. Spots are closes of
+/// `series` on each chain's quote date. `None` if either side has no quote date, the series
+/// doesn't cover the post-event session or no expiry outlives it.
+pub fn analyze_event(
+    event: EarningsEvent,
+    pre: &OptionChain,
+    post: &OptionChain,
+    series: SeriesView,
+    rate: f64,
+    dividend_yield: f64,
+) -> Option<EventMove> {
+    let pre_date = pre.data.iter().find_map(|option| option.date)?;
+    let post_date = post.data.iter().find_map(|option| option.date)?;
+
+    let (_, pre_spot) = close_on(series, pre_date)?;
+    let (post_day, post_spot) = close_on(series, post_date)?;
+    if post_day <= pre_date {
+        return None;
+    }
+
+    let moves = expected_moves(&pre.data, pre_date, pre_spot);
+    let front = moves.iter().find(|straddle| straddle.expiry > post_date)?;
+
+    Some(EventMove {
+        event,
+        pre_date,
+        post_date,
+        expiry: front.expiry,
+        pre_spot,
+        post_spot,
+        expected_move: front.move_fraction,
+        realized_move: post_spot / pre_spot - 1.0,
+        pre_iv: atm_iv(pre, pre_date, pre_spot, rate, dividend_yield, front.expiry),
+        post_iv: atm_iv(post, post_date, post_spot, rate, dividend_yield, front.expiry),
+        expected_moves: moves,
+    })
+}
+
+/// Averages over a symbol's event history. NaN fields of individual events are skipped.
+#[derive(Debug, Clone, Copy)]
+pub struct EventMoveSummary {
+    pub events: usize,
+    pub mean_expected_move: f64,
+    /// Mean absolute realized move
+    pub mean_realized_move: f64,
+    pub mean_move_ratio: f64,
+    /// Share of events that moved more than the straddle priced
+    pub exceeded: f64,
+    pub mean_iv_crush: f64,
+    pub mean_relative_iv_crush: f64,
+}
+
+pub fn summarize(moves: &[EventMove]) -> EventMoveSummary {
+    let mean = |value: fn(&EventMove) -> f64| {
+        let values = moves.iter().map(value).filter(|v| v.is_finite()).collect::<Vec<_>>();
+        values.iter().sum::<f64>() / values.len() as f64
+    };
+
+    EventMoveSummary {
+        events: moves.len(),
+        mean_expected_move: mean(|m| m.expected_move),
+        mean_realized_move: mean(|m| m.realized_move.abs()),
+        mean_move_ratio: mean(|m| m.move_ratio()),
+        exceeded: mean(|m| if m.move_ratio() > 1.0 { 1.0 } else { 0.0 }),
+        mean_iv_crush: mean(|m| m.iv_crush()),
+        mean_relative_iv_crush: mean(|m| m.relative_iv_crush()),
+    }
+}
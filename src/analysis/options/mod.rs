@@ -0,0 +1,5 @@
+pub mod black_scholes;
+pub mod american;
+pub mod arbitrage;
+pub mod strategy;
+pub mod events;
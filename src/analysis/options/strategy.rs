@@ -0,0 +1,249 @@
+use chrono::NaiveDate;
+use ndarray::Array2;
+
+use crate::analysis::options::american::{self, AmericanOption, TreeMethod};
+use crate::analysis::options::black_scholes::{self, Greeks};
+use crate::analysis::surface::linspace;
+use crate::data::types::{OptionType, StockOption};
+
+/// Shares per listed equity option contract
+pub const CONTRACT_MULTIPLIER: f64 = 100.0;
+
+/// Tree steps per leg valuation
+const VALUATION_STEPS: usize = 100;
+
+/// Market inputs used to value legs before their expiry.
+#[derive(Debug, Clone)]
+pub struct ValuationParams {
+    pub quote_date: NaiveDate,
+    /// Underlying price on `quote_date`
+    pub spot: f64,
+    /// Continuously compounded risk-free rate
+    pub rate: f64,
+    /// Continuous dividend yield
+    pub dividend_yield: f64,
+    /// Cash dividends as ex-date and amount
+    pub dividends: Vec<(NaiveDate, f64)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Leg {
+    pub option: StockOption,
+    /// Contracts, negative when short
+    pub quantity: f64,
+    /// Per share price the leg was opened at
+    pub entry_price: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StrategyKind {
+    /// Long the first strike's call, short the second's
+    CallVertical,
+    /// Long the first strike's put, short the second's
+    PutVertical,
+    /// Long call and put at one strike
+    Straddle,
+    /// Long put at the first strike, long call at the second
+    Strangle,
+    /// Long put, short put, short call, long call, by increasing strike
+    IronCondor,
+    /// Short the near expiry call, long the far one, at one strike
+    Calendar,
+}
+
+impl StrategyKind {
+    pub const ALL: [StrategyKind; 6] = [
+        StrategyKind::CallVertical,
+        StrategyKind::PutVertical,
+        StrategyKind::Straddle,
+        StrategyKind::Strangle,
+        StrategyKind::IronCondor,
+        StrategyKind::Calendar,
+    ];
+
+    /// Number of strikes [`OptionStrategy::from_chain`] expects
+    pub fn strike_count(&self) -> usize {
+        match self {
+            Self::Straddle | Self::Calendar => 1,
+            Self::CallVertical | Self::PutVertical | Self::Strangle => 2,
+            Self::IronCondor => 4,
+        }
+    }
+}
+
+/// A multi-leg option position. All P&L figures are in dollars for the whole position.
+#[derive(Debug, Clone)]
+pub struct OptionStrategy {
+    pub name: String,
+    pub legs: Vec<Leg>,
+}
+
+impl ValuationParams {
+    /// Listed equity options are American, so legs are valued on a tree that allows early exercise
+    fn american(&self, option: &StockOption, spot: f64, date: NaiveDate) -> AmericanOption {
+        AmericanOption { dividend_yield: self.dividend_yield, ..AmericanOption::from_quote(option, date, spot, self.rate, &self.dividends) }
+    }
+
+    /// Per share value of `option` on `date`, at its quoted IV before expiry and intrinsic after
+    fn value(&self, option: &StockOption, spot: f64, date: NaiveDate) -> f64 {
+        self.american(option, spot, date).price(option.implied_volatility, VALUATION_STEPS, TreeMethod::Trinomial)
+    }
+}
+
+impl OptionStrategy {
+    /// Adds `quantity` contracts of `option`, opened at the mid price, or at its model value
+    /// when it isn't quoted on both sides.
+    pub fn with_leg(mut self, option: &StockOption, quantity: f64, params: &ValuationParams) -> Self {
+        let entry_price = black_scholes::mid_price(option).unwrap_or_else(|| params.value(option, params.spot, params.quote_date));
+        self.legs.push(Leg { option: option.clone(), quantity, entry_price });
+        self
+    }
+
+    /// Builds a `kind` position from contracts in `chain`. `far_expiry` is only used by calendars.
+    /// A negative `quantity` sells the whole structure. `None` if a contract isn't in the chain.
+    /// Legs are valued at the vol their mid implies on the tree rather than the vendor's European
+    /// IV, which is only kept for legs without a two-sided quote.
+    pub fn from_chain(
+        kind: StrategyKind,
+        chain: &[StockOption],
+        expiry: NaiveDate,
+        far_expiry: NaiveDate,
+        strikes: &[f64],
+        quantity: f64,
+        params: &ValuationParams,
+    ) -> Option<Self> {
+        if strikes.len() < kind.strike_count() {
+            return None;
+        }
+
+        let find = |option_type: OptionType, strike: f64, expiry: NaiveDate| {
+            chain.iter().find(|option| {
+                option.option_type == option_type && option.strike_price == strike && option.expiry_date == expiry
+            })
+        };
+
+        let legs = match kind {
+            StrategyKind::CallVertical => vec![
+                (find(OptionType::Call, strikes[0], expiry)?, 1.0),
+                (find(OptionType::Call, strikes[1], expiry)?, -1.0),
+            ],
+            StrategyKind::PutVertical => vec![
+                (find(OptionType::Put, strikes[0], expiry)?, 1.0),
+                (find(OptionType::Put, strikes[1], expiry)?, -1.0),
+            ],
+            StrategyKind::Straddle => vec![
+                (find(OptionType::Call, strikes[0], expiry)?, 1.0),
+                (find(OptionType::Put, strikes[0], expiry)?, 1.0),
+            ],
+            StrategyKind::Strangle => vec![
+                (find(OptionType::Put, strikes[0], expiry)?, 1.0),
+                (find(OptionType::Call, strikes[1], expiry)?, 1.0),
+            ],
+            StrategyKind::IronCondor => vec![
+                (find(OptionType::Put, strikes[0], expiry)?, 1.0),
+                (find(OptionType::Put, strikes[1], expiry)?, -1.0),
+                (find(OptionType::Call, strikes[2], expiry)?, -1.0),
+                (find(OptionType::Call, strikes[3], expiry)?, 1.0),
+            ],
+            StrategyKind::Calendar => vec![
+                (find(OptionType::Call, strikes[0], expiry)?, -1.0),
+                (find(OptionType::Call, strikes[0], far_expiry)?, 1.0),
+            ],
+        };
+
+        let options = legs.iter().map(|&(option, _)| option.clone()).collect::<Vec<_>>();
+        let vols = american::chain_implied_vols(
+            &options,
+            params.quote_date,
+            params.spot,
+            params.rate,
+            params.dividend_yield,
+            &params.dividends,
+            TreeMethod::Trinomial,
+        );
+
+        let strategy = Self { name: format!("{kind:?}"), legs: Vec::new() };
+        Some(options.into_iter().zip(vols).zip(legs).fold(strategy, |strategy, ((option, vol), (_, size))| {
+            let option = StockOption { implied_volatility: vol.unwrap_or(option.implied_volatility), ..option };
+            strategy.with_leg(&option, size * quantity, params)
+        }))
+    }
+
+    /// Date the first leg expires, where payoff diagrams are drawn
+    pub fn first_expiry(&self) -> Option<NaiveDate> {
+        self.legs.iter().map(|leg| leg.option.expiry_date).min()
+    }
+
+    /// Net premium paid to open, negative for a credit
+    pub fn cost(&self) -> f64 {
+        self.legs.iter().map(|leg| leg.quantity * leg.entry_price * CONTRACT_MULTIPLIER).sum()
+    }
+
+    /// Position P&L on `date` with the underlying at `spot`
+    pub fn pnl(&self, spot: f64, date: NaiveDate, params: &ValuationParams) -> f64 {
+        self.legs
+            .iter()
+            .map(|leg| leg.quantity * (params.value(&leg.option, spot, date) - leg.entry_price) * CONTRACT_MULTIPLIER)
+            .sum()
+    }
+
+    /// P&L at the first expiry. Legs expiring later, as in a calendar, are still valued at their IV
+    pub fn payoff_at_expiry(&self, spot: f64, params: &ValuationParams) -> f64 {
+        self.first_expiry().map_or(0.0, |expiry| self.pnl(spot, expiry, params))
+    }
+
+    /// P&L indexed by `[date, spot]`
+    pub fn pnl_surface(&self, spots: &[f64], dates: &[NaiveDate], params: &ValuationParams) -> Array2<f64> {
+        Array2::from_shape_fn((dates.len(), spots.len()), |(i, j)| self.pnl(spots[j], dates[i], params))
+    }
+
+    /// Spots between `low` and `high` where the P&L at first expiry crosses zero
+    pub fn breakevens(&self, low: f64, high: f64, params: &ValuationParams) -> Vec<f64> {
+        let spots = linspace(low, high, 2001);
+        let pnl = spots.iter().map(|&spot| self.payoff_at_expiry(spot, params)).collect::<Vec<_>>();
+
+        (1..spots.len())
+            .filter(|&i| (pnl[i - 1] < 0.0) != (pnl[i] < 0.0))
+            .map(|i| spots[i - 1] - pnl[i - 1] * (spots[i] - spots[i - 1]) / (pnl[i] - pnl[i - 1]))
+            .collect()
+    }
+
+    /// Largest profit and loss at first expiry for spots from 0 up to `high`. A P&L still
+    /// changing far above `high` is reported as unbounded, +inf profit or -inf loss.
+    pub fn max_profit_loss(&self, high: f64, params: &ValuationParams) -> (f64, f64) {
+        let spots = linspace(0.0, high, 2001);
+        let pnl = spots.iter().map(|&spot| self.payoff_at_expiry(spot, params)).collect::<Vec<_>>();
+
+        let mut max_profit = pnl.iter().copied().fold(f64::MIN, f64::max);
+        let mut max_loss = pnl.iter().copied().fold(f64::MAX, f64::min);
+
+        let at_high = self.payoff_at_expiry(high, params);
+        let far_above = self.payoff_at_expiry(high * 10.0, params);
+        let tolerance = 1e-6 * (1.0 + at_high.abs());
+
+        if far_above > at_high + tolerance {
+            max_profit = f64::INFINITY;
+        } else if far_above < at_high - tolerance {
+            max_loss = f64::NEG_INFINITY;
+        }
+
+        (max_profit, max_loss)
+    }
+
+    /// Position greeks on the quote date, each leg at its quoted IV
+    pub fn greeks(&self, params: &ValuationParams) -> Greeks {
+        self.legs.iter().fold(Greeks::default(), |total, leg| {
+            let american = params.american(&leg.option, params.spot, params.quote_date);
+            let greeks = american.greeks(leg.option.implied_volatility, VALUATION_STEPS, TreeMethod::Trinomial);
+            let size = leg.quantity * CONTRACT_MULTIPLIER;
+
+            Greeks {
+                delta: total.delta + size * greeks.delta,
+                gamma: total.gamma + size * greeks.gamma,
+                theta: total.theta + size * greeks.theta,
+                vega: total.vega + size * greeks.vega,
+                rho: total.rho + size * greeks.rho,
+            }
+        })
+    }
+}
@@ -4,44 +4,62 @@ use linfa_ensemble::{EnsembleLearner, EnsembleLearnerParams};
 // use linfa_ensemble::{EnsembleLearner, EnsembleLearnerParams};
 // use linfa::{Dataset};
 use linfa_trees::DecisionTree;
-use linfa::traits::Fit;
+use linfa::{traits::{Fit, PredictInplace}, Float};
 use ndarray::{Array1, Array2};
 use rand::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use super::super::features::*;
+use super::super::normalization::{Normalizer, Scaling};
+use crate::data::types::TickerData;
 
-pub fn prepare_dataset(features: &[featureset::DirectionClassificationFeatures]) -> (Array2<f32>, Array1<usize>) {
-    let n_samples = features.len();
-    let n_featues = 13;
-
-    let mut feature_matrix = Array2::zeros((n_samples, n_featues));
-    // let mut targets = Array1::zeros((n_samples,));
-    let mut targets = Array1::from_elem((n_samples, ), 0);
-    // targets.fill(String::default());
-
-    for (i, feature) in features.iter().enumerate() {
-        feature_matrix[[i, 0]] = feature.prev_close_to_high_ratio;
-        feature_matrix[[i, 1]] = feature.prev_close_to_low_ratio;
-        feature_matrix[[i, 2]] = feature.daily_return;
-        feature_matrix[[i, 3]] = feature.volatility_5d;
-        feature_matrix[[i, 4]] = feature.volatility_20d;
-        feature_matrix[[i, 5]] = feature.volume_ratio_5d;
-        feature_matrix[[i, 6]] = feature.volume_ratio_20d;
-        feature_matrix[[i, 7]] = feature.rsi_14;
-        feature_matrix[[i, 8]] = feature.sma_5;
-        feature_matrix[[i, 9]] = feature.sma_20;
-        feature_matrix[[i, 10]] = feature.macd_line;
-        feature_matrix[[i, 11]] = feature.macd_signal;
-        feature_matrix[[i, 12]] = feature.macd_histogram;
-        
-        targets[i] = feature.direction.to_usize();
+/// How features are scaled before training, fit on the training rows only
+const SCALING: Scaling = Scaling::Robust;
+
+/// A trained classifier and the [`Normalizer`] fit on its training features. Features it predicts
+/// on are scaled with that same state, so keep the two together when storing the model.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirectionModel<M> {
+    pub model: M,
+    pub normalizer: Normalizer,
+}
+
+impl<M: PredictInplace<Array2<f32>, Array1<usize>>> DirectionModel<M> {
+    /// [`prepare_dataset`] on the features of `dataset` scaled by the training state. Fails if
+    /// they aren't the columns the model was trained on.
+    pub fn prepare(&self, dataset: &featureset::DirectionDataset) -> Result<(Array2<f32>, Array1<usize>), String> {
+        let features = self.normalizer.transform(&dataset.features)?;
+
+        Ok(to_arrays(&features, &dataset.direction))
     }
-    
+
+    /// Predicted direction of each row of `dataset`, as [`featureset::PriceDirection::to_usize`]
+    pub fn predict(&self, dataset: &featureset::DirectionDataset) -> Result<Array1<usize>, String> {
+        let (features, _) = self.prepare(dataset)?;
+        let mut predictions = self.model.default_target(&features);
+        self.model.predict_inplace(&features, &mut predictions);
+
+        Ok(predictions)
+    }
+}
+
+/// Builds the feature matrix in the float type the model is trained with. Features are computed
+/// in `f64` and narrowed here, so `f32` models lose precision only at the very end. The values
+/// are not scaled, use [`DirectionModel::prepare`] for a trained model's inputs.
+pub fn prepare_dataset<F: Float>(dataset: &featureset::DirectionDataset) -> (Array2<F>, Array1<usize>) {
+    to_arrays(&dataset.features, &dataset.direction)
+}
+
+fn to_arrays<F: Float>(features: &pipeline::FeatureMatrix, direction: &[featureset::PriceDirection]) -> (Array2<F>, Array1<usize>) {
+    let feature_matrix = features.values.mapv(F::cast);
+    let targets = direction.iter().map(|direction| direction.to_usize()).collect::<Array1<usize>>();
+
     (feature_matrix, targets)
 }
 
-pub fn train(train: &[featureset::DirectionClassificationFeatures]) -> Result<DecisionTree<f32, usize>, Box<dyn std::error::Error>> {
-    let (features, targets) = prepare_dataset(train);
+pub fn train(train: &featureset::DirectionDataset) -> Result<DirectionModel<DecisionTree<f32, usize>>, Box<dyn std::error::Error>> {
+    let (normalizer, features) = Normalizer::fit_transform(SCALING, &train.features);
+    let (features, targets) = to_arrays::<f32>(&features, &train.direction);
 
     let dataset = linfa::Dataset::new(features, targets);
 
@@ -51,11 +69,17 @@ pub fn train(train: &[featureset::DirectionClassificationFeatures]) -> Result<De
         .min_weight_split(5.0)
         .fit(&dataset)?;
     
-    Ok(model)
+    Ok(DirectionModel { model, normalizer })
+}
+
+/// [`train`] on the cached feature set of `data`
+pub fn train_on(data: &TickerData) -> Result<DirectionModel<DecisionTree<f32, usize>>, Box<dyn Error>> {
+    train(&featureset::calculate_featureset_cached(data))
 }
 
-pub fn train_ensemble(train: &[featureset::DirectionClassificationFeatures]) -> Result<EnsembleLearner<DecisionTree<f32, usize>>, Box<dyn Error>> {
-    let (features, targets) = prepare_dataset(train);
+pub fn train_ensemble(train: &featureset::DirectionDataset) -> Result<DirectionModel<EnsembleLearner<DecisionTree<f32, usize>>>, Box<dyn Error>> {
+    let (normalizer, features) = Normalizer::fit_transform(SCALING, &train.features);
+    let (features, targets) = to_arrays::<f32>(&features, &train.direction);
     let rng = rand::rngs::SmallRng::seed_from_u64(69);
     let dataset = linfa::Dataset::new(features, targets);
 
@@ -70,5 +94,61 @@ pub fn train_ensemble(train: &[featureset::DirectionClassificationFeatures]) ->
         .fit(&dataset)?;
 
 
-    Ok(model)
-} 
\ No newline at end of file
+    Ok(DirectionModel { model, normalizer })
+}
+
+/// [`train_ensemble`] on the cached feature set of `data`
+pub fn train_ensemble_on(data: &TickerData) -> Result<DirectionModel<EnsembleLearner<DecisionTree<f32, usize>>>, Box<dyn Error>> {
+    train_ensemble(&featureset::calculate_featureset_cached(data))
+}
+
+/// Out-of-sample score of a model trained on the start of one symbol's feature set.
+#[derive(Debug, Clone, Copy)]
+pub struct Evaluation {
+    pub train_rows: usize,
+    pub test_rows: usize,
+    /// Fraction of test rows whose direction was predicted
+    pub accuracy: f64,
+}
+
+/// Featurizes every symbol of `universe` in parallel with the columns of `options`, then scores
+/// [`train`] on each with [`evaluate`].
+pub fn evaluate_universe(
+    universe: &[TickerData],
+    options: &featureset::FeatureOptions,
+    inputs: &pipeline::DailyInputs,
+    test_fraction: f64,
+) -> Vec<(String, Result<Evaluation, String>)> {
+    let datasets = featureset::calculate_featuresets(universe, options, inputs);
+
+    universe
+        .iter()
+        .zip(datasets)
+        .map(|(data, dataset)| (data.symbol.clone(), dataset.and_then(|dataset| evaluate(&dataset, test_fraction))))
+        .collect()
+}
+
+/// Trains the overnight gap classifier on the sessions of intraday `data`, e.g. from
+/// [`crate::data::db_service::get_price_history`], and scores it with [`evaluate`]. Gaps beyond
+/// `threshold` (a fraction) are up or down.
+pub fn evaluate_gaps(data: &TickerData, threshold: f64, test_fraction: f64) -> Result<Evaluation, String> {
+    let dataset = featureset::calculate_gap_featureset(data, &featureset::gap_pipeline(), threshold)?;
+
+    evaluate(&dataset, test_fraction)
+}
+
+/// Trains [`train`] on the first `1 - test_fraction` of the rows of `dataset` and scores it on
+/// the rest. Training rows whose labels reach into the test rows are purged.
+pub fn evaluate(dataset: &featureset::DirectionDataset, test_fraction: f64) -> Result<Evaluation, String> {
+    let rows = dataset.features.bars.len();
+    let split_row = ((rows as f64) * (1.0 - test_fraction.clamp(0.0, 1.0))) as usize;
+    let Some(&split_bar) = dataset.features.bars.get(split_row).filter(|_| split_row > 0) else {
+        return Err(format!("{rows} rows can't be split to train and test"));
+    };
+
+    let (train_set, test_set) = dataset.split_at_bar(split_bar);
+    let predictions = train(&train_set).map_err(|e| e.to_string())?.predict(&test_set)?;
+    let hits = predictions.iter().zip(&test_set.direction).filter(|(predicted, actual)| **predicted == actual.to_usize()).count();
+
+    Ok(Evaluation { train_rows: train_set.direction.len(), test_rows: test_set.direction.len(), accuracy: hits as f64 / test_set.direction.len() as f64 })
+}
@@ -0,0 +1,509 @@
+use std::collections::VecDeque;
+
+use chrono::NaiveDate;
+use chrono_tz::America::New_York;
+
+use crate::{analysis::moving_average::MacdPoint, data::types::TickerDataframe};
+
+/// Indicator that is updated one bar at a time, for live feeds.
+///
+/// The EMA, MACD and RSI streams produce exactly the same values, bit for bit, as their batch
+/// counterparts in `moving_average`. The SMA, standard deviation and VWAP batch functions are
+/// built on the streams themselves.
+pub trait StreamingIndicator {
+    type Output;
+
+    /// Feeds the next bar and returns the indicator value at that bar
+    fn update(&mut self, bar: &TickerDataframe) -> Self::Output;
+}
+
+/// Rolling sum over the last `period` values. The sum is recomputed from the window once every
+/// `period` updates so rounding error can't build up over long feeds, which keeps updates
+/// amortized O(1).
+#[derive(Debug, Clone)]
+pub(crate) struct RollingSum {
+    pub(crate) period: usize,
+    window: VecDeque<f64>,
+    pub(crate) sum: f64,
+    since_resync: usize,
+}
+
+impl RollingSum {
+    pub(crate) fn new(period: usize) -> Self {
+        Self { period, window: VecDeque::with_capacity(period + 1), sum: 0.0, since_resync: 0 }
+    }
+
+    pub(crate) fn push(&mut self, value: f64) {
+        self.window.push_back(value);
+        self.sum += value;
+
+        if self.window.len() > self.period {
+            let dropped = self.window.pop_front().unwrap();
+            self.sum -= dropped;
+        }
+
+        self.since_resync += 1;
+        if self.since_resync >= self.period {
+            self.sum = self.window.iter().sum();
+            self.since_resync = 0;
+        }
+    }
+
+    pub(crate) fn is_full(&self) -> bool {
+        self.window.len() == self.period
+    }
+}
+
+/// Mean and variance of a sliding window, updated in O(1) with Welford's add and remove steps so
+/// the variance doesn't cancel catastrophically the way `E[x^2] - mean^2` does. Both are
+/// recomputed from the window once it has seen as many updates as it holds values. The window
+/// either keeps the last `period` values or, from [`RollingMoments::unbounded`], whatever the
+/// caller hasn't [`RollingMoments::pop`]ped, e.g. the values within a time span.
+#[derive(Debug, Clone)]
+pub(crate) struct RollingMoments {
+    pub(crate) period: usize,
+    window: VecDeque<f64>,
+    mean: f64,
+    /// Sum of squared deviations from `mean`
+    m2: f64,
+    since_resync: usize,
+}
+
+impl RollingMoments {
+    pub(crate) fn new(period: usize) -> Self {
+        Self { period, window: VecDeque::with_capacity(period + 1), mean: 0.0, m2: 0.0, since_resync: 0 }
+    }
+
+    /// Window that only shrinks when values are popped
+    pub(crate) fn unbounded() -> Self {
+        Self { period: usize::MAX, window: VecDeque::new(), mean: 0.0, m2: 0.0, since_resync: 0 }
+    }
+
+    pub(crate) fn push(&mut self, value: f64) {
+        self.window.push_back(value);
+
+        let delta = value - self.mean;
+        self.mean += delta / self.window.len() as f64;
+        self.m2 += delta * (value - self.mean);
+
+        if self.window.len() > self.period {
+            self.remove_front();
+        }
+        self.updated();
+    }
+
+    /// Drops the oldest value, returning it
+    pub(crate) fn pop(&mut self) -> Option<f64> {
+        let dropped = self.remove_front();
+        self.updated();
+        dropped
+    }
+
+    fn remove_front(&mut self) -> Option<f64> {
+        let dropped = self.window.pop_front()?;
+
+        if self.window.is_empty() {
+            (self.mean, self.m2) = (0.0, 0.0);
+        } else {
+            let old_mean = self.mean;
+            self.mean -= (dropped - self.mean) / self.window.len() as f64;
+            self.m2 -= (dropped - old_mean) * (dropped - self.mean);
+        }
+
+        Some(dropped)
+    }
+
+    fn updated(&mut self) {
+        self.since_resync += 1;
+        if self.since_resync >= self.window.len() {
+            let n = self.window.len().max(1) as f64;
+            self.mean = self.window.iter().sum::<f64>() / n;
+            self.m2 = self.window.iter().map(|v| (v - self.mean) * (v - self.mean)).sum();
+            self.since_resync = 0;
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.window.len()
+    }
+
+    pub(crate) fn is_full(&self) -> bool {
+        self.window.len() == self.period
+    }
+
+    pub(crate) fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// Population variance of the values in the window
+    pub(crate) fn variance(&self) -> f64 {
+        (self.m2 / self.window.len() as f64).max(0.0)
+    }
+
+    /// Variance with Bessel's correction, NaN below two values
+    pub(crate) fn sample_variance(&self) -> f64 {
+        if self.window.len() < 2 {
+            return f64::NAN;
+        }
+
+        (self.m2 / (self.window.len() - 1) as f64).max(0.0)
+    }
+}
+
+/// Highest high and lowest low of the last `period` bars. Keeps monotonic deques of the bars
+/// that can still become the extreme, so every bar is pushed and popped at most once.
+#[derive(Debug, Clone)]
+pub(crate) struct RollingExtremes {
+    period: usize,
+    count: usize,
+    /// `(bar, high)` with highs decreasing from the front
+    highs: VecDeque<(usize, f64)>,
+    /// `(bar, low)` with lows increasing from the front
+    lows: VecDeque<(usize, f64)>,
+}
+
+impl RollingExtremes {
+    pub(crate) fn new(period: usize) -> Self {
+        Self { period, count: 0, highs: VecDeque::new(), lows: VecDeque::new() }
+    }
+
+    pub(crate) fn push(&mut self, high: f64, low: f64) {
+        let bar = self.count;
+        self.count += 1;
+
+        while self.highs.back().is_some_and(|&(_, h)| h <= high) {
+            self.highs.pop_back();
+        }
+        self.highs.push_back((bar, high));
+
+        while self.lows.back().is_some_and(|&(_, l)| l >= low) {
+            self.lows.pop_back();
+        }
+        self.lows.push_back((bar, low));
+
+        // Drop the bars that slid out of the window
+        while self.highs.front().is_some_and(|&(b, _)| b + self.period <= bar) {
+            self.highs.pop_front();
+        }
+        while self.lows.front().is_some_and(|&(b, _)| b + self.period <= bar) {
+            self.lows.pop_front();
+        }
+    }
+
+    pub(crate) fn is_full(&self) -> bool {
+        self.period > 0 && self.count >= self.period
+    }
+
+    pub(crate) fn highest(&self) -> f64 {
+        self.highs.front().map_or(f64::NAN, |&(_, h)| h)
+    }
+
+    pub(crate) fn lowest(&self) -> f64 {
+        self.lows.front().map_or(f64::NAN, |&(_, l)| l)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SmaStream {
+    sum: RollingSum,
+}
+
+impl SmaStream {
+    pub fn new(period: usize) -> Self {
+        Self { sum: RollingSum::new(period.max(1)) }
+    }
+
+    pub fn push(&mut self, value: f64) -> f64 {
+        self.sum.push(value);
+
+        if self.sum.is_full() {
+            self.sum.sum * (1.0 / self.sum.period as f64)
+        } else {
+            f64::NAN
+        }
+    }
+}
+
+impl StreamingIndicator for SmaStream {
+    type Output = f64;
+
+    fn update(&mut self, bar: &TickerDataframe) -> f64 {
+        self.push(bar.close)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EmaStream {
+    period: usize,
+    smoothing: f64,
+    count: usize,
+    seed_sum: f64,
+    ema: f64,
+}
+
+impl EmaStream {
+    pub fn new(period: usize) -> Self {
+        Self {
+            period,
+            smoothing: 2.0 / (period as f64 + 1.0),
+            count: 0,
+            seed_sum: 0.0,
+            ema: f64::NAN,
+        }
+    }
+
+    pub fn push(&mut self, value: f64) -> f64 {
+        if self.period == 0 {
+            return f64::NAN;
+        }
+
+        self.count += 1;
+
+        if self.count < self.period {
+            self.seed_sum += value;
+            return f64::NAN;
+        }
+
+        if self.count == self.period {
+            self.seed_sum += value;
+            self.ema = self.seed_sum / self.period as f64;
+        } else {
+            self.ema = (value * self.smoothing) + (self.ema * (1.0 - self.smoothing));
+        }
+
+        self.ema
+    }
+}
+
+impl StreamingIndicator for EmaStream {
+    type Output = f64;
+
+    fn update(&mut self, bar: &TickerDataframe) -> f64 {
+        self.push(bar.close)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MacdStream {
+    long: usize,
+    count: usize,
+    ema_short: EmaStream,
+    ema_long: EmaStream,
+    signal: EmaStream,
+}
+
+impl MacdStream {
+    pub fn new(period_short: usize, period_long: usize, period_signal: usize) -> Self {
+        Self {
+            long: period_long.max(1),
+            count: 0,
+            ema_short: EmaStream::new(period_short.max(1)),
+            ema_long: EmaStream::new(period_long.max(1)),
+            signal: EmaStream::new(period_signal.max(1)),
+        }
+    }
+
+    pub fn push(&mut self, value: f64) -> MacdPoint {
+        self.count += 1;
+
+        let macd = self.ema_short.push(value) - self.ema_long.push(value);
+
+        // Signal line only starts once the MACD line itself is valid
+        let signal = if self.count >= self.long {
+            self.signal.push(macd)
+        } else {
+            f64::NAN
+        };
+
+        MacdPoint { macd, signal }
+    }
+}
+
+impl StreamingIndicator for MacdStream {
+    type Output = MacdPoint;
+
+    fn update(&mut self, bar: &TickerDataframe) -> MacdPoint {
+        self.push(bar.close)
+    }
+}
+
+/// Wilder RSI, NaN for the first `period` bars.
+#[derive(Debug, Clone)]
+pub struct RsiStream {
+    period: usize,
+    prev_close: Option<f64>,
+    changes: usize,
+    avg_gain: f64,
+    avg_loss: f64,
+}
+
+impl RsiStream {
+    pub fn new(period: usize) -> Self {
+        Self { period, prev_close: None, changes: 0, avg_gain: 0.0, avg_loss: 0.0 }
+    }
+
+    pub fn push(&mut self, close: f64) -> f64 {
+        let Some(prev) = self.prev_close.replace(close) else {
+            return f64::NAN;
+        };
+
+        if self.period == 0 {
+            return f64::NAN;
+        }
+
+        let change = close - prev;
+        let gain = if change > 0.0 { change } else { 0.0 };
+        let loss = if change < 0.0 { -change } else { 0.0 };
+
+        self.changes += 1;
+
+        if self.changes <= self.period {
+            // avg_gain/avg_loss hold running sums until the first full period
+            self.avg_gain += gain;
+            self.avg_loss += loss;
+
+            if self.changes < self.period {
+                return f64::NAN;
+            }
+
+            self.avg_gain /= self.period as f64;
+            self.avg_loss /= self.period as f64;
+        } else {
+            self.avg_gain = (self.avg_gain * (self.period as f64 - 1.0) + gain) / self.period as f64;
+            self.avg_loss = (self.avg_loss * (self.period as f64 - 1.0) + loss) / self.period as f64;
+        }
+
+        if self.avg_loss == 0.0 {
+            100.0
+        } else {
+            let rs = self.avg_gain / self.avg_loss;
+            100.0 - (100.0 / (1.0 + rs))
+        }
+    }
+}
+
+impl StreamingIndicator for RsiStream {
+    type Output = f64;
+
+    fn update(&mut self, bar: &TickerDataframe) -> f64 {
+        self.push(bar.close)
+    }
+}
+
+/// Population standard deviation of the last `period` closes, NaN until the window is full.
+#[derive(Debug, Clone)]
+pub struct StdDevStream {
+    moments: RollingMoments,
+}
+
+impl StdDevStream {
+    pub fn new(period: usize) -> Self {
+        Self { moments: RollingMoments::new(period.max(1)) }
+    }
+
+    pub fn push(&mut self, value: f64) -> f64 {
+        self.moments.push(value);
+
+        if self.moments.is_full() {
+            self.moments.variance().sqrt()
+        } else {
+            f64::NAN
+        }
+    }
+}
+
+impl StreamingIndicator for StdDevStream {
+    type Output = f64;
+
+    fn update(&mut self, bar: &TickerDataframe) -> f64 {
+        self.push(bar.close)
+    }
+}
+
+/// Where a VWAP starts accumulating from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VwapAnchor {
+    /// From the first bar of the series
+    Start,
+    /// Resets on the first bar of each New York trading day
+    Session,
+    /// From the first bar at or after the given unix time, NaN before it
+    At(i64),
+}
+
+/// Volume-weighted average of the typical price `(high + low + close) / 3` accumulated from its
+/// anchor, along with the volume-weighted standard deviation of the typical price around it.
+/// Both use West's weighted form of Welford's update.
+#[derive(Debug, Clone)]
+pub struct VwapStream {
+    anchor: VwapAnchor,
+    day: Option<NaiveDate>,
+    volume: f64,
+    vwap: f64,
+    /// Volume-weighted sum of squared deviations from `vwap`
+    m2: f64,
+}
+
+impl VwapStream {
+    pub fn new(anchor: VwapAnchor) -> Self {
+        Self { anchor, day: None, volume: 0.0, vwap: 0.0, m2: 0.0 }
+    }
+
+    /// Feeds a bar given as unix seconds, high, low, close and volume and returns the VWAP.
+    /// NaN before the anchor and while no volume has traded since it.
+    pub fn push(&mut self, t: i64, high: f64, low: f64, close: f64, vol: i64) -> f64 {
+        match self.anchor {
+            VwapAnchor::Start => {}
+            VwapAnchor::Session => {
+                let day = vwap_session_day(t);
+                if self.day != Some(day) {
+                    self.day = Some(day);
+                    self.volume = 0.0;
+                    self.vwap = 0.0;
+                    self.m2 = 0.0;
+                }
+            }
+            VwapAnchor::At(anchor) => {
+                if t < anchor {
+                    return f64::NAN;
+                }
+            }
+        }
+
+        let typical_price = (high + low + close) / 3.0;
+        let weight = vol as f64;
+        if weight > 0.0 {
+            self.volume += weight;
+            let delta = typical_price - self.vwap;
+            self.vwap += delta * weight / self.volume;
+            self.m2 += weight * delta * (typical_price - self.vwap);
+        }
+
+        self.vwap()
+    }
+
+    pub fn vwap(&self) -> f64 {
+        if self.volume == 0.0 { f64::NAN } else { self.vwap }
+    }
+
+    /// Volume-weighted standard deviation of the typical price since the anchor
+    pub fn std_dev(&self) -> f64 {
+        if self.volume == 0.0 { f64::NAN } else { (self.m2 / self.volume).max(0.0).sqrt() }
+    }
+}
+
+impl StreamingIndicator for VwapStream {
+    type Output = f64;
+
+    fn update(&mut self, bar: &TickerDataframe) -> f64 {
+        self.push(bar.t.timestamp(), bar.high, bar.low, bar.close, bar.vol)
+    }
+}
+
+/// New York calendar day of a bar, used to anchor session VWAPs.
+pub fn vwap_session_day(t: i64) -> NaiveDate {
+    chrono::DateTime::from_timestamp(t, 0)
+        .unwrap_or_default()
+        .with_timezone(&New_York)
+        .date_naive()
+}
@@ -0,0 +1,292 @@
+use chrono::NaiveDate;
+use ndarray::Array2;
+
+use crate::analysis::{optimize, options::black_scholes};
+use crate::data::types::{OptionType, StockOption};
+
+pub const DAYS_PER_YEAR: f64 = 365.0;
+
+/// One quoted option placed on the surface.
+#[derive(Debug, Clone, Copy)]
+pub struct SurfacePoint {
+    pub strike: f64,
+    /// Years from the quote date to expiry
+    pub time_to_expiry: f64,
+    /// `ln(strike / forward)`, the forward carrying the rate less the dividend yield
+    pub log_moneyness: f64,
+    pub implied_volatility: f64,
+    /// Vendor delta of the contract the point came from
+    pub delta: f64,
+}
+
+/// Raw SVI parameterization of total implied variance for a single expiry:
+/// `w(k) = a + b * (rho * (k - m) + sqrt((k - m)² + sigma²))`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SviSlice {
+    pub time_to_expiry: f64,
+    pub a: f64,
+    pub b: f64,
+    pub rho: f64,
+    pub m: f64,
+    pub sigma: f64,
+    /// Root mean squared error of the fit in implied volatility
+    pub rmse: f64,
+}
+
+impl SviSlice {
+    pub fn total_variance(&self, k: f64) -> f64 {
+        let x = k - self.m;
+        self.a + self.b * (self.rho * x + (x * x + self.sigma * self.sigma).sqrt())
+    }
+
+    pub fn implied_vol(&self, k: f64) -> f64 {
+        (self.total_variance(k).max(0.0) / self.time_to_expiry).sqrt()
+    }
+
+    /// Least-squares SVI fit on total variance. Needs at least 5 points, one per parameter.
+    pub fn fit(time_to_expiry: f64, points: &[(f64, f64)]) -> Option<Self> {
+        if points.len() < 5 || time_to_expiry <= 0.0 {
+            return None;
+        }
+
+        let variance = points.iter().map(|(k, iv)| (*k, iv * iv * time_to_expiry)).collect::<Vec<_>>();
+        let min_variance = variance.iter().map(|(_, w)| *w).fold(f64::MAX, f64::min);
+        let atm = variance
+            .iter()
+            .min_by(|a, b| a.0.abs().total_cmp(&b.0.abs()))
+            .map(|(k, _)| *k)
+            .unwrap();
+
+        // b and sigma in log space and rho through tanh keep the search unconstrained; the
+        // remaining no-arbitrage condition, non-negative minimum variance, is rejected outright
+        let slice = |x: &[f64]| SviSlice {
+            time_to_expiry,
+            a: x[0],
+            b: x[1].exp(),
+            rho: x[2].tanh(),
+            m: x[3],
+            sigma: x[4].exp(),
+            rmse: 0.0,
+        };
+        let objective = |x: &[f64]| {
+            let s = slice(x);
+            if s.a + s.b * s.sigma * (1.0 - s.rho * s.rho).sqrt() < 0.0 {
+                return f64::NAN;
+            }
+
+            variance.iter().map(|(k, w)| (s.total_variance(*k) - w).powi(2)).sum::<f64>()
+        };
+
+        let x0 = [min_variance * 0.5, (0.1f64).ln(), (-0.3f64).atanh(), atm, (0.1f64).ln()];
+        let minimum = optimize::nelder_mead(objective, &x0, 0.5, 1e-14, 10_000);
+        if !minimum.value.is_finite() {
+            return None;
+        }
+
+        let mut fitted = slice(&minimum.x);
+        fitted.rmse = (points.iter().map(|(k, iv)| (fitted.implied_vol(*k) - iv).powi(2)).sum::<f64>() / points.len() as f64).sqrt();
+
+        Some(fitted)
+    }
+}
+
+/// Expiries whose total variance falls at some log-moneyness, so a calendar spread there would
+/// cost less than nothing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CalendarViolation {
+    pub log_moneyness: f64,
+    pub near_expiry: f64,
+    pub far_expiry: f64,
+    pub near_variance: f64,
+    pub far_variance: f64,
+}
+
+/// Implied volatility surface built from an option chain: an SVI fit per expiry, interpolated
+/// linearly in total variance between expiries, sampled onto a regular grid.
+#[derive(Debug, Clone)]
+pub struct VolSurface {
+    pub quote_date: NaiveDate,
+    pub spot: f64,
+    /// Continuously compounded rate used for forwards
+    pub rate: f64,
+    /// Continuous dividend yield taken out of the forwards
+    pub dividend_yield: f64,
+    pub points: Vec<SurfacePoint>,
+    /// One per expiry with enough quotes, sorted by time to expiry
+    pub slices: Vec<SviSlice>,
+    pub grid_log_moneyness: Vec<f64>,
+    pub grid_expiries: Vec<f64>,
+    /// Implied volatility indexed by `[expiry, log_moneyness]`
+    pub grid: Array2<f64>,
+}
+
+impl VolSurface {
+    /// Builds the surface from out-of-the-money quotes, puts below the forward and calls above,
+    /// as those are the liquid side of each strike.
+    pub fn build(options: &[StockOption], quote_date: NaiveDate, spot: f64, rate: f64, dividend_yield: f64) -> Option<Self> {
+        let mut points = options
+            .iter()
+            .filter_map(|option| {
+                let days = (option.expiry_date - quote_date).num_days();
+                if days <= 0 || option.implied_volatility <= 0.0 || option.strike_price <= 0.0 {
+                    return None;
+                }
+
+                let time_to_expiry = days as f64 / DAYS_PER_YEAR;
+                let log_moneyness = (option.strike_price / forward(spot, rate, dividend_yield, time_to_expiry)).ln();
+
+                let out_of_the_money = match option.option_type {
+                    OptionType::Call => log_moneyness >= 0.0,
+                    OptionType::Put => log_moneyness < 0.0,
+                };
+
+                out_of_the_money.then_some(SurfacePoint {
+                    strike: option.strike_price,
+                    time_to_expiry,
+                    log_moneyness,
+                    implied_volatility: option.implied_volatility,
+                    delta: option.delta,
+                })
+            })
+            .collect::<Vec<_>>();
+        points.sort_by(|a, b| a.time_to_expiry.total_cmp(&b.time_to_expiry).then(a.log_moneyness.total_cmp(&b.log_moneyness)));
+
+        let slices = points
+            .chunk_by(|a, b| a.time_to_expiry == b.time_to_expiry)
+            .filter_map(|expiry| {
+                let quotes = expiry.iter().map(|p| (p.log_moneyness, p.implied_volatility)).collect::<Vec<_>>();
+                SviSlice::fit(expiry[0].time_to_expiry, &quotes)
+            })
+            .collect::<Vec<_>>();
+
+        if slices.is_empty() {
+            return None;
+        }
+
+        let k_min = points.iter().map(|p| p.log_moneyness).fold(f64::MAX, f64::min);
+        let k_max = points.iter().map(|p| p.log_moneyness).fold(f64::MIN, f64::max);
+        let t_min = slices.first().unwrap().time_to_expiry;
+        let t_max = slices.last().unwrap().time_to_expiry;
+
+        let mut surface = Self {
+            quote_date,
+            spot,
+            rate,
+            dividend_yield,
+            points,
+            slices,
+            grid_log_moneyness: linspace(k_min, k_max, 41),
+            grid_expiries: linspace(t_min, t_max, 20),
+            grid: Array2::zeros((0, 0)),
+        };
+        surface.grid = surface.sample(&surface.grid_log_moneyness, &surface.grid_expiries);
+
+        Some(surface)
+    }
+
+    /// Total implied variance at log-moneyness `k` and `time_to_expiry` years. Linear in total
+    /// variance between fitted expiries and constant in volatility outside of them.
+    pub fn total_variance(&self, k: f64, time_to_expiry: f64) -> f64 {
+        let first = self.slices.first().unwrap();
+        let last = self.slices.last().unwrap();
+
+        if time_to_expiry <= first.time_to_expiry {
+            return first.total_variance(k) * time_to_expiry / first.time_to_expiry;
+        }
+        if time_to_expiry >= last.time_to_expiry {
+            return last.total_variance(k) * time_to_expiry / last.time_to_expiry;
+        }
+
+        let upper = self.slices.iter().position(|s| s.time_to_expiry >= time_to_expiry).unwrap();
+        let (lo, hi) = (&self.slices[upper - 1], &self.slices[upper]);
+        let weight = (time_to_expiry - lo.time_to_expiry) / (hi.time_to_expiry - lo.time_to_expiry);
+
+        lo.total_variance(k) * (1.0 - weight) + hi.total_variance(k) * weight
+    }
+
+    /// Implied volatility at log-moneyness `k`
+    pub fn implied_vol_at(&self, k: f64, time_to_expiry: f64) -> f64 {
+        if time_to_expiry <= 0.0 {
+            return f64::NAN;
+        }
+
+        (self.total_variance(k, time_to_expiry).max(0.0) / time_to_expiry).sqrt()
+    }
+
+    /// Implied volatility for any strike and expiry date
+    pub fn implied_vol(&self, strike: f64, expiry: NaiveDate) -> f64 {
+        let time_to_expiry = (expiry - self.quote_date).num_days() as f64 / DAYS_PER_YEAR;
+        let forward = forward(self.spot, self.rate, self.dividend_yield, time_to_expiry);
+
+        self.implied_vol_at((strike / forward).ln(), time_to_expiry)
+    }
+
+    /// Every grid log-moneyness where a fitted slice's total variance is more than `tolerance`
+    /// below the previous expiry's. Total variance has to grow with expiry at fixed forward
+    /// moneyness or the surface admits calendar arbitrage.
+    pub fn calendar_violations(&self, tolerance: f64) -> Vec<CalendarViolation> {
+        self.slices
+            .windows(2)
+            .flat_map(|pair| {
+                self.grid_log_moneyness.iter().filter_map(move |&k| {
+                    let (near_variance, far_variance) = (pair[0].total_variance(k), pair[1].total_variance(k));
+
+                    (far_variance < near_variance - tolerance).then_some(CalendarViolation {
+                        log_moneyness: k,
+                        near_expiry: pair[0].time_to_expiry,
+                        far_expiry: pair[1].time_to_expiry,
+                        near_variance,
+                        far_variance,
+                    })
+                })
+            })
+            .collect()
+    }
+
+    /// Implied volatility on an arbitrary grid, indexed by `[expiry, log_moneyness]`
+    pub fn sample(&self, log_moneyness: &[f64], expiries: &[f64]) -> Array2<f64> {
+        Array2::from_shape_fn((expiries.len(), log_moneyness.len()), |(i, j)| {
+            self.implied_vol_at(log_moneyness[j], expiries[i])
+        })
+    }
+}
+
+/// At-the-money implied volatility of the nearest expiry at least `min_days` after
+/// `quote_date`. By put-call parity the forward sits where call and put mids are closest, so
+/// this averages the call and put IV at that strike and needs no spot or rate.
+pub fn atm_implied_vol(options: &[StockOption], quote_date: NaiveDate, min_days: i64) -> Option<f64> {
+    let expiry = options
+        .iter()
+        .map(|option| option.expiry_date)
+        .filter(|expiry| (*expiry - quote_date).num_days() >= min_days)
+        .min()?;
+
+    let quoted = |option_type: OptionType| {
+        options.iter().filter(move |option| {
+            option.expiry_date == expiry && option.option_type == option_type && option.implied_volatility > 0.0
+        })
+    };
+
+    quoted(OptionType::Call)
+        .filter_map(|call| {
+            let put = quoted(OptionType::Put).find(|put| put.strike_price == call.strike_price)?;
+            let gap = (black_scholes::mid_price(call)? - black_scholes::mid_price(put)?).abs();
+
+            Some((gap, (call.implied_volatility + put.implied_volatility) / 2.0))
+        })
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, iv)| iv)
+}
+
+/// Forward price of the underlying `time_to_expiry` years out
+fn forward(spot: f64, rate: f64, dividend_yield: f64, time_to_expiry: f64) -> f64 {
+    spot * ((rate - dividend_yield) * time_to_expiry).exp()
+}
+
+pub(crate) fn linspace(start: f64, end: f64, n: usize) -> Vec<f64> {
+    if n < 2 || start == end {
+        return vec![start];
+    }
+
+    (0..n).map(|i| start + (end - start) * i as f64 / (n - 1) as f64).collect()
+}
@@ -0,0 +1,135 @@
+use crate::data::series::SeriesView;
+
+#[derive(Debug, Clone, Copy)]
+pub struct DmiPoint {
+    pub plus_di: f64,
+    pub minus_di: f64,
+    pub adx: f64,
+}
+
+/// Wilder's directional movement system. +DI/-DI are NaN for the first `period` bars and ADX,
+/// which smooths DX over another `period` bars, for the first `2 * period - 1`.
+pub fn dmi_on_series(series: SeriesView, period: usize) -> Vec<DmiPoint> {
+    let n = series.len();
+    let nan = DmiPoint { plus_di: f64::NAN, minus_di: f64::NAN, adx: f64::NAN };
+    let mut ret = vec![nan; n];
+
+    if period == 0 || n <= period {
+        return ret;
+    }
+
+    let p = period as f64;
+
+    let mut smoothed_tr = 0.0;
+    let mut smoothed_plus_dm = 0.0;
+    let mut smoothed_minus_dm = 0.0;
+
+    let mut dx_sum = 0.0;
+    let mut adx = f64::NAN;
+
+    for (i, point) in ret.iter_mut().enumerate().skip(1) {
+        let up_move = series.high[i] - series.high[i - 1];
+        let down_move = series.low[i - 1] - series.low[i];
+
+        let plus_dm = if up_move > down_move && up_move > 0.0 { up_move } else { 0.0 };
+        let minus_dm = if down_move > up_move && down_move > 0.0 { down_move } else { 0.0 };
+
+        let prev_close = series.close[i - 1];
+        let true_range = (series.high[i] - series.low[i])
+            .max((series.high[i] - prev_close).abs())
+            .max((series.low[i] - prev_close).abs());
+
+        // Wilder smoothing starts from a plain sum of the first `period` moves
+        if i <= period {
+            smoothed_tr += true_range;
+            smoothed_plus_dm += plus_dm;
+            smoothed_minus_dm += minus_dm;
+
+            if i < period {
+                continue;
+            }
+        } else {
+            smoothed_tr = smoothed_tr - smoothed_tr / p + true_range;
+            smoothed_plus_dm = smoothed_plus_dm - smoothed_plus_dm / p + plus_dm;
+            smoothed_minus_dm = smoothed_minus_dm - smoothed_minus_dm / p + minus_dm;
+        }
+
+        let (plus_di, minus_di) = if smoothed_tr == 0.0 {
+            (0.0, 0.0)
+        } else {
+            (100.0 * smoothed_plus_dm / smoothed_tr, 100.0 * smoothed_minus_dm / smoothed_tr)
+        };
+
+        let di_sum = plus_di + minus_di;
+        let dx = if di_sum == 0.0 { 0.0 } else { 100.0 * (plus_di - minus_di).abs() / di_sum };
+
+        // First ADX is the mean of the first `period` DX values
+        let dx_count = i + 1 - period;
+        if dx_count < period {
+            dx_sum += dx;
+        } else if dx_count == period {
+            dx_sum += dx;
+            adx = dx_sum / p;
+        } else {
+            adx = (adx * (p - 1.0) + dx) / p;
+        }
+
+        *point = DmiPoint { plus_di, minus_di, adx };
+    }
+
+    ret
+}
+
+/// Wilder's Parabolic SAR with acceleration factor starting at `step` and capped at `max_step`.
+/// NaN for the first bar.
+pub fn psar_on_series(series: SeriesView, step: f64, max_step: f64) -> Vec<f64> {
+    let n = series.len();
+    let mut ret = vec![f64::NAN; n];
+
+    if n < 2 {
+        return ret;
+    }
+
+    let (high, low) = (series.high, series.low);
+
+    let mut rising = series.close[1] >= series.close[0];
+    let mut sar = if rising { low[0] } else { high[0] };
+    let mut extreme = if rising { high[0].max(high[1]) } else { low[0].min(low[1]) };
+    let mut af = step;
+    ret[1] = sar;
+
+    for i in 2..n {
+        sar += af * (extreme - sar);
+
+        if rising {
+            // SAR may never move into the previous two bars' range
+            sar = sar.min(low[i - 1]).min(low[i - 2]);
+
+            if low[i] < sar {
+                rising = false;
+                sar = extreme;
+                extreme = low[i];
+                af = step;
+            } else if high[i] > extreme {
+                extreme = high[i];
+                af = (af + step).min(max_step);
+            }
+        } else {
+            sar = sar.max(high[i - 1]).max(high[i - 2]);
+
+            if high[i] > sar {
+                rising = true;
+                sar = extreme;
+                extreme = high[i];
+                af = step;
+            } else if low[i] < extreme {
+                extreme = low[i];
+                af = (af + step).min(max_step);
+            }
+        }
+
+        ret[i] = sar;
+    }
+
+    ret
+}
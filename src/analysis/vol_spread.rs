@@ -0,0 +1,241 @@
+use chrono::NaiveDate;
+
+use crate::analysis::{garch::{self, GarchDiagnostics, GarchKind, GarchModel, VolForecastComparison}, streaming::RollingMoments, volatility::{self, RealizedVolEstimator, RealizedVolWindow}};
+use crate::data::{macro_series, series::SeriesView};
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VolSpreadParams {
+    /// Calendar days realized volatility is measured over, matching the implied vol's tenor
+    pub horizon_days: i64,
+    /// Tenor of the long implied vol, in calendar days
+    pub long_horizon_days: i64,
+    /// Calendar days of history the premium z-score is taken over
+    pub zscore_days: i64,
+    pub estimator: RealizedVolEstimator,
+    /// Model whose forecasts the implied term structure is compared against
+    pub garch_kind: GarchKind,
+    /// Leading bars the GARCH model is fitted on, its forecasts are NaN before them
+    pub garch_fit_bars: usize,
+}
+
+impl Default for VolSpreadParams {
+    fn default() -> Self {
+        Self {
+            horizon_days: 30,
+            long_horizon_days: 90,
+            zscore_days: 365,
+            estimator: RealizedVolEstimator::CloseToClose,
+            garch_kind: GarchKind::GjrGarch,
+            garch_fit_bars: 500,
+        }
+    }
+}
+
+/// Implied against realized volatility at one bar. Vols are annualized fractions, premiums are
+/// in annualized variance.
+#[derive(Debug, Clone, Copy)]
+pub struct VolSpreadPoint {
+    /// Latest implied vol known at the bar's close
+    pub implied: f64,
+    /// Realized over the trailing horizon
+    pub realized: f64,
+    /// Realized over the coming horizon, NaN until the series covers it. Looks ahead.
+    pub forward_realized: f64,
+    /// `implied² - forward_realized²`, what selling variance would have earned. Looks ahead.
+    pub variance_premium: f64,
+    /// `implied² - realized²`, the premium as it could be estimated at the time
+    pub expected_premium: f64,
+    /// Long tenor implied vol less `implied`, NaN without a long tenor
+    pub term_slope: f64,
+    /// GARCH forecast of the volatility over the coming horizon, as known at the bar
+    pub garch: f64,
+    /// `implied - garch`, how rich the options are against the model
+    pub garch_spread: f64,
+    /// GARCH forecast over the long tenor less `garch`, the model's counterpart to `term_slope`
+    pub garch_term_slope: f64,
+    /// Z-score of `expected_premium` against the trailing `zscore_days`
+    pub premium_zscore: f64,
+}
+
+/// [`VolSpreadPoint`] field known at the bar's close, used as a feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VolSpreadStat {
+    Implied,
+    Realized,
+    ExpectedPremium,
+    TermSlope,
+    PremiumZScore,
+    GarchSpread,
+    /// `term_slope - garch_term_slope`, how much steeper the implied term structure is than the model's
+    TermSlopeOverGarch,
+}
+
+impl VolSpreadStat {
+    pub fn of(self, point: &VolSpreadPoint) -> f64 {
+        match self {
+            VolSpreadStat::Implied => point.implied,
+            VolSpreadStat::Realized => point.realized,
+            VolSpreadStat::ExpectedPremium => point.expected_premium,
+            VolSpreadStat::TermSlope => point.term_slope,
+            VolSpreadStat::PremiumZScore => point.premium_zscore,
+            VolSpreadStat::GarchSpread => point.garch_spread,
+            VolSpreadStat::TermSlopeOverGarch => point.term_slope - point.garch_term_slope,
+        }
+    }
+}
+
+/// VIX levels, e.g. from [`crate::data::macro_series::fetch_macro_series`], as implied volatility fractions.
+pub fn vix_implied(vix: &[(NaiveDate, f64)]) -> Vec<(NaiveDate, f64)> {
+    vix.iter().map(|&(date, level)| (date, level / 100.0)).collect()
+}
+
+/// Aligns daily `implied` vols, and optionally a longer tenor in `long_implied` (empty to skip),
+/// with realized volatility of `series` and derives the variance risk premium, term structure slope
+/// and premium z-scores, plus the GARCH forecasts over both tenors to set the implied term
+/// structure against. Works on bars of any spacing since horizons are in calendar days. Fails
+/// on a horizon or z-score span that isn't a positive number of days.
+pub fn vol_spread_on_series(
+    series: SeriesView,
+    implied: &[(NaiveDate, f64)],
+    long_implied: &[(NaiveDate, f64)],
+    params: VolSpreadParams,
+) -> Result<Vec<VolSpreadPoint>, String> {
+    if params.horizon_days <= 0 || params.long_horizon_days <= 0 || params.zscore_days <= 0 {
+        return Err(format!(
+            "Vol spread horizons must be positive, got {} and {} days with a {} day z-score",
+            params.horizon_days, params.long_horizon_days, params.zscore_days
+        ));
+    }
+
+    let n = series.len();
+    let horizon = params.horizon_days * SECONDS_PER_DAY;
+    let zscore_span = params.zscore_days * SECONDS_PER_DAY;
+    let periods_per_year = volatility::periods_per_year(series);
+
+    let implied = macro_series::align_daily(series, implied);
+    let long_implied = macro_series::align_daily(series, long_implied);
+
+    let garch = garch::garch_forecast_on_series(
+        series,
+        params.garch_kind,
+        params.garch_fit_bars,
+        &[bars_over(params.horizon_days, periods_per_year), bars_over(params.long_horizon_days, periods_per_year)],
+    );
+
+    let mut points = Vec::with_capacity(n);
+    let mut trailing_start = 0;
+    let mut forward_end = 0;
+
+    // Bars `max(trailing_start, 1)..=i` and `i + 1..=forward_end`, each paired with the close before it
+    let mut trailing = RealizedVolWindow::new(params.estimator);
+    let mut forward = RealizedVolWindow::new(params.estimator);
+
+    for i in 0..n {
+        let t = series.t[i];
+
+        if i > 0 {
+            trailing.push(series, i);
+        }
+        // First bar inside the trailing horizon, the bar before it supplies the previous close
+        while series.t[trailing_start] <= t - horizon {
+            if trailing_start > 0 {
+                trailing.pop();
+            }
+            trailing_start += 1;
+        }
+        let realized = trailing.volatility(periods_per_year);
+
+        if i > 0 && forward_end >= i {
+            forward.pop();
+        }
+        forward_end = forward_end.max(i);
+        while forward_end + 1 < n && series.t[forward_end + 1] <= t + horizon {
+            forward_end += 1;
+            forward.push(series, forward_end);
+        }
+        let forward_realized = if series.t[n - 1] >= t + horizon { forward.volatility(periods_per_year) } else { f64::NAN };
+
+        points.push(VolSpreadPoint {
+            implied: implied[i],
+            realized,
+            forward_realized,
+            variance_premium: implied[i].powi(2) - forward_realized.powi(2),
+            expected_premium: implied[i].powi(2) - realized.powi(2),
+            term_slope: long_implied[i] - implied[i],
+            garch: garch[0][i],
+            garch_spread: implied[i] - garch[0][i],
+            garch_term_slope: garch[1][i] - garch[0][i],
+            premium_zscore: f64::NAN,
+        });
+    }
+
+    let premiums = points.iter().map(|p| p.expected_premium).collect::<Vec<_>>();
+    for (point, zscore) in points.iter_mut().zip(rolling_zscore(series.t, &premiums, zscore_span)) {
+        point.premium_zscore = zscore;
+    }
+
+    Ok(points)
+}
+
+/// The GARCH model behind [`VolSpreadPoint::garch`], as fitted on the leading
+/// `garch_fit_bars` bars.
+#[derive(Debug, Clone)]
+pub struct GarchFitReport {
+    pub diagnostics: GarchDiagnostics,
+    /// Forecasts over the horizon against realized volatility, on the bars after the fit only
+    pub forecast_vs_realized: VolForecastComparison,
+}
+
+/// Fits the model [`vol_spread_on_series`] forecasts with and scores it out of sample. `None`
+/// if `series` doesn't reach past the fitted bars or the fit fails.
+pub fn garch_fit_report(series: SeriesView, params: VolSpreadParams) -> Option<GarchFitReport> {
+    let fit_bars = params.garch_fit_bars;
+    if series.len() <= fit_bars + 1 {
+        return None;
+    }
+
+    let model = GarchModel::fit_on_series(series.slice(..=fit_bars), params.garch_kind)?;
+    let periods_per_year = volatility::periods_per_year(series);
+    let horizon = bars_over(params.horizon_days, periods_per_year);
+
+    Some(GarchFitReport {
+        diagnostics: model.diagnostics(),
+        forecast_vs_realized: model.compare_with_realized(series.slice(fit_bars..), horizon, params.estimator, periods_per_year),
+    })
+}
+
+/// Bars spanning `days` calendar days, at least one
+fn bars_over(days: i64, periods_per_year: f64) -> usize {
+    (days as f64 / 365.0 * periods_per_year).round().max(1.0) as usize
+}
+
+/// Z-score of each value against the non-NaN values timestamped within the trailing `span`
+/// seconds, itself included. NaN with fewer than 20 values in the window.
+fn rolling_zscore(t: &[i64], values: &[f64], span: i64) -> Vec<f64> {
+    let mut moments = RollingMoments::unbounded();
+    let mut start = 0;
+
+    (0..values.len())
+        .map(|i| {
+            if !values[i].is_nan() {
+                moments.push(values[i]);
+            }
+
+            while t[start] <= t[i] - span {
+                if !values[start].is_nan() {
+                    moments.pop();
+                }
+                start += 1;
+            }
+
+            if moments.len() < 20 || values[i].is_nan() {
+                return f64::NAN;
+            }
+
+            let std = moments.sample_variance().sqrt();
+            if std > 0.0 { (values[i] - moments.mean()) / std } else { f64::NAN }
+        })
+        .collect()
+}
@@ -1,74 +1,389 @@
-use ndarray::Array3;
+use std::{error::Error, f64::consts::LN_2};
 
-use crate::data::types::{OptionChain, TickerData, TickerDataframe};
+use chrono::NaiveDate;
 
-pub fn ticker_volatility_n_series(data: &Vec<TickerDataframe>) -> f32 {
-    let closing_prices = data.iter().map(|el| {
-        el.close
-    }).collect::<Vec<_>>();
+use crate::analysis::{moving_average, streaming::{RollingExtremes, RollingMoments, StdDevStream}, surface::VolSurface};
+use crate::data::{macro_series::{self, MacroSeries}, series::SeriesView, types::{MarketSession, OptionChain, TickerData}};
 
-    let avg = closing_prices.iter().sum::<f32>() / closing_prices.len() as f32;
+pub const TRADING_DAYS_PER_YEAR: f64 = 252.0;
+const SECONDS_PER_YEAR: f64 = 365.25 * 86_400.0;
 
-    let squared_delta = closing_prices.iter().map(|el| {
-        (el - avg).powf(2.0)
-    }).collect::<Vec<_>>();
+/// Population standard deviation of raw closes. Scales with the price level, so use
+/// [`realized_volatility`] to compare symbols.
+pub fn ticker_volatility_n_series(data: SeriesView) -> f64 {
+    let closing_prices = data.close;
+
+    let avg = closing_prices.iter().sum::<f64>() / closing_prices.len() as f64;
 
-    let variance = squared_delta.iter().sum::<f32>() / closing_prices.len() as f32;
+    let variance = closing_prices.iter().map(|el| {
+        (el - avg).powf(2.0)
+    }).sum::<f64>() / closing_prices.len() as f64;
 
     let std_dev = variance.sqrt();
 
     std_dev
 }
 
-pub fn get_vix_along_data(data: &TickerData) -> Vec<TickerDataframe> {
-    use crate::data::collection::*;
-    let dataframes = &data.price_data;
+/// Population standard deviation of closes over a rolling `period`-bar window, NaN until the
+/// first window is full.
+pub fn rolling_std_on_series(series: SeriesView, period: usize) -> Vec<f64> {
+    let mut std_dev = StdDevStream::new(period);
+
+    series.close
+        .iter()
+        .map(|&close| std_dev.push(close))
+        .collect()
+}
+
+/// Realized volatility estimator over log returns and log price ranges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RealizedVolEstimator {
+    /// Sample standard deviation of close-to-close log returns
+    CloseToClose,
+    /// High-low range, assumes no drift and no opening gaps
+    Parkinson,
+    /// Open-high-low-close, assumes no drift and no opening gaps
+    GarmanKlass,
+    /// Open-high-low-close, unbiased under drift
+    RogersSatchell,
+    /// Combines overnight, open-to-close and Rogers-Satchell variance, handles drift and gaps
+    YangZhang,
+}
+
+/// Bars per year used to annualize per-bar volatility.
+///
+/// Derived from the bar spacing alone, never from how many bars the series holds, so the value
+/// at a bar doesn't depend on the bars after it. Intraday bars count [`TRADING_DAYS_PER_YEAR`]
+/// days of 6.5 regular hours, 16 hours once the series has pre-market or after-hours bars and
+/// 24 with overnight ones. Daily bars count [`TRADING_DAYS_PER_YEAR`] and longer bars the
+/// calendar periods per year.
+pub fn periods_per_year(series: SeriesView) -> f64 {
+    if series.len() < 2 {
+        return TRADING_DAYS_PER_YEAR;
+    }
 
-    let mut vol_series: Vec<TickerDataframe> = Vec::new();
+    let mut gaps = series.t.windows(2).map(|w| w[1] - w[0]).collect::<Vec<_>>();
+    gaps.sort_unstable();
+    let median_gap = gaps[gaps.len() / 2].max(1) as f64;
 
-    futures::executor::block_on(async {
-        let resp = get_ticker_data(
-            "VIX",
-            crate::data::types::TickerDatatype::HistPrice(dataframes.get(0).unwrap().t.clone(), dataframes.last().unwrap().t.clone()),
-            crate::data::types::PointTimeDelta::Day
-        ).await.unwrap();
+    if median_gap < 20.0 * 3_600.0 {
+        let day_hours = if series.session.contains(&MarketSession::Overnight) {
+            24.0
+        } else if series.session.iter().any(|session| session.is_extended_hours()) {
+            16.0
+        } else {
+            6.5
+        };
 
-        vol_series = resp.price_data;
-    });
-    
-    vol_series.clone()
+        TRADING_DAYS_PER_YEAR * (day_hours * 3_600.0 / median_gap).max(1.0)
+    } else if median_gap < 2.0 * 86_400.0 {
+        TRADING_DAYS_PER_YEAR
+    } else {
+        SECONDS_PER_YEAR / median_gap
+    }
 }
 
-pub fn calculate_volatility_surface(options: &OptionChain) -> Array3<f32> {
-    let surface = Array3::<f32>::zeros((3, 1, 1));
+/// Sample variance, 0 for fewer than two values.
+fn sample_variance(values: impl Iterator<Item = f64> + Clone) -> f64 {
+    let n = values.clone().count();
+    if n < 2 {
+        return 0.0;
+    }
 
-    let ticker = options.data.get(0).unwrap().contract_id
-        .chars()
-        .take_while(|c| !c.is_ascii_digit() || c.is_ascii_digit() && c == &'0')
-        .collect::<String>();
+    let mean = values.clone().sum::<f64>() / n as f64;
+    values.map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1) as f64
+}
 
-    let ivs = options.data
-        .iter()
-        .map(|el| el.implied_volatility)
-        .collect::<Vec<_>>();
-    
-    let time_to_exp = options.data
-        .iter()
-        .map(|el| {
-            let expiration = el.expiry_date;
-            let delta = expiration.signed_duration_since(expiration);
-            delta.num_days()
+/// Per-bar variance over every bar of `window` but the first, which only supplies the previous close.
+fn window_variance(window: SeriesView, estimator: RealizedVolEstimator) -> f64 {
+    let bars = 1..window.len();
+    let n = bars.len() as f64;
+
+    let (open, high, low, close) = (window.open, window.high, window.low, window.close);
+    let rogers_satchell = |i: usize| {
+        (high[i] / close[i]).ln() * (high[i] / open[i]).ln() + (low[i] / close[i]).ln() * (low[i] / open[i]).ln()
+    };
+
+    match estimator {
+        RealizedVolEstimator::CloseToClose => {
+            sample_variance(bars.map(|i| (close[i] / close[i - 1]).ln()))
+        }
+        RealizedVolEstimator::Parkinson => {
+            bars.map(|i| (high[i] / low[i]).ln().powi(2)).sum::<f64>() / (4.0 * LN_2 * n)
+        }
+        RealizedVolEstimator::GarmanKlass => {
+            bars.map(|i| {
+                0.5 * (high[i] / low[i]).ln().powi(2) - (2.0 * LN_2 - 1.0) * (close[i] / open[i]).ln().powi(2)
+            }).sum::<f64>() / n
+        }
+        RealizedVolEstimator::RogersSatchell => {
+            bars.map(rogers_satchell).sum::<f64>() / n
+        }
+        RealizedVolEstimator::YangZhang => {
+            let overnight = sample_variance(bars.clone().map(|i| (open[i] / close[i - 1]).ln()));
+            let open_to_close = sample_variance(bars.clone().map(|i| (close[i] / open[i]).ln()));
+            let rs = bars.map(rogers_satchell).sum::<f64>() / n;
+
+            let k = 0.34 / (1.34 + (n + 1.0) / (n - 1.0));
+            overnight + k * open_to_close + (1.0 - k) * rs
+        }
+    }
+}
+
+/// Annualized realized volatility over the whole of `series`. NaN for fewer than three bars.
+pub fn realized_volatility(series: SeriesView, estimator: RealizedVolEstimator, periods_per_year: f64) -> f64 {
+    if series.len() < 3 {
+        return f64::NAN;
+    }
+
+    (window_variance(series, estimator).max(0.0) * periods_per_year).sqrt()
+}
+
+/// Per-bar terms of a realized volatility estimate over a window that bars enter at the back
+/// and leave at the front, so rolling windows are updated instead of recomputed. Matches
+/// [`realized_volatility`] over the same bars and their previous close.
+#[derive(Debug, Clone)]
+pub struct RealizedVolWindow {
+    estimator: RealizedVolEstimator,
+    returns: RollingMoments,
+    overnight: RollingMoments,
+    open_to_close: RollingMoments,
+    ranges: RollingMoments,
+}
+
+impl RealizedVolWindow {
+    pub fn new(estimator: RealizedVolEstimator) -> Self {
+        Self {
+            estimator,
+            returns: RollingMoments::unbounded(),
+            overnight: RollingMoments::unbounded(),
+            open_to_close: RollingMoments::unbounded(),
+            ranges: RollingMoments::unbounded(),
+        }
+    }
+
+    /// Adds bar `i` of `series`, which also needs the close of bar `i - 1`
+    pub fn push(&mut self, series: SeriesView, i: usize) {
+        let (open, high, low, close) = (series.open, series.high, series.low, series.close);
+
+        let range = match self.estimator {
+            RealizedVolEstimator::CloseToClose => 0.0,
+            RealizedVolEstimator::Parkinson => (high[i] / low[i]).ln().powi(2),
+            RealizedVolEstimator::GarmanKlass => {
+                0.5 * (high[i] / low[i]).ln().powi(2) - (2.0 * LN_2 - 1.0) * (close[i] / open[i]).ln().powi(2)
+            }
+            RealizedVolEstimator::RogersSatchell | RealizedVolEstimator::YangZhang => {
+                (high[i] / close[i]).ln() * (high[i] / open[i]).ln() + (low[i] / close[i]).ln() * (low[i] / open[i]).ln()
+            }
+        };
+
+        self.ranges.push(range);
+        match self.estimator {
+            RealizedVolEstimator::CloseToClose => self.returns.push((close[i] / close[i - 1]).ln()),
+            RealizedVolEstimator::YangZhang => {
+                self.overnight.push((open[i] / close[i - 1]).ln());
+                self.open_to_close.push((close[i] / open[i]).ln());
+            }
+            _ => {}
+        }
+    }
+
+    /// Drops the oldest bar
+    pub fn pop(&mut self) {
+        self.ranges.pop();
+        match self.estimator {
+            RealizedVolEstimator::CloseToClose => {
+                self.returns.pop();
+            }
+            RealizedVolEstimator::YangZhang => {
+                self.overnight.pop();
+                self.open_to_close.pop();
+            }
+            _ => {}
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Annualized volatility of the bars in the window, NaN for fewer than two
+    pub fn volatility(&self, periods_per_year: f64) -> f64 {
+        if self.len() < 2 {
+            return f64::NAN;
+        }
+
+        let n = self.len() as f64;
+        let window_variance = match self.estimator {
+            RealizedVolEstimator::CloseToClose => self.returns.sample_variance(),
+            RealizedVolEstimator::Parkinson => self.ranges.mean() / (4.0 * LN_2),
+            RealizedVolEstimator::GarmanKlass | RealizedVolEstimator::RogersSatchell => self.ranges.mean(),
+            RealizedVolEstimator::YangZhang => {
+                let k = 0.34 / (1.34 + (n + 1.0) / (n - 1.0));
+                self.overnight.sample_variance() + k * self.open_to_close.sample_variance() + (1.0 - k) * self.ranges.mean()
+            }
+        };
+
+        (window_variance.max(0.0) * periods_per_year).sqrt()
+    }
+}
+
+/// Annualized realized volatility over a rolling window of `period` bars. Each bar also needs the
+/// previous close, so the first `period` bars are NaN. Rolls the per-bar terms forward instead of
+/// recomputing each window, so long intraday series stay linear in their length.
+pub fn realized_vol_on_series(series: SeriesView, period: usize, estimator: RealizedVolEstimator, periods_per_year: f64) -> Vec<f64> {
+    let mut out = vec![f64::NAN; series.len()];
+    if period < 2 {
+        return out;
+    }
+
+    let mut window = RealizedVolWindow::new(estimator);
+    for (i, vol) in out.iter_mut().enumerate().skip(1) {
+        window.push(series, i);
+        if window.len() > period {
+            window.pop();
+        }
+
+        if window.len() == period {
+            *vol = window.volatility(periods_per_year);
+        }
+    }
+
+    out
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BollingerPoint {
+    pub middle: f64,
+    pub upper: f64,
+    pub lower: f64,
+    /// Where the close sits between the bands, 0 at the lower band and 1 at the upper
+    pub percent_b: f64,
+    /// Band width relative to the middle band
+    pub bandwidth: f64,
+}
+
+/// Upper, middle and lower line of a price channel.
+#[derive(Debug, Clone, Copy)]
+pub struct BandPoint {
+    pub upper: f64,
+    pub middle: f64,
+    pub lower: f64,
+}
+
+/// Bollinger Bands: SMA of closes ± `num_std` population standard deviations over `period` bars.
+pub fn bollinger_on_series(series: SeriesView, period: usize, num_std: f64) -> Vec<BollingerPoint> {
+    let middle = moving_average::sma_on_series(series, period as i32);
+    let std_dev = rolling_std_on_series(series, period);
+
+    (0..series.len())
+        .map(|i| {
+            let upper = middle[i] + num_std * std_dev[i];
+            let lower = middle[i] - num_std * std_dev[i];
+
+            // The bands collapse onto the middle over a flat window
+            let percent_b = if upper == lower { 0.5 } else { (series.close[i] - lower) / (upper - lower) };
+
+            BollingerPoint {
+                middle: middle[i],
+                upper,
+                lower,
+                percent_b,
+                bandwidth: (upper - lower) / middle[i],
+            }
         })
-        .collect::<Vec<_>>();
+        .collect()
+}
 
-    let moneyness = options.data
-        .iter()
-        .map(|el| {
-            // let market_price = 
-            0.0
+/// True range of each bar. The first bar has no previous close, so it is just high - low.
+pub fn true_range_on_series(series: SeriesView) -> Vec<f64> {
+    (0..series.len())
+        .map(|i| {
+            let range = series.high[i] - series.low[i];
+            if i == 0 {
+                return range;
+            }
+
+            let prev_close = series.close[i - 1];
+            range
+                .max((series.high[i] - prev_close).abs())
+                .max((series.low[i] - prev_close).abs())
         })
-        .collect::<Vec<_>>();
+        .collect()
+}
+
+/// Wilder's average true range, NaN for the first `period - 1` bars.
+pub fn atr_on_series(series: SeriesView, period: usize) -> Vec<f64> {
+    let true_range = true_range_on_series(series);
+    let n = true_range.len();
+    let mut ret = vec![f64::NAN; n];
+
+    if period == 0 || n < period {
+        return ret;
+    }
 
-    surface
+    let mut atr = true_range.iter().take(period).sum::<f64>() / period as f64;
+    ret[period - 1] = atr;
+
+    for i in period..n {
+        atr = (atr * (period as f64 - 1.0) + true_range[i]) / period as f64;
+        ret[i] = atr;
+    }
+
+    ret
+}
+
+/// Keltner Channels: EMA of closes ± `multiplier` ATRs.
+pub fn keltner_on_series(series: SeriesView, ema_period: usize, atr_period: usize, multiplier: f64) -> Vec<BandPoint> {
+    let middle = moving_average::ema_on_values(series.close, ema_period);
+    let atr = atr_on_series(series, atr_period);
+
+    (0..series.len())
+        .map(|i| BandPoint {
+            upper: middle[i] + multiplier * atr[i],
+            middle: middle[i],
+            lower: middle[i] - multiplier * atr[i],
+        })
+        .collect()
+}
+
+/// Donchian channel: highest high and lowest low of the last `period` bars, including the current one.
+pub fn donchian_on_series(series: SeriesView, period: usize) -> Vec<BandPoint> {
+    let mut extremes = RollingExtremes::new(period);
+
+    (0..series.len())
+        .map(|i| {
+            extremes.push(series.high[i], series.low[i]);
+            if !extremes.is_full() {
+                return BandPoint { upper: f64::NAN, middle: f64::NAN, lower: f64::NAN };
+            }
+
+            let (upper, lower) = (extremes.highest(), extremes.lowest());
+            BandPoint { upper, middle: (upper + lower) / 2.0, lower }
+        })
+        .collect()
+}
+
+/// VIX close known at each bar of `data`, NaN before the first one. Only a daily series, so
+/// intraday bars see the previous day's close until the session's last bar.
+pub async fn get_vix_along_data(data: &TickerData) -> Result<Vec<f64>, Box<dyn Error + Send + Sync>> {
+    let series = data.series();
+    if series.is_empty() {
+        return Ok(Vec::new());
+    }
+    let (first, last) = (series.datetime(0), series.datetime(series.len() - 1));
+
+    let vix = macro_series::fetch_macro_series(MacroSeries::Vix, first.date_naive(), last.date_naive()).await?;
+
+    Ok(macro_series::align_daily(series, &vix))
 }
 
+/// Implied volatility surface of `options` as quoted on `quote_date` with the underlying at `spot`.
+/// `None` if no expiry has enough out-of-the-money quotes to fit.
+pub fn calculate_volatility_surface(options: &OptionChain, quote_date: NaiveDate, spot: f64, rate: f64, dividend_yield: f64) -> Option<VolSurface> {
+    VolSurface::build(&options.data, quote_date, spot, rate, dividend_yield)
+}
@@ -0,0 +1,128 @@
+use crate::{analysis::streaming::{RollingSum, VwapAnchor, VwapStream}, data::series::SeriesView};
+
+/// On-Balance Volume, starting from 0 at the first bar.
+pub fn obv_on_series(series: SeriesView) -> Vec<f64> {
+    let mut obv = 0.0;
+
+    (0..series.len())
+        .map(|i| {
+            if i > 0 {
+                if series.close[i] > series.close[i - 1] {
+                    obv += series.vol[i] as f64;
+                } else if series.close[i] < series.close[i - 1] {
+                    obv -= series.vol[i] as f64;
+                }
+            }
+            obv
+        })
+        .collect()
+}
+
+/// Chaikin Money Flow: money flow volume over total volume across `period` bars.
+/// NaN for the first `period - 1` bars.
+pub fn cmf_on_series(series: SeriesView, period: usize) -> Vec<f64> {
+    let money_flow_volume = (0..series.len())
+        .map(|i| {
+            let range = series.high[i] - series.low[i];
+            if range == 0.0 {
+                return 0.0;
+            }
+
+            let multiplier = ((series.close[i] - series.low[i]) - (series.high[i] - series.close[i])) / range;
+            multiplier * series.vol[i] as f64
+        })
+        .collect::<Vec<_>>();
+
+    let mut flow = RollingSum::new(period.max(1));
+    let mut volume = 0i64;
+
+    (0..series.len())
+        .map(|i| {
+            flow.push(money_flow_volume[i]);
+            volume += series.vol[i];
+            if period > 0 && i >= period {
+                volume -= series.vol[i - period];
+            }
+
+            if period == 0 || i + 1 < period {
+                return f64::NAN;
+            }
+
+            if volume == 0 {
+                0.0
+            } else {
+                flow.sum / volume as f64
+            }
+        })
+        .collect()
+}
+
+/// Money Flow Index, a volume-weighted RSI of the typical price. NaN for the first `period` bars.
+pub fn mfi_on_series(series: SeriesView, period: usize) -> Vec<f64> {
+    let n = series.len();
+    let typical_price = (0..n)
+        .map(|i| (series.high[i] + series.low[i] + series.close[i]) / 3.0)
+        .collect::<Vec<_>>();
+
+    // Signed money flow of each bar vs the previous one, 0 for the first bar
+    let flow = (0..n)
+        .map(|i| {
+            if i == 0 {
+                return 0.0;
+            }
+
+            let raw = typical_price[i] * series.vol[i] as f64;
+            if typical_price[i] > typical_price[i - 1] {
+                raw
+            } else if typical_price[i] < typical_price[i - 1] {
+                -raw
+            } else {
+                0.0
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut positive = RollingSum::new(period.max(1));
+    let mut negative = RollingSum::new(period.max(1));
+
+    (0..n)
+        .map(|i| {
+            positive.push(flow[i].max(0.0));
+            negative.push((-flow[i]).max(0.0));
+
+            if period == 0 || i < period {
+                return f64::NAN;
+            }
+
+            if negative.sum == 0.0 {
+                100.0
+            } else {
+                100.0 - 100.0 / (1.0 + positive.sum / negative.sum)
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct VwapBandPoint {
+    pub vwap: f64,
+    /// Volume-weighted standard deviation of the typical price around the VWAP
+    pub std_dev: f64,
+    pub upper: f64,
+    pub lower: f64,
+}
+
+/// VWAP accumulated from `anchor`, with bands `num_std` volume-weighted standard deviations away.
+/// NaN before the anchor and while no volume has traded since it.
+pub fn vwap_bands_on_series(series: SeriesView, anchor: VwapAnchor, num_std: f64) -> Vec<VwapBandPoint> {
+    let mut stream = VwapStream::new(anchor);
+
+    (0..series.len())
+        .map(|i| {
+            let vwap = stream.push(series.t[i], series.high[i], series.low[i], series.close[i], series.vol[i]);
+            let std_dev = if vwap.is_nan() { f64::NAN } else { stream.std_dev() };
+
+            VwapBandPoint { vwap, std_dev, upper: vwap + num_std * std_dev, lower: vwap - num_std * std_dev }
+        })
+        .collect()
+}
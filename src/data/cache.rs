@@ -0,0 +1,189 @@
+use std::{collections::HashMap, hash::{DefaultHasher, Hash, Hasher}, mem::size_of, sync::{Arc, LazyLock, Mutex}};
+
+use crate::analysis::features::featureset;
+
+use super::{series::PriceSeries, types::{MarketSession, TickerData, TickerDataframe}};
+
+/// Upper bound on the memory held by cached price histories.
+pub const PRICE_CACHE_BYTES: usize = 512 * 1024 * 1024;
+
+pub static PRICE_CACHE: LazyLock<Mutex<LruCache<CacheKey, Arc<TickerData>>>> = LazyLock::new(|| {
+    Mutex::new(LruCache::new(PRICE_CACHE_BYTES))
+});
+
+/// Bumped on every write to a symbol so keys built before the write no longer match.
+static SYMBOL_GENERATION: LazyLock<Mutex<HashMap<String, u64>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    pub symbol: String,
+    pub range: Option<(String, String)>,
+    pub timeframe: Option<String>,
+    pub feature_version: Option<u32>,
+    /// Bar count and hash of the timestamps and closes, for keys built from loaded data
+    pub fingerprint: Option<(usize, u64)>,
+    generation: u64,
+}
+
+impl CacheKey {
+    pub fn new(symbol: impl ToString, range: Option<(String, String)>, timeframe: Option<String>) -> Self {
+        let symbol = symbol.to_string();
+        let generation = SYMBOL_GENERATION.lock().unwrap().get(&symbol).copied().unwrap_or(0);
+
+        Self { symbol, range, timeframe, feature_version: None, fingerprint: None, generation }
+    }
+
+    /// Key for a series that is already loaded: ranged by its first and last timestamp, with its
+    /// bar spacing as the timeframe and a fingerprint of its contents, so two different series
+    /// with the same endpoints don't share entries.
+    pub fn for_series(data: &TickerData) -> Self {
+        let bars = data.series();
+        let range = (!bars.is_empty())
+            .then(|| (bars.datetime(0).to_rfc3339(), bars.datetime(bars.len() - 1).to_rfc3339()));
+
+        let mut gaps = bars.t.windows(2).map(|w| w[1] - w[0]).collect::<Vec<_>>();
+        gaps.sort_unstable();
+        let timeframe = gaps.get(gaps.len() / 2).map(|gap| format!("{gap}s"));
+
+        let mut hasher = DefaultHasher::new();
+        for (t, close) in bars.t.iter().zip(bars.close) {
+            t.hash(&mut hasher);
+            close.to_bits().hash(&mut hasher);
+        }
+
+        Self { fingerprint: Some((bars.len(), hasher.finish())), ..Self::new(&data.symbol, range, timeframe) }
+    }
+
+    pub fn with_feature_version(mut self, version: u32) -> Self {
+        self.feature_version = Some(version);
+        self
+    }
+}
+
+/// Approximate heap footprint of a cached value, used to keep caches within their byte budget.
+pub trait CacheWeight {
+    fn weight(&self) -> usize;
+}
+
+impl CacheWeight for TickerDataframe {
+    fn weight(&self) -> usize {
+        size_of::<Self>()
+    }
+}
+
+impl CacheWeight for TickerData {
+    fn weight(&self) -> usize {
+        size_of::<Self>() + self.symbol.capacity() + self.price_data.weight()
+    }
+}
+
+impl CacheWeight for PriceSeries {
+    fn weight(&self) -> usize {
+        size_of::<Self>()
+            + self.t.capacity() * size_of::<i64>()
+            + (self.open.capacity() + self.high.capacity() + self.low.capacity() + self.close.capacity()) * size_of::<f64>()
+            + self.vol.capacity() * size_of::<i64>()
+            + self.vol_weighted.capacity() * size_of::<f64>()
+            + self.session.capacity() * size_of::<MarketSession>()
+    }
+}
+
+impl<T: CacheWeight> CacheWeight for Arc<T> {
+    fn weight(&self) -> usize {
+        self.as_ref().weight()
+    }
+}
+
+impl<T: CacheWeight> CacheWeight for Vec<T> {
+    fn weight(&self) -> usize {
+        self.iter().map(|el| el.weight()).sum()
+    }
+}
+
+struct CacheEntry<V> {
+    value: V,
+    weight: usize,
+    last_used: u64,
+}
+
+/// Least-recently-used cache bounded by the total [`CacheWeight`] of its values.
+///
+/// Entries are large series rather than many small items, so eviction simply scans for
+/// the oldest entry.
+pub struct LruCache<K, V> {
+    entries: HashMap<K, CacheEntry<V>>,
+    max_bytes: usize,
+    used_bytes: usize,
+    tick: u64,
+}
+
+impl<K: Eq + Hash + Clone, V: CacheWeight + Clone> LruCache<K, V> {
+    pub fn new(max_bytes: usize) -> Self {
+        Self { entries: HashMap::new(), max_bytes, used_bytes: 0, tick: 0 }
+    }
+
+    pub fn get(&mut self, key: &K) -> Option<V> {
+        self.tick += 1;
+        let tick = self.tick;
+
+        self.entries.get_mut(key).map(|entry| {
+            entry.last_used = tick;
+            entry.value.clone()
+        })
+    }
+
+    pub fn insert(&mut self, key: K, value: V) {
+        let weight = value.weight();
+        self.remove(&key);
+
+        if weight > self.max_bytes {
+            return;
+        }
+
+        while self.used_bytes + weight > self.max_bytes {
+            let oldest = self.entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(k, _)| k.clone());
+
+            match oldest {
+                Some(k) => self.remove(&k),
+                None => break,
+            }
+        }
+
+        self.tick += 1;
+        self.used_bytes += weight;
+        self.entries.insert(key, CacheEntry { value, weight, last_used: self.tick });
+    }
+
+    pub fn remove(&mut self, key: &K) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.used_bytes -= entry.weight;
+        }
+    }
+
+    pub fn retain(&mut self, mut keep: impl FnMut(&K) -> bool) {
+        let removed = self.entries.keys().filter(|k| !keep(k)).cloned().collect::<Vec<_>>();
+        for key in removed {
+            self.remove(&key);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.used_bytes = 0;
+    }
+
+    pub fn used_bytes(&self) -> usize {
+        self.used_bytes
+    }
+}
+
+/// Drops everything cached for `symbol`. Called by every DB writer so readers never see stale data.
+pub fn invalidate_symbol(symbol: &str) {
+    *SYMBOL_GENERATION.lock().unwrap().entry(symbol.to_string()).or_insert(0) += 1;
+
+    PRICE_CACHE.lock().unwrap().retain(|key| key.symbol != symbol);
+    featureset::invalidate_symbol(symbol);
+}
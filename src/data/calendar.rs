@@ -0,0 +1,122 @@
+use chrono::{DateTime, Datelike, Days, NaiveDate, NaiveTime, Utc, Weekday};
+use chrono_tz::{America::New_York, Tz};
+
+/// NYSE regular session open, New York time
+pub const REGULAR_OPEN: NaiveTime = NaiveTime::from_hms_opt(9, 30, 0).unwrap();
+pub const REGULAR_CLOSE: NaiveTime = NaiveTime::from_hms_opt(16, 0, 0).unwrap();
+/// Close on the day after Thanksgiving, Christmas Eve and the day before Independence Day
+pub const EARLY_CLOSE: NaiveTime = NaiveTime::from_hms_opt(13, 0, 0).unwrap();
+
+/// Unscheduled full-day closures since 2001: the September 11 attacks, Hurricane Sandy and national
+/// days of mourning. Closures before 2001 aren't listed, so earlier dates may be taken as trading days.
+const SPECIAL_CLOSURES: [(i32, u32, u32); 10] = [
+    (2001, 9, 11),
+    (2001, 9, 12),
+    (2001, 9, 13),
+    (2001, 9, 14),
+    (2004, 6, 11),
+    (2007, 1, 2),
+    (2012, 10, 29),
+    (2012, 10, 30),
+    (2018, 12, 5),
+    (2025, 1, 9),
+];
+
+/// Whether the NYSE is closed for a holiday on `date`. Weekends are not holidays.
+pub fn is_holiday(date: NaiveDate) -> bool {
+    let year = date.year();
+    let ymd = |month, day| NaiveDate::from_ymd_opt(year, month, day).unwrap();
+
+    // New Year's Day falling on a Saturday is not observed on the Friday before
+    let new_year = ymd(1, 1);
+    let new_year = if new_year.weekday() == Weekday::Sun { new_year + Days::new(1) } else { new_year };
+
+    let mut holidays = vec![
+        new_year,
+        nth_weekday(year, 1, Weekday::Mon, 3),
+        nth_weekday(year, 2, Weekday::Mon, 3),
+        easter(year) - Days::new(2),
+        last_weekday(year, 5, Weekday::Mon),
+        observed(ymd(7, 4)),
+        nth_weekday(year, 9, Weekday::Mon, 1),
+        nth_weekday(year, 11, Weekday::Thu, 4),
+        observed(ymd(12, 25)),
+    ];
+    if year >= 2022 {
+        holidays.push(observed(ymd(6, 19)));
+    }
+
+    holidays.contains(&date)
+        || SPECIAL_CLOSURES.iter().any(|&(y, m, d)| NaiveDate::from_ymd_opt(y, m, d) == Some(date))
+}
+
+pub fn is_trading_day(date: NaiveDate) -> bool {
+    !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) && !is_holiday(date)
+}
+
+/// Close of the regular session on `date`, None when the exchange is closed
+pub fn regular_close(date: NaiveDate) -> Option<NaiveTime> {
+    if !is_trading_day(date) {
+        return None;
+    }
+
+    let day_after_thanksgiving = nth_weekday(date.year(), 11, Weekday::Thu, 4) + Days::new(1);
+    let early = date == day_after_thanksgiving
+        || (date.month(), date.day()) == (12, 24)
+        || (date.month(), date.day()) == (7, 3);
+
+    Some(if early { EARLY_CLOSE } else { REGULAR_CLOSE })
+}
+
+/// Regular session open and close of `date` as UTC instants, None when the exchange is closed
+pub fn regular_session(date: NaiveDate) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let close = regular_close(date)?;
+    let at = |time| date.and_time(time).and_local_timezone(New_York).single().map(|t: DateTime<Tz>| t.to_utc());
+
+    Some((at(REGULAR_OPEN)?, at(close)?))
+}
+
+pub fn previous_trading_day(date: NaiveDate) -> NaiveDate {
+    let mut day = date - Days::new(1);
+    while !is_trading_day(day) {
+        day = day - Days::new(1);
+    }
+    day
+}
+
+pub fn next_trading_day(date: NaiveDate) -> NaiveDate {
+    let mut day = date + Days::new(1);
+    while !is_trading_day(day) {
+        day = day + Days::new(1);
+    }
+    day
+}
+
+/// Saturday holidays are observed on the Friday before, Sunday ones on the Monday after
+fn observed(date: NaiveDate) -> NaiveDate {
+    match date.weekday() {
+        Weekday::Sat => date - Days::new(1),
+        Weekday::Sun => date + Days::new(1),
+        _ => date,
+    }
+}
+
+fn nth_weekday(year: i32, month: u32, weekday: Weekday, n: u8) -> NaiveDate {
+    NaiveDate::from_weekday_of_month_opt(year, month, weekday, n).unwrap()
+}
+
+fn last_weekday(year: i32, month: u32, weekday: Weekday) -> NaiveDate {
+    NaiveDate::from_weekday_of_month_opt(year, month, weekday, 5).unwrap_or_else(|| nth_weekday(year, month, weekday, 4))
+}
+
+/// Gregorian Easter Sunday (anonymous Gregorian algorithm)
+fn easter(year: i32) -> NaiveDate {
+    let a = year % 19;
+    let (b, c) = (year / 100, year % 100);
+    let g = (b - (b + 8) / 25 + 1) / 3;
+    let h = (19 * a + b - b / 4 - g + 15) % 30;
+    let l = (32 + 2 * (b % 4) + 2 * (c / 4) - h - c % 4) % 7;
+    let f = h + l - 7 * ((a + 11 * h + 22 * l) / 451) + 114;
+
+    NaiveDate::from_ymd_opt(year, (f / 31) as u32, (f % 31 + 1) as u32).unwrap()
+}
@@ -1,12 +1,13 @@
 use lazy_static::lazy_static;
+use std::error::Error;
+use std::fmt;
 use std::result::Result;
 use std::thread;
 use std::time::Duration;
 use chrono::DateTime;
-use chrono::Datelike;
 use chrono_tz::Tz;
 use std::sync::atomic::{AtomicI32, Ordering};
-use super::types::*;
+use super::{series::PriceSeries, types::*};
 
 
 lazy_static! {
@@ -61,7 +62,7 @@ lazy_static! {
 }
 // static mut API_CALL_COUNT: i32 = 0;
 
-pub async fn get_ticker_data(ticker: impl ToString, datatype: TickerDatatype, point_time_delta: PointTimeDelta,) -> Result<TickerData, reqwest::Error> {
+pub async fn get_ticker_data(ticker: impl ToString, datatype: TickerDatatype, point_time_delta: PointTimeDelta,) -> Result<TickerData, Box<dyn Error + Send + Sync>> {
     let (from, to) = match &datatype {
         TickerDatatype::HistPrice(from, to) => (from, to),
         TickerDatatype::HistVolume(from, to) => (from, to),
@@ -100,7 +101,7 @@ pub async fn get_ticker_data(ticker: impl ToString, datatype: TickerDatatype, po
         }
 
         let resp_text = req.send().await?.text().await?;
-        let resp_json: serde_json::Value = serde_json::from_str(&resp_text).unwrap();
+        let resp_json: serde_json::Value = serde_json::from_str(&resp_text)?;
         println!("{resp_text}");
 
         let bars = resp_json["bars"][ticker.to_string()]
@@ -123,18 +124,22 @@ pub async fn get_ticker_data(ticker: impl ToString, datatype: TickerDatatype, po
     resp.insert("bars".to_string(), serde_json::Value::Object(bars_map));
     let resp = serde_json::Value::Object(resp).to_string();
 
-    let resp: serde_json::Value = serde_json::from_str(&resp).unwrap();
+    let resp: serde_json::Value = serde_json::from_str(&resp)?;
 
-    let price_data = resp["bars"][ticker.to_string()]
+    let bars = resp["bars"][ticker.to_string()]
         .as_array()
-        .unwrap_or(&vec![])
-        .iter()
-        .map(|item| TickerDataframe {
-            t: item["t"].as_str().unwrap_or("").to_string(),
-            open: item["o"].as_f64().unwrap_or(0.0) as f32,
-            high: item["h"].as_f64().unwrap_or(0.0) as f32,
-            close: item["c"].as_f64().unwrap_or(0.0) as f32,
-            low: item["l"].as_f64().unwrap_or(0.0) as f32,
+        .cloned()
+        .unwrap_or_default();
+
+    let mut price_data = PriceSeries::with_capacity(bars.len());
+    for item in &bars {
+        let t = parse_timestamp(item["t"].as_str().unwrap_or_default())?;
+        price_data.push(&TickerDataframe {
+            t,
+            open: item["o"].as_f64().unwrap_or(0.0),
+            high: item["h"].as_f64().unwrap_or(0.0),
+            close: item["c"].as_f64().unwrap_or(0.0),
+            low: item["l"].as_f64().unwrap_or(0.0),
             vol: if let Some(v) = item["v"].as_f64() {
                 v as i64
             } else if let Some(v) = item["v"].as_str() {
@@ -142,9 +147,10 @@ pub async fn get_ticker_data(ticker: impl ToString, datatype: TickerDatatype, po
             } else {
                 0
             },
-            vol_weighted: item["vw"].as_f64().unwrap_or(0.0) as f32,
-        })
-        .collect();
+            vol_weighted: item["vw"].as_f64().unwrap_or(0.0),
+            session: MarketSession::for_bar(t, &point_time_delta),
+        });
+    }
 
     // println!("{}", resp);
     // if matches!(&datatype, TickerDatatype::HistPrice(_, _)) {
@@ -193,7 +199,71 @@ pub async fn get_etf_holdings(etf: Etf, n: i32) -> Result<Vec<(String, f32)>, re
     Ok(symbols)
 }
 
-pub async fn get_options_chain(ticker: impl ToString, date: Option<DateTime<Tz>>) -> Result<OptionChain, reqwest::Error> {
+/// Why an Alpha Vantage request didn't return data. The API answers failures with HTTP 200 and
+/// a message in place of the payload.
+#[derive(Debug)]
+pub enum AlphaVantageError {
+    Request(reqwest::Error),
+    /// `Note`: the per-minute call limit was hit
+    RateLimited(String),
+    /// `Information`: a daily or premium limit, or the key's plan doesn't cover the endpoint
+    Information(String),
+    /// `Error Message`: the request itself was rejected, e.g. an unknown symbol
+    Rejected(String),
+    /// The body was neither data nor one of the messages above
+    Malformed(serde_json::Error),
+}
+
+impl AlphaVantageError {
+    /// Whether the same request may succeed if sent again later
+    pub fn is_transient(&self) -> bool {
+        match self {
+            AlphaVantageError::Request(_) | AlphaVantageError::RateLimited(_) => true,
+            AlphaVantageError::Information(message) => message.contains("rate limit"),
+            AlphaVantageError::Rejected(_) | AlphaVantageError::Malformed(_) => false,
+        }
+    }
+
+    /// Error carried by a response body, if it is one of the API's messages
+    fn from_body(body: &serde_json::Value) -> Option<Self> {
+        let message = |key: &str| body[key].as_str().map(str::to_string);
+
+        message("Note")
+            .map(AlphaVantageError::RateLimited)
+            .or_else(|| message("Information").map(AlphaVantageError::Information))
+            .or_else(|| message("Error Message").map(AlphaVantageError::Rejected))
+    }
+}
+
+impl fmt::Display for AlphaVantageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AlphaVantageError::Request(e) => write!(f, "Alpha Vantage request failed: {e}"),
+            AlphaVantageError::RateLimited(message) => write!(f, "Alpha Vantage rate limit: {message}"),
+            AlphaVantageError::Information(message) => write!(f, "Alpha Vantage: {message}"),
+            AlphaVantageError::Rejected(message) => write!(f, "Alpha Vantage rejected the request: {message}"),
+            AlphaVantageError::Malformed(e) => write!(f, "Unexpected Alpha Vantage response: {e}"),
+        }
+    }
+}
+
+impl Error for AlphaVantageError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AlphaVantageError::Request(e) => Some(e),
+            AlphaVantageError::Malformed(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for AlphaVantageError {
+    fn from(e: reqwest::Error) -> Self {
+        AlphaVantageError::Request(e)
+    }
+}
+
+pub async fn get_options_chain(ticker: impl ToString, date: Option<DateTime<Tz>>) -> Result<OptionChain, AlphaVantageError> {
     let client = reqwest::Client::new();
 
     let symbol = ticker.to_string().clone();
@@ -203,13 +273,12 @@ pub async fn get_options_chain(ticker: impl ToString, date: Option<DateTime<Tz>>
         .query(
             &{
                 let mut params = vec![
-                    ("function", "HISTORICAL_OPTIONS"),
-                    ("symbol", symbol.as_str()),
-                    ("apikey", ALPHA_VANTAGE_API_KEY.as_str()),
+                    ("function", "HISTORICAL_OPTIONS".to_string()),
+                    ("symbol", symbol.clone()),
+                    ("apikey", ALPHA_VANTAGE_API_KEY.to_string()),
                 ];
                 if let Some(day) = date {
-                    let date_str = format!("{}-{}-{}", day.year(), day.month(), day.day());
-                    params.push(("date", Box::leak(date_str.into_boxed_str())));
+                    params.push(("date", day.format("%Y-%m-%d").to_string()));
                 }
                 params
             }
@@ -217,9 +286,22 @@ pub async fn get_options_chain(ticker: impl ToString, date: Option<DateTime<Tz>>
         .send()
         .await?;
         
-    let json = response.text().await.unwrap().replace("\"endpoint\": \"Historical Options\",", "").replace("\"message\": \"success\",", "");
+    let body: serde_json::Value = serde_json::from_str(&response.text().await?).map_err(AlphaVantageError::Malformed)?;
+    if let Some(e) = AlphaVantageError::from_body(&body) {
+        return Err(e);
+    }
 
-    let serialized_data: OptionChain = serde_json::from_str(&json).unwrap();
+    // Besides `data` the payload only has the endpoint name and a status message
+    let mut serialized_data: OptionChain = serde_json::from_value(body).map_err(AlphaVantageError::Malformed)?;
+
+    // Rows are stored per underlying and quote date, so make sure both are always set
+    let quote_date = date.map(|day| day.date_naive());
+    for option in &mut serialized_data.data {
+        if option.symbol.is_empty() {
+            option.symbol = symbol.clone();
+        }
+        option.date = option.date.or(quote_date);
+    }
 
     Ok(serialized_data)
 }
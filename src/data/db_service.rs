@@ -1,13 +1,15 @@
-use std::{error::Error, sync::LazyLock};
+use std::{collections::BTreeMap, error::Error, sync::{Arc, LazyLock}};
 
 use chrono::{Datelike, Days, NaiveDate};
+use futures::{stream::BoxStream, StreamExt};
 use lazy_static::lazy_static;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use surrealdb::{
-    engine::local::{Db, RocksDb}, RecordId, Surreal
+    engine::local::{Db, RocksDb}, Action, Notification, RecordId, Surreal
 };
 
-use crate::data::{db_service::etf_tables::{HIST_PRICE_DATA, STOCK, TECHNICALS}, types::*};
+use crate::analysis::{indicator::IndicatorRegistry, options::events::EarningsEvent, surface};
+use crate::data::{cache::{self, CacheKey}, calendar, db_service::etf_tables::{ETF_HOLDING, HIST_PRICE_DATA, OPTION_QUOTE, STOCK, TECHNICALS, TRADE}, series::PriceSeries, types::*};
 
 static DB: LazyLock<Surreal<Db>> = LazyLock::new(Surreal::init);
 
@@ -31,16 +33,67 @@ mod etf_tables {
     pub const STOCK: &str = "stock";
     pub const TRADE: &str = "trade";
     pub const ETF_HOLDING: &str = "etf_holding";
+    pub const OPTION_QUOTE: &str = "option_quote";
 }
 
+/// Price history stored column-wise. Records written before bar times were kept have no `t`
+/// or `session`, they are rebuilt and written back the first time the record is loaded.
 #[derive(Serialize, Deserialize, Clone, Debug)]
 struct DbHistEntries {
-    close: Vec<f32>,
-    high: Vec<f32>,
-    low: Vec<f32>,
-    open: Vec<f32>,
+    #[serde(default)]
+    t: Vec<i64>,
+    #[serde(default)]
+    session: Vec<MarketSession>,
+    close: Vec<f64>,
+    high: Vec<f64>,
+    low: Vec<f64>,
+    open: Vec<f64>,
     volume: Vec<i64>,
-    volume_weighted: Vec<f32>,
+    volume_weighted: Vec<f64>,
+}
+
+impl From<&PriceSeries> for DbHistEntries {
+    fn from(series: &PriceSeries) -> Self {
+        Self {
+            t: series.t.clone(),
+            session: series.session.clone(),
+            close: series.close.clone(),
+            high: series.high.clone(),
+            low: series.low.clone(),
+            open: series.open.clone(),
+            volume: series.vol.clone(),
+            volume_weighted: series.vol_weighted.clone(),
+        }
+    }
+}
+
+/// A chain row keyed by underlying, quote date and contract so re-downloading a day overwrites it.
+#[derive(Serialize, Debug)]
+struct OptionQuoteRow {
+    key: String,
+    quote: StockOption,
+}
+
+#[derive(Deserialize, Debug)]
+struct LiveHistEntries {
+    symbol: String,
+    #[serde(flatten)]
+    entries: DbHistEntries,
+}
+
+#[derive(Deserialize, Debug)]
+struct LiveRecord<T> {
+    id: RecordId,
+    #[serde(flatten)]
+    record: T,
+}
+
+/// A change pushed by one of the live queries opened in [`live_events`].
+#[derive(Debug, Clone)]
+pub enum DbEvent {
+    PriceHistory(LiveAction, TickerData),
+    Holding(LiveAction, RecordId, EtfHolding),
+    Trade(LiveAction, RecordId, Trade),
 }
 
 
@@ -49,6 +102,12 @@ pub async fn init_database() -> Result<(), surrealdb::Error> {
     // let db = Surreal::new::<RocksDb>("../../db_instance").await?;
     DB.connect::<RocksDb>("../../db_instance").await?;
 
+    DB.use_ns("ticker_data").use_db("etfs").await?;
+    DB.query(format!("
+        DEFINE INDEX IF NOT EXISTS {OPTION_QUOTE}_chain ON TABLE {OPTION_QUOTE} FIELDS symbol, date;
+        DEFINE INDEX IF NOT EXISTS {OPTION_QUOTE}_contract ON TABLE {OPTION_QUOTE} FIELDS contractID, date;
+    ")).await?.check()?;
+
     // DB.signin(Root {
     //     username: "root",
     //     password: "root",
@@ -107,18 +166,10 @@ pub async fn insert_etf(etf: Etf, data: TickerData) -> Result<(), Box<dyn Error>
 
     println!("{:#?}", etf_components);
 
-    let hist_data_entries: Option<DbHistEntries> = DB
-        .upsert((HIST_PRICE_DATA, &data.symbol))
-        .content(DbHistEntries {
-            close: hist_data.iter().map(|el| el.close).collect(),
-            high: hist_data.iter().map(|el| el.high).collect(),
-            low: hist_data.iter().map(|el| el.low).collect(),
-            open: hist_data.iter().map(|el| el.open).collect(),
-            volume: hist_data.iter().map(|el| el.vol).collect(),
-            volume_weighted: hist_data.iter().map(|el| el.vol_weighted).collect(),
-        })
-        .await.unwrap();
+    let data = TickerData { price_data: hist_data, ..data };
+    upsert_price_history(&data).await?;
     println!("hist_data");
+    upsert_technicals(&data, &IndicatorRegistry::with_defaults()).await?;
     
     let ticker: Option<EtfTicker> = DB
         .upsert(("Ticker", &data.symbol))
@@ -141,18 +192,10 @@ pub async fn populate_stock(symbol: String) -> Result<(), Box<dyn Error>> {
         symbol,
         TickerDatatype::HistOHCL("2016-01-01".to_string(), "2025-01-01".to_string()),
         PointTimeDelta::Minute(10)
-    ).await?;
-
-    let data_record: Option<DbHistEntries> = DB
-        .upsert((HIST_PRICE_DATA, data.symbol.clone()))
-        .merge(DbHistEntries {
-            open: data.price_data.iter().map(|el| el.open).collect(),
-            close: data.price_data.iter().map(|el| el.close).collect(),
-            high: data.price_data.iter().map(|el| el.high).collect(),
-            low: data.price_data.iter().map(|el| el.low).collect(),
-            volume: data.price_data.iter().map(|el| el.vol).collect(),
-            volume_weighted: data.price_data.iter().map(|el| el.vol_weighted).collect(),
-        }).await?;
+    ).await.map_err(|e| e.to_string())?;
+
+    upsert_price_history(&data).await?;
+    upsert_technicals(&data, &IndicatorRegistry::with_defaults()).await?;
 
     let ticker: Option<StockTicker> = DB
         .upsert((STOCK, &data.symbol))
@@ -166,6 +209,53 @@ pub async fn populate_stock(symbol: String) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// Stores the bars of `data` on `historical_price_data:<symbol>`, replacing any stored before.
+pub async fn upsert_price_history(data: &TickerData) -> Result<(), Box<dyn Error>> {
+    DB.use_ns("ticker_data").use_db("etfs").await?;
+
+    let _: Option<DbHistEntries> = DB
+        .upsert((HIST_PRICE_DATA, data.symbol.as_str()))
+        .content(DbHistEntries::from(&data.price_data))
+        .await?;
+    cache::invalidate_symbol(&data.symbol);
+
+    Ok(())
+}
+
+/// Removes the bars stored for `symbol`.
+pub async fn delete_price_history(symbol: &str) -> Result<(), Box<dyn Error>> {
+    DB.use_ns("ticker_data").use_db("etfs").await?;
+
+    let _: Option<DbHistEntries> = DB.delete((HIST_PRICE_DATA, symbol)).await?;
+    cache::invalidate_symbol(symbol);
+
+    Ok(())
+}
+
+/// Computes every indicator in `registry` over `data` and stores them on `technicals:<symbol>`,
+/// one array per indicator output with NULL while the indicator warms up.
+pub async fn upsert_technicals(data: &TickerData, registry: &IndicatorRegistry) -> Result<(), Box<dyn Error>> {
+    DB.use_ns("ticker_data").use_db("etfs").await?;
+
+    let series = data.series();
+
+    let mut columns: BTreeMap<String, Vec<Option<f64>>> = BTreeMap::new();
+    for (_, indicator) in registry.iter() {
+        let outputs = indicator.compute(series);
+        for (name, values) in indicator.column_names().into_iter().zip(outputs) {
+            columns.insert(name, values.into_iter().map(|v| (!v.is_nan()).then_some(v)).collect());
+        }
+    }
+
+    DB.query("UPSERT type::thing($table, $symbol) CONTENT $technicals")
+        .bind(("table", TECHNICALS))
+        .bind(("symbol", data.symbol.clone()))
+        .bind(("technicals", columns))
+        .await?;
+
+    Ok(())
+}
+
 pub async fn get_ticker(symbol: String) -> Result<StockTicker, Box<dyn Error>> {
     DB.use_ns("ticker_data").use_db("etfs").await?;
 
@@ -178,10 +268,18 @@ pub async fn get_ticker(symbol: String) -> Result<StockTicker, Box<dyn Error>> {
     Ok(ticker.unwrap())
 }
 
-pub async fn get_etf(etf: Etf) -> Result<TickerData, Box<dyn Error>> {
-    DB.use_ns("ticker_data").use_db("etfs").await?;
+pub async fn get_etf(etf: Etf) -> Result<Arc<TickerData>, Box<dyn Error>> {
+    get_price_history(etf.as_ref()).await
+}
 
-    let symbol = etf.as_ref();
+/// Bars stored for `symbol`, empty if there are none.
+pub async fn get_price_history(symbol: &str) -> Result<Arc<TickerData>, Box<dyn Error>> {
+    let key = CacheKey::new(symbol, None, None);
+    if let Some(cached) = cache::PRICE_CACHE.lock().unwrap().get(&key) {
+        return Ok(cached);
+    }
+
+    DB.use_ns("ticker_data").use_db("etfs").await?;
 
     let mut data = DB.query(format!("
         SELECT * FROM {HIST_PRICE_DATA} WHERE id = {HIST_PRICE_DATA}:{symbol}
@@ -194,41 +292,338 @@ pub async fn get_etf(etf: Etf) -> Result<TickerData, Box<dyn Error>> {
     // println!("{:#?}", data);
 
     let decoded_price_data: Option<DbHistEntries> = data.take(0)?;
+    let legacy = decoded_price_data.as_ref().is_some_and(DbHistEntries::is_legacy);
 
     // println!("{:#?}", decoded_price_data);
 
-    let price_data = if let Some(hist) = decoded_price_data {
-        let len = hist.close.len();
-        let mut frames = Vec::with_capacity(len);
-        for i in 0..len {
-            let date = NaiveDate::from_ymd_opt(2016, 1, 1)
-                .unwrap()
-                .checked_add_days(Days::new(i as u64))
-                .unwrap();
-            frames.push(TickerDataframe {
-                close: hist.close[i],
-                high: hist.high[i],
-                low: hist.low[i],
-                open: hist.open[i],
-                vol: hist.volume[i],
-                vol_weighted: hist.volume_weighted[i],
-                t: date.to_string(),
-            });
-        }
-        frames
-    } else {
-        Vec::new()
-    };
+    let price_data = decoded_price_data
+        .map(hist_entries_to_series)
+        .transpose()?
+        .unwrap_or_default();
+
+    if legacy {
+        let _: Option<DbHistEntries> = DB
+            .upsert((HIST_PRICE_DATA, symbol))
+            .content(DbHistEntries::from(&price_data))
+            .await?;
+    }
 
     // let technicals: Option<Vec<Technicals>> = data.take(1)?;
     let technicals = Option::Some(Vec::new());
 
 
-    let ticker_data = TickerData {
+    let ticker_data = Arc::new(TickerData {
         symbol: symbol.to_string(),
         price_data: price_data,
         technicals: technicals.unwrap(),
-    };
+    });
+    cache::PRICE_CACHE.lock().unwrap().insert(key, ticker_data.clone());
     
     Ok(ticker_data)
 }
+
+
+/// Stores every row of `chain` that has a quote date, returns how many were stored.
+pub async fn insert_option_chain(chain: &OptionChain) -> Result<usize, Box<dyn Error>> {
+    DB.use_ns("ticker_data").use_db("etfs").await?;
+
+    let rows = chain.data
+        .iter()
+        .filter_map(|option| {
+            let date = option.date?;
+            Some(OptionQuoteRow {
+                key: format!("{}_{}_{}", option.symbol, date, option.contract_id),
+                quote: option.clone(),
+            })
+        })
+        .collect::<Vec<_>>();
+    let stored = rows.len();
+
+    DB.query("FOR $row IN $rows { UPSERT type::thing($table, $row.key) CONTENT $row.quote; }")
+        .bind(("table", OPTION_QUOTE))
+        .bind(("rows", rows))
+        .await?
+        .check()?;
+
+    Ok(stored)
+}
+
+/// Attempts per day before [`download_option_chains`] gives up on it
+const CHAIN_DOWNLOAD_ATTEMPTS: u32 = 3;
+
+/// What [`download_option_chains`] stored and which days it couldn't.
+#[derive(Debug, Clone, Default)]
+pub struct ChainDownload {
+    /// Rows stored
+    pub stored: usize,
+    /// Trading days the vendor returned no quotes for
+    pub empty_days: Vec<NaiveDate>,
+    /// Trading days that still failed after retrying, with the last error
+    pub failed_days: Vec<(NaiveDate, String)>,
+}
+
+/// Downloads and stores the chain of `symbol` for every trading day in `from..=to`. Rate limits
+/// and failed requests are retried after a minute, up to [`CHAIN_DOWNLOAD_ATTEMPTS`] times; days
+/// that still fail or come back empty are reported rather than skipped. Only database errors
+/// abort the download.
+pub async fn download_option_chains(symbol: String, from: NaiveDate, to: NaiveDate) -> Result<ChainDownload, Box<dyn Error>> {
+    let mut download = ChainDownload::default();
+
+    for date in from.iter_days().take_while(|day| *day <= to).filter(|day| calendar::is_trading_day(*day)) {
+        let close = date
+            .and_hms_opt(16, 0, 0)
+            .and_then(|dt| dt.and_local_timezone(chrono_tz::America::New_York).single());
+
+        let mut attempt = 1;
+        let chain = loop {
+            match super::collection::get_options_chain(&symbol, close).await {
+                Err(e) if e.is_transient() && attempt < CHAIN_DOWNLOAD_ATTEMPTS => {
+                    eprintln!("[WARN] {e}, retrying {symbol} on {date} in a minute");
+                    tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+                    attempt += 1;
+                }
+                result => break result,
+            }
+        };
+
+        match chain {
+            Ok(chain) if chain.data.is_empty() => download.empty_days.push(date),
+            Ok(chain) => {
+                download.stored += insert_option_chain(&chain).await?;
+                println!("Stored {} option quotes for {symbol} on {date}", chain.data.len());
+            }
+            Err(e) => {
+                eprintln!("[ERROR] Could not download {symbol} options on {date}: {e}");
+                download.failed_days.push((date, e.to_string()));
+            }
+        }
+    }
+
+    Ok(download)
+}
+
+/// The most recent stored chain of `symbol` quoted on or before `date`.
+pub async fn get_option_chain_as_of(symbol: String, date: NaiveDate) -> Result<OptionChain, Box<dyn Error>> {
+    DB.use_ns("ticker_data").use_db("etfs").await?;
+
+    let mut response = DB
+        .query("LET $latest = (SELECT VALUE date FROM type::table($table) WHERE symbol = $symbol AND date <= $date ORDER BY date DESC LIMIT 1)[0]")
+        .query("SELECT * FROM type::table($table) WHERE symbol = $symbol AND date = $latest ORDER BY expiration, strike")
+        .bind(("table", OPTION_QUOTE))
+        .bind(("symbol", symbol))
+        .bind(("date", date))
+        .await?;
+
+    let data: Vec<StockOption> = response.take(1)?;
+
+    Ok(OptionChain { data })
+}
+
+/// Chains quoted at the close of the last session before `event` and the first one after it.
+/// Stored snapshots are used when there is one for that exact day, the rest is fetched from the vendor.
+pub async fn get_event_chains(symbol: String, event: EarningsEvent) -> Result<(OptionChain, OptionChain), Box<dyn Error>> {
+    let mut chains = Vec::with_capacity(2);
+
+    for date in [event.pre_date(), event.post_date()] {
+        let stored = get_option_chain_as_of(symbol.clone(), date).await?;
+
+        if stored.data.first().and_then(|option| option.date) == Some(date) {
+            chains.push(stored);
+            continue;
+        }
+
+        let close = date
+            .and_hms_opt(16, 0, 0)
+            .and_then(|dt| dt.and_local_timezone(chrono_tz::America::New_York).single());
+        chains.push(super::collection::get_options_chain(&symbol, close).await?);
+    }
+
+    let post = chains.pop().unwrap();
+    let pre = chains.pop().unwrap();
+
+    Ok((pre, post))
+}
+
+/// Every stored quote of one contract, oldest first.
+pub async fn get_contract_history(contract_id: String) -> Result<Vec<StockOption>, Box<dyn Error>> {
+    DB.use_ns("ticker_data").use_db("etfs").await?;
+
+    let mut response = DB
+        .query("SELECT * FROM type::table($table) WHERE contractID = $contract ORDER BY date")
+        .bind(("table", OPTION_QUOTE))
+        .bind(("contract", contract_id))
+        .await?;
+
+    Ok(response.take(0)?)
+}
+
+/// At-the-money implied volatility of `symbol` for each stored quote date in `from..=to`, taken
+/// from the nearest expiry at least `min_days` out. See [`surface::atm_implied_vol`].
+pub async fn get_atm_iv_series(symbol: String, from: NaiveDate, to: NaiveDate, min_days: i64) -> Result<Vec<(NaiveDate, f64)>, Box<dyn Error>> {
+    DB.use_ns("ticker_data").use_db("etfs").await?;
+
+    let mut response = DB
+        .query("SELECT * FROM type::table($table) WHERE symbol = $symbol AND date >= $from AND date <= $to")
+        .bind(("table", OPTION_QUOTE))
+        .bind(("symbol", symbol))
+        .bind(("from", from))
+        .bind(("to", to))
+        .await?;
+
+    let quotes: Vec<StockOption> = response.take(0)?;
+
+    let mut by_date: BTreeMap<NaiveDate, Vec<StockOption>> = BTreeMap::new();
+    for quote in quotes {
+        if let Some(date) = quote.date {
+            by_date.entry(date).or_default().push(quote);
+        }
+    }
+
+    Ok(by_date
+        .into_iter()
+        .filter_map(|(date, chain)| surface::atm_implied_vol(&chain, date, min_days).map(|iv| (date, iv)))
+        .collect())
+}
+
+
+/// First day legacy records were downloaded from, they hold one daily bar per trading day after it.
+const LEGACY_HISTORY_START: NaiveDate = NaiveDate::from_ymd_opt(2016, 1, 1).unwrap();
+
+impl DbHistEntries {
+    /// Whether the record was written before bar times and sessions were kept.
+    fn is_legacy(&self) -> bool {
+        self.t.len() != self.close.len() || self.session.len() != self.close.len()
+    }
+}
+
+/// Rebuilds what legacy records are missing: bar times as consecutive trading days from
+/// [`LEGACY_HISTORY_START`] and sessions from the bar times.
+fn backfill_hist_entries(mut hist: DbHistEntries) -> Result<DbHistEntries, String> {
+    let len = hist.close.len();
+
+    if hist.t.is_empty() {
+        let mut day = LEGACY_HISTORY_START;
+        if !calendar::is_trading_day(day) {
+            day = calendar::next_trading_day(day);
+        }
+
+        hist.t = Vec::with_capacity(len);
+        for _ in 0..len {
+            hist.t.push(parse_timestamp(&day.to_string())?.timestamp());
+            day = calendar::next_trading_day(day);
+        }
+    }
+    if hist.t.len() != len {
+        return Err(format!("stored price history has {} bars but {} timestamps", len, hist.t.len()));
+    }
+
+    if hist.session.len() != len {
+        let daily = hist.t.windows(2).all(|w| w[1] - w[0] >= 24 * 60 * 60);
+        hist.session = hist.t.iter()
+            .map(|&t| match chrono::DateTime::from_timestamp(t, 0) {
+                Some(t) if !daily => MarketSession::of(t),
+                _ => MarketSession::FullDay,
+            })
+            .collect();
+    }
+
+    Ok(hist)
+}
+
+fn hist_entries_to_series(hist: DbHistEntries) -> Result<PriceSeries, String> {
+    let hist = if hist.is_legacy() { backfill_hist_entries(hist)? } else { hist };
+
+    Ok(PriceSeries {
+        t: hist.t,
+        open: hist.open,
+        high: hist.high,
+        low: hist.low,
+        close: hist.close,
+        vol: hist.volume,
+        vol_weighted: hist.volume_weighted,
+        session: hist.session,
+    })
+}
+
+fn live_action(action: Action) -> Option<LiveAction> {
+    match action {
+        Action::Create => Some(LiveAction::Create),
+        Action::Update => Some(LiveAction::Update),
+        Action::Delete => Some(LiveAction::Delete),
+        _ => None,
+    }
+}
+
+/// Current rows of `table` with their record ids
+async fn get_records<T: DeserializeOwned>(table: &str) -> Result<Vec<(RecordId, T)>, surrealdb::Error> {
+    DB.use_ns("ticker_data").use_db("etfs").await?;
+
+    let records: Vec<LiveRecord<T>> = DB.select(table).await?;
+
+    Ok(records.into_iter().map(|el| (el.id, el.record)).collect())
+}
+
+pub async fn get_trades() -> Result<Vec<(RecordId, Trade)>, surrealdb::Error> {
+    get_records(TRADE).await
+}
+
+pub async fn get_holdings() -> Result<Vec<(RecordId, EtfHolding)>, surrealdb::Error> {
+    get_records(ETF_HOLDING).await
+}
+
+/// Opens LIVE SELECT queries on price history, ETF holdings and trades and merges
+/// their notifications into a single stream of [`DbEvent`]s.
+pub async fn live_events() -> Result<BoxStream<'static, DbEvent>, surrealdb::Error> {
+    DB.use_ns("ticker_data").use_db("etfs").await?;
+
+    let mut response = DB
+        .query(format!("LIVE SELECT *, meta::id(id) AS symbol FROM {HIST_PRICE_DATA}"))
+        .query(format!("LIVE SELECT * FROM {ETF_HOLDING}"))
+        .query(format!("LIVE SELECT * FROM {TRADE}"))
+        .await?;
+
+    let price_history = response
+        .stream::<Notification<LiveHistEntries>>(0)?
+        .filter_map(|res| futures::future::ready(match res {
+            Ok(n) => live_action(n.action).and_then(|action| {
+                cache::invalidate_symbol(&n.data.symbol);
+                match hist_entries_to_series(n.data.entries) {
+                    Ok(price_data) => Some(DbEvent::PriceHistory(action, TickerData {
+                        symbol: n.data.symbol,
+                        price_data,
+                        technicals: Vec::new(),
+                    })),
+                    Err(e) => {
+                        eprintln!("[ERROR] could not read {} from {HIST_PRICE_DATA}: {e}", n.data.symbol);
+                        None
+                    }
+                }
+            }),
+            Err(e) => {
+                eprintln!("[ERROR] live query on {HIST_PRICE_DATA} failed: {e}");
+                None
+            }
+        }));
+
+    let holdings = response
+        .stream::<Notification<LiveRecord<EtfHolding>>>(1)?
+        .filter_map(|res| futures::future::ready(match res {
+            Ok(n) => live_action(n.action).map(|action| DbEvent::Holding(action, n.data.id, n.data.record)),
+            Err(e) => {
+                eprintln!("[ERROR] live query on {ETF_HOLDING} failed: {e}");
+                None
+            }
+        }));
+
+    let trades = response
+        .stream::<Notification<LiveRecord<Trade>>>(2)?
+        .filter_map(|res| futures::future::ready(match res {
+            Ok(n) => live_action(n.action).map(|action| DbEvent::Trade(action, n.data.id, n.data.record)),
+            Err(e) => {
+                eprintln!("[ERROR] live query on {TRADE} failed: {e}");
+                None
+            }
+        }));
+
+    Ok(futures::stream::select_all(vec![price_history.boxed(), holdings.boxed(), trades.boxed()]).boxed())
+}
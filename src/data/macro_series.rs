@@ -0,0 +1,160 @@
+use std::{error::Error, path::PathBuf};
+
+use chrono::NaiveDate;
+
+use crate::analysis::streaming::vwap_session_day;
+use crate::data::{calendar, series::SeriesView, types::MarketSession};
+
+/// Index and macro series usable as cross-asset features.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MacroSeries {
+    Vix,
+    Vix3m,
+    Treasury3m,
+    Treasury2y,
+    Treasury10y,
+    /// Broad trade-weighted dollar index
+    DollarIndex,
+}
+
+/// Where a series is loaded from. Both are CSVs of `date,value` rows with a header line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MacroSource {
+    /// FRED series id, fetched without an API key from the graph CSV endpoint
+    Fred(String),
+    Csv(PathBuf),
+}
+
+impl MacroSeries {
+    pub const ALL: [MacroSeries; 6] = [
+        MacroSeries::Vix,
+        MacroSeries::Vix3m,
+        MacroSeries::Treasury3m,
+        MacroSeries::Treasury2y,
+        MacroSeries::Treasury10y,
+        MacroSeries::DollarIndex,
+    ];
+
+    /// Feature column and CSV file name
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Vix => "vix",
+            Self::Vix3m => "vix3m",
+            Self::Treasury3m => "ust_3m",
+            Self::Treasury2y => "ust_2y",
+            Self::Treasury10y => "ust_10y",
+            Self::DollarIndex => "dollar_index",
+        }
+    }
+
+    pub fn fred_id(&self) -> &'static str {
+        match self {
+            Self::Vix => "VIXCLS",
+            Self::Vix3m => "VXVCLS",
+            Self::Treasury3m => "DGS3MO",
+            Self::Treasury2y => "DGS2",
+            Self::Treasury10y => "DGS10",
+            Self::DollarIndex => "DTWEXBGS",
+        }
+    }
+
+    /// `{MACRO_CSV_DIR}/{name}.csv` if that file exists, FRED otherwise.
+    pub fn source(&self) -> MacroSource {
+        dotenv::dotenv().ok();
+
+        dotenv::var("MACRO_CSV_DIR")
+            .ok()
+            .map(|dir| PathBuf::from(dir).join(format!("{}.csv", self.name())))
+            .filter(|path| path.is_file())
+            .map_or_else(|| MacroSource::Fred(self.fred_id().to_string()), MacroSource::Csv)
+    }
+}
+
+/// Daily observations of `series` in `from..=to`, oldest first. Days the source has no value
+/// for, like FRED's holiday rows, are left out.
+pub async fn fetch_macro_series(series: MacroSeries, from: NaiveDate, to: NaiveDate) -> Result<Vec<(NaiveDate, f64)>, Box<dyn Error + Send + Sync>> {
+    fetch_macro_source(&series.source(), from, to).await
+}
+
+pub async fn fetch_macro_source(source: &MacroSource, from: NaiveDate, to: NaiveDate) -> Result<Vec<(NaiveDate, f64)>, Box<dyn Error + Send + Sync>> {
+    let text = match source {
+        MacroSource::Fred(id) => {
+            reqwest::Client::new()
+                .get("https://fred.stlouisfed.org/graph/fredgraph.csv")
+                .query(&[("id", id.clone()), ("cosd", from.to_string()), ("coed", to.to_string())])
+                .send()
+                .await?
+                .error_for_status()?
+                .text()
+                .await?
+        }
+        MacroSource::Csv(path) => tokio::fs::read_to_string(path).await?,
+    };
+
+    let mut observations = parse_csv(&text)?;
+    observations.retain(|(date, _)| *date >= from && *date <= to);
+    observations.sort_by_key(|(date, _)| *date);
+
+    Ok(observations)
+}
+
+/// Parses `date,value` rows. The header and rows without a number (FRED writes `.` or nothing)
+/// are skipped, a malformed date anywhere else is an error.
+fn parse_csv(text: &str) -> Result<Vec<(NaiveDate, f64)>, String> {
+    let mut observations = Vec::new();
+
+    for (idx, line) in text.lines().enumerate() {
+        let mut fields = line.split(',').map(str::trim);
+        let (Some(date), Some(value)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+
+        let date = match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+            Ok(date) => date,
+            Err(_) if idx == 0 => continue,
+            Err(e) => return Err(format!("line {}: {date}: {e}", idx + 1)),
+        };
+
+        if let Ok(value) = value.parse::<f64>() {
+            observations.push((date, value));
+        }
+    }
+
+    Ok(observations)
+}
+
+/// Places daily values, sorted by date, on the bars of `series`. A day's value is taken as of
+/// its regular session close, so it first applies to the bar that ends at or after the close, or
+/// spans it like a daily bar. Earlier bars of the day still see the previous day's value, as does
+/// a day whose bars stop before the close. NaN before the first known value.
+pub fn align_daily(series: SeriesView, values: &[(NaiveDate, f64)]) -> Vec<f64> {
+    let mut gaps = series.t.windows(2).map(|w| w[1] - w[0]).collect::<Vec<_>>();
+    gaps.sort_unstable();
+    let spacing = gaps.get(gaps.len() / 2).copied().unwrap_or(0);
+
+    let mut next = 0;
+    let mut current = f64::NAN;
+    let mut close: Option<(NaiveDate, Option<i64>)> = None;
+
+    (0..series.len())
+        .map(|i| {
+            let day = vwap_session_day(series.t[i]);
+            let day_close = match close {
+                Some((close_day, at)) if close_day == day => at,
+                _ => {
+                    let at = calendar::regular_session(day).map(|(_, close)| close.timestamp());
+                    close = Some((day, at));
+                    at
+                }
+            };
+            let past_close = series.session[i] == MarketSession::FullDay || day_close.is_some_and(|at| series.t[i] + spacing >= at);
+
+            while next < values.len() && (values[next].0 < day || (past_close && values[next].0 == day)) {
+                current = values[next].1;
+                next += 1;
+            }
+
+            current
+        })
+        .collect()
+}
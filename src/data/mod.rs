@@ -1,4 +1,8 @@
 pub mod collection;
 pub mod types;
 pub mod realtime_data;
-pub mod db_service;
\ No newline at end of file
+pub mod db_service;
+pub mod cache;
+pub mod series;
+pub mod macro_series;
+pub mod calendar;
@@ -0,0 +1,175 @@
+use std::ops::{Bound, RangeBounds};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::types::{MarketSession, TickerDataframe};
+
+/// Struct-of-arrays price history. Every column has one entry per bar, so a series of
+/// a few million intraday bars stays compact and each column can be handed to vectorized
+/// code as a plain slice.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PriceSeries {
+    /// Bar open time as unix seconds (UTC)
+    pub t: Vec<i64>,
+    pub open: Vec<f64>,
+    pub high: Vec<f64>,
+    pub low: Vec<f64>,
+    pub close: Vec<f64>,
+    pub vol: Vec<i64>,
+    pub vol_weighted: Vec<f64>,
+    pub session: Vec<MarketSession>,
+}
+
+/// Borrowed window over a [`PriceSeries`]. Cheap to copy and re-slice.
+#[derive(Debug, Clone, Copy)]
+pub struct SeriesView<'a> {
+    pub t: &'a [i64],
+    pub open: &'a [f64],
+    pub high: &'a [f64],
+    pub low: &'a [f64],
+    pub close: &'a [f64],
+    pub vol: &'a [i64],
+    pub vol_weighted: &'a [f64],
+    pub session: &'a [MarketSession],
+}
+
+impl PriceSeries {
+    pub fn with_capacity(n: usize) -> Self {
+        Self {
+            t: Vec::with_capacity(n),
+            open: Vec::with_capacity(n),
+            high: Vec::with_capacity(n),
+            low: Vec::with_capacity(n),
+            close: Vec::with_capacity(n),
+            vol: Vec::with_capacity(n),
+            vol_weighted: Vec::with_capacity(n),
+            session: Vec::with_capacity(n),
+        }
+    }
+
+    pub fn push(&mut self, frame: &TickerDataframe) {
+        self.t.push(frame.t.timestamp());
+        self.open.push(frame.open);
+        self.high.push(frame.high);
+        self.low.push(frame.low);
+        self.close.push(frame.close);
+        self.vol.push(frame.vol);
+        self.vol_weighted.push(frame.vol_weighted);
+        self.session.push(frame.session);
+    }
+
+    pub fn len(&self) -> usize {
+        self.close.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.close.is_empty()
+    }
+
+    pub fn view(&self) -> SeriesView<'_> {
+        SeriesView {
+            t: &self.t,
+            open: &self.open,
+            high: &self.high,
+            low: &self.low,
+            close: &self.close,
+            vol: &self.vol,
+            vol_weighted: &self.vol_weighted,
+            session: &self.session,
+        }
+    }
+
+    pub fn to_frames(&self) -> Vec<TickerDataframe> {
+        self.view().to_frames()
+    }
+
+    pub fn frame(&self, idx: usize) -> TickerDataframe {
+        self.view().frame(idx)
+    }
+
+    pub fn datetime(&self, idx: usize) -> DateTime<Utc> {
+        self.view().datetime(idx)
+    }
+}
+
+impl From<&[TickerDataframe]> for PriceSeries {
+    fn from(frames: &[TickerDataframe]) -> Self {
+        let mut series = Self::with_capacity(frames.len());
+        for frame in frames {
+            series.push(frame);
+        }
+
+        series
+    }
+}
+
+impl<'a> SeriesView<'a> {
+    pub fn len(&self) -> usize {
+        self.close.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.close.is_empty()
+    }
+
+    /// Owned copy of the bars in the view.
+    pub fn to_series(self) -> PriceSeries {
+        PriceSeries {
+            t: self.t.to_vec(),
+            open: self.open.to_vec(),
+            high: self.high.to_vec(),
+            low: self.low.to_vec(),
+            close: self.close.to_vec(),
+            vol: self.vol.to_vec(),
+            vol_weighted: self.vol_weighted.to_vec(),
+            session: self.session.to_vec(),
+        }
+    }
+
+    pub fn to_frames(self) -> Vec<TickerDataframe> {
+        (0..self.len()).map(|i| self.frame(i)).collect()
+    }
+
+    pub fn frame(&self, idx: usize) -> TickerDataframe {
+        TickerDataframe {
+            t: self.datetime(idx),
+            open: self.open[idx],
+            high: self.high[idx],
+            close: self.close[idx],
+            low: self.low[idx],
+            vol: self.vol[idx],
+            vol_weighted: self.vol_weighted[idx],
+            session: self.session[idx],
+        }
+    }
+
+    pub fn datetime(&self, idx: usize) -> DateTime<Utc> {
+        DateTime::from_timestamp(self.t[idx], 0).unwrap_or_default()
+    }
+
+    /// Narrows the view to `range` without copying any bars.
+    pub fn slice(&self, range: impl RangeBounds<usize>) -> SeriesView<'a> {
+        let start = match range.start_bound() {
+            Bound::Included(&s) => s,
+            Bound::Excluded(&s) => s + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&e) => e + 1,
+            Bound::Excluded(&e) => e,
+            Bound::Unbounded => self.len(),
+        };
+
+        SeriesView {
+            t: &self.t[start..end],
+            open: &self.open[start..end],
+            high: &self.high[start..end],
+            low: &self.low[start..end],
+            close: &self.close[start..end],
+            vol: &self.vol[start..end],
+            vol_weighted: &self.vol_weighted[start..end],
+            session: &self.session[start..end],
+        }
+    }
+}
@@ -1,10 +1,13 @@
-use chrono::NaiveDate;
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, Utc, Weekday};
+use chrono_tz::{America::New_York, Tz};
 use serde::{Deserialize, Serialize};
 use strum::AsRefStr;
 use surrealdb::RecordId;
 use std::str::FromStr;
 
-#[derive(Serialize, Deserialize, Debug)]
+use super::series::{PriceSeries, SeriesView};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct EtfHolding {
     #[serde(rename = "in")]
     pub table_in: RecordId,
@@ -74,70 +77,198 @@ impl ToString for PointTimeDelta {
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct TickerData {
     pub symbol: String,
-    pub price_data: Vec<TickerDataframe>,
+    pub price_data: PriceSeries,
     pub technicals: Vec<Technicals>,
 }
 
+impl TickerData {
+    /// Borrowed view of `price_data` for the analysis functions.
+    pub fn series(&self) -> SeriesView<'_> {
+        self.price_data.view()
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(try_from = "StoredDataframe")]
 pub struct TickerDataframe {
-    pub t: String,
-    pub open: f32,
-    pub high: f32,
-    pub close: f32,
-    pub low: f32,
+    #[serde(serialize_with = "serialize_timestamp")]
+    pub t: DateTime<Utc>,
+    pub open: f64,
+    pub high: f64,
+    pub close: f64,
+    pub low: f64,
     pub vol: i64,
-    pub vol_weighted: f32,
+    pub vol_weighted: f64,
+    pub session: MarketSession,
+}
+
+/// [`TickerDataframe`] as read back, with its timestamp still unparsed. Rows stored before
+/// sessions were recorded have none.
+#[derive(Deserialize)]
+struct StoredDataframe {
+    t: String,
+    open: f64,
+    high: f64,
+    close: f64,
+    low: f64,
+    vol: i64,
+    vol_weighted: f64,
+    session: Option<MarketSession>,
+}
+
+impl TryFrom<StoredDataframe> for TickerDataframe {
+    type Error = String;
+
+    fn try_from(stored: StoredDataframe) -> Result<Self, Self::Error> {
+        let t = parse_timestamp(&stored.t)?;
+        // Only daily data is stored as a bare date
+        let daily = NaiveDate::parse_from_str(&stored.t, "%Y-%m-%d").is_ok();
+
+        Ok(Self {
+            t,
+            open: stored.open,
+            high: stored.high,
+            close: stored.close,
+            low: stored.low,
+            vol: stored.vol,
+            vol_weighted: stored.vol_weighted,
+            session: stored.session.unwrap_or(if daily { MarketSession::FullDay } else { MarketSession::of(t) }),
+        })
+    }
+}
+
+impl TickerDataframe {
+    /// Bar time on the exchange clock (America/New_York).
+    pub fn local_time(&self) -> DateTime<Tz> {
+        self.t.with_timezone(&New_York)
+    }
+}
+
+/// US equity trading session a bar was printed in.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum MarketSession {
+    /// 04:00 - 09:30 ET
+    PreMarket,
+    /// 09:30 - 16:00 ET
+    #[default]
+    Regular,
+    /// 16:00 - 20:00 ET
+    AfterHours,
+    /// Outside of all sessions, including weekends
+    Overnight,
+    /// Bar spans a whole day or more (daily, weekly and monthly bars)
+    FullDay,
+}
+
+impl MarketSession {
+    pub fn of(t: DateTime<Utc>) -> Self {
+        let local = t.with_timezone(&New_York);
+        if matches!(local.weekday(), Weekday::Sat | Weekday::Sun) {
+            return Self::Overnight;
+        }
+
+        let time = local.time();
+        let at = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+
+        if time < at(4, 0) || time >= at(20, 0) {
+            Self::Overnight
+        } else if time < at(9, 30) {
+            Self::PreMarket
+        } else if time < at(16, 0) {
+            Self::Regular
+        } else {
+            Self::AfterHours
+        }
+    }
+
+    /// Session of a bar starting at `t`. Bars of a day or longer cover every session.
+    pub fn for_bar(t: DateTime<Utc>, timeframe: &PointTimeDelta) -> Self {
+        match timeframe {
+            PointTimeDelta::Day | PointTimeDelta::Week | PointTimeDelta::Month(_) => Self::FullDay,
+            _ => Self::of(t),
+        }
+    }
+
+    pub fn is_extended_hours(&self) -> bool {
+        matches!(self, Self::PreMarket | Self::AfterHours | Self::Overnight)
+    }
+}
+
+/// Parses the timestamps we get from Alpaca (RFC3339) and from stored daily data (`YYYY-MM-DD`).
+/// A bare date is taken as midnight in New York, so the bar falls on that exchange day.
+pub fn parse_timestamp(t: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(t) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+
+    let date = NaiveDate::parse_from_str(t, "%Y-%m-%d").map_err(|e| format!("invalid bar timestamp {t:?}: {e}"))?;
+    date.and_time(NaiveTime::MIN)
+        .and_local_timezone(New_York)
+        .single()
+        .map(|t| t.to_utc())
+        .ok_or_else(|| format!("invalid bar timestamp {t:?}: no New York midnight"))
+}
+
+/// Bar timestamps are written as RFC3339.
+fn serialize_timestamp<S: serde::Serializer>(t: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&t.to_rfc3339())
 }
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Technicals {
-    pub volatility_at_t: f32,
-    pub sma: f32,
-    pub rsi: f32,
-    pub analyst_target: f32,
+    pub volatility_at_t: f64,
+    pub sma: f64,
+    pub rsi: f64,
+    pub analyst_target: f64,
 }
-#[derive(Default, Debug, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct OptionChain {
     pub data: Vec<StockOption>
 }
 
-#[derive(Default, Debug, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct StockOption {
     #[serde(rename = "contractID")]
     pub contract_id: String,
+    /// Underlying symbol
+    #[serde(default)]
+    pub symbol: String,
+    /// Day the quote was taken
+    #[serde(default)]
+    pub date: Option<NaiveDate>,
     #[serde(rename = "expiration")]
     pub expiry_date: NaiveDate,
     #[serde(rename = "strike")]
     #[serde(deserialize_with = "serde_aux::field_attributes::deserialize_number_from_string")]
-    pub strike_price: f32,
+    pub strike_price: f64,
     #[serde(rename = "type")]
     pub option_type: OptionType,
     #[serde(deserialize_with = "serde_aux::field_attributes::deserialize_number_from_string")]
-    pub bid: f32,
+    pub bid: f64,
     #[serde(deserialize_with = "serde_aux::field_attributes::deserialize_number_from_string")]
-    pub bid_size: f32,
+    pub bid_size: f64,
     #[serde(deserialize_with = "serde_aux::field_attributes::deserialize_number_from_string")]
-    pub ask: f32,
+    pub ask: f64,
     #[serde(deserialize_with = "serde_aux::field_attributes::deserialize_number_from_string")]
-    pub ask_size: f32,
+    pub ask_size: f64,
     #[serde(deserialize_with = "serde_aux::field_attributes::deserialize_number_from_string")]
-    pub open_interest: f32,
+    pub open_interest: f64,
     #[serde(deserialize_with = "serde_aux::field_attributes::deserialize_number_from_string")]
-    pub volume: f32,
+    pub volume: f64,
     #[serde(deserialize_with = "serde_aux::field_attributes::deserialize_number_from_string")]
-    pub implied_volatility: f32,
+    pub implied_volatility: f64,
     #[serde(deserialize_with = "serde_aux::field_attributes::deserialize_number_from_string")]
-    pub delta: f32,
+    pub delta: f64,
     #[serde(deserialize_with = "serde_aux::field_attributes::deserialize_number_from_string")]
-    pub gamma: f32,
+    pub gamma: f64,
     #[serde(deserialize_with = "serde_aux::field_attributes::deserialize_number_from_string")]
-    pub theta: f32,
+    pub theta: f64,
     #[serde(deserialize_with = "serde_aux::field_attributes::deserialize_number_from_string")]
-    pub vega: f32,
+    pub vega: f64,
     #[serde(deserialize_with = "serde_aux::field_attributes::deserialize_number_from_string")]
-    pub rho: f32,
+    pub rho: f64,
 }
 
-#[derive(Default, Debug, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum OptionType {
     #[default]
     #[serde(rename = "call")]
@@ -149,7 +280,7 @@ pub enum OptionType {
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct EtfTicker {
     pub components: Vec<RecordId>,
-    pub current_market_price: f32,
+    pub current_market_price: f64,
     pub historical_price_data: RecordId,
     pub symbol: String,
     pub technicals: RecordId,
@@ -160,5 +291,29 @@ pub struct StockTicker {
     pub historical_price_data: RecordId,
     pub symbol: String,
     pub technicals: RecordId,
-    pub market_price: f32,
-}
\ No newline at end of file
+    pub market_price: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Trade {
+    pub asset_type: TradeAssetType,
+    pub market_value: f64,
+    pub num_shares: f64,
+    pub symbol: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum TradeAssetType {
+    #[serde(rename = "option")]
+    Option,
+    #[serde(rename = "common_stock")]
+    CommonStock,
+}
+
+/// What happened to a record watched by a live query.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LiveAction {
+    Create,
+    Update,
+    Delete,
+}
@@ -17,7 +17,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
     eframe::run_native(
         "Arbitrium",
         options,
-        Box::new(|_cc| Ok(Box::new(ui::renderer::App::default()))),
+        Box::new(|cc| Ok(Box::new(ui::renderer::App::new(cc)))),
     ).unwrap();
 
     Ok(())
@@ -40,37 +40,24 @@ mod tests {
 
         data::db_service::init_database().await.unwrap();
 
-        let train_data = db_service::get_etf(Etf::SPY).await.unwrap().price_data.iter().take(10000).cloned().collect::<Vec<TickerDataframe>>();
+        let spy = db_service::get_etf(Etf::SPY).await.unwrap();
+        let train_data = spy.series().slice(..spy.price_data.len().min(10000)).to_series();
         let test_data = collection::get_ticker_data(
             "SPY",
             TickerDatatype::HistOHCL("2025-01-02".to_string(), "2025-05-30".to_string()),
             PointTimeDelta::Day
         ).await.unwrap();
 
-        let train_features = analysis::features::featureset::calculate_featureset(&TickerData { symbol: "SPY".to_string(), price_data: train_data, technicals: Vec::new() });
-        let test_features = analysis::features::featureset::calculate_featureset(&test_data);
+        let train_data = TickerData { symbol: "SPY".to_string(), price_data: train_data, technicals: Vec::new() };
+        let test_features = featureset::calculate_featureset_cached(&test_data);
 
-        let model = analysis::strategies::gradient_trees::train(&train_features).unwrap();
+        let model = analysis::strategies::gradient_trees::train_on(&train_data).unwrap();
 
-        let (test_feats, test_targs) = analysis::strategies::gradient_trees::prepare_dataset(&test_features);
+        let (test_feats, test_targs) = model.prepare(&test_features).unwrap();
 
-        let test_dataset = linfa::Dataset::new(test_feats, test_targs).with_feature_names(vec![
-            "close/high ratio",
-            "close/low ratio",
-            "daily return",
-            "volatility 5d",
-            "volatility 20d",
-            "volume ratio 5d",
-            "volume ratio 20d",
-            "rsi 14",
-            "sma 5",
-            "sma 20",
-            "macd line",
-            "macd signal",
-            "macd histogram",
-        ]);
+        let test_dataset = linfa::Dataset::new(test_feats, test_targs).with_feature_names(test_features.features.names.clone());
 
-        let output = model.predict(&test_dataset);
+        let output = model.model.predict(&test_dataset);
 
         let con_matrix = output.confusion_matrix(&test_dataset.targets).unwrap();
         let accuracy = con_matrix.accuracy();
@@ -82,85 +69,48 @@ mod tests {
         println!("{:#?}", con_matrix);
 
         println!("------------------------------------");
-        let price_changes: Vec<f32> = test_data.price_data
-            .iter()
-            .map(|el| ((el.open - el.close) / el.close) * 100.0)
-            .collect();
 
+        // Each row is the bar it was computed at, its forward return is what the label was
+        // thresholded from and the prediction is for that same row
         let predictions = output.into_raw_vec();
+        assert_eq!(predictions.len(), test_features.features.bars.len());
+        let directions = [PriceDirection::Down, PriceDirection::None, PriceDirection::Up];
+        let name = |class: usize| directions.iter().find(|direction| direction.to_usize() == class).map_or(String::from("?"), |direction| direction.to_string());
 
-        let price_strs: Vec<String> = price_changes.iter().map(|v| format!("{:.2}", v)).collect();
-        let pred_strs: Vec<String> = predictions.iter().map(|v| format!("{}", v)).collect();
+        let return_strs: Vec<String> = test_features.forward_return.iter().map(|v| format!("{:.2}", v * 100.0)).collect();
+        let pred_strs: Vec<String> = predictions.iter().map(|&class| name(class)).collect();
+        let date_strs: Vec<String> = test_features.features.bars.iter().map(|&bar| test_data.price_data.datetime(bar).format("%m-%d").to_string()).collect();
 
-        let max_width = price_strs.iter()
+        let max_width = return_strs.iter()
             .chain(pred_strs.iter())
+            .chain(date_strs.iter())
             .map(|s| s.len())
             .max()
             .unwrap_or(0);
 
-        let mut idx = 0;
-        let total = price_strs.len();
-        while idx < total {
-            let end = usize::min(idx + 10, total);
+        for start in (0..predictions.len()).step_by(10) {
+            let rows = start..usize::min(start + 10, predictions.len());
 
-            // Print price changes centered
-            for s in &price_strs[idx..end] {
-                print!("{:^width$} ", s, width = max_width);
+            for i in rows.clone() {
+                print!("{:^width$} ", date_strs[i], width = max_width);
             }
             println!();
-
-            // Print predictions centered - fix: don't subtract 1
-            // print!("{:^width$} ", "", width = max_width);
-            for i in idx..end {
-                if i == 0 {
-                    if idx == 0 {
-                        print!("{:^width$} ", "", width = max_width);
-                    } // Empty space for first position
-                    continue;
-                }
-                let pred_str = &pred_strs[i-1];
-                print!("{:^width$} ", pred_str, width = max_width);
+            for i in rows.clone() {
+                print!("{:^width$} ", return_strs[i], width = max_width);
             }
             println!();
-
-            // Print validation marks
-            // print!("{:^width$} ", "", width = max_width);
-            for i in idx..end {
-                let price_change = price_changes[i];
-                if i == 0 {
-                    if idx == 0 {
-                        print!("{:^width$} ", "", width = max_width);
-                    }
-                     // Empty space for first position
-                    continue;
-                }
-                let el = &pred_strs[i-1];
-                let matches = if price_change > 0.5 && el == "Up" {
-                    "✓"
-                } else if price_change < -0.5 && el == "Down" {
-                    "✓"
-                } else if price_change < 0.5 && price_change > -0.5 && el == "None" {
-                    "✓"
-                } else {
-                    "✗"
-                };
+            for i in rows.clone() {
+                print!("{:^width$} ", pred_strs[i], width = max_width);
+            }
+            println!();
+            for i in rows {
+                let matches = if predictions[i] == test_features.direction[i].to_usize() { "✓" } else { "✗" };
                 print!("{:^width$} ", matches, width = max_width);
             }
             println!();
             println!();
-
-            idx += 10;
         }
-        println!();
         println!("------------------------------------");
-        println!();
-
-        // for i in 0..output.into_raw_vec().len() {
-        //     let price_el = test_data.price_data.get(i).unwrap();
-
-        //     print!("  {}  \n  {}", ((price_el.open - price_el.close) / price_el.close) * 100.0);
-        // }          
-
     }
 
     #[tokio::test]
@@ -169,37 +119,1622 @@ mod tests {
 
         data::db_service::init_database().await.unwrap();
 
-        let train_data = db_service::get_etf(Etf::SPY).await.unwrap().price_data.iter().take(10000).cloned().collect::<Vec<TickerDataframe>>();
+        let spy = db_service::get_etf(Etf::SPY).await.unwrap();
+        let train_data = spy.series().slice(..spy.price_data.len().min(10000)).to_series();
         let test_data = collection::get_ticker_data(
             "SPY",
             TickerDatatype::HistOHCL("2025-01-02".to_string(), "2025-05-30".to_string()),
             PointTimeDelta::Day
         ).await.unwrap();
 
-        let train_features = analysis::features::featureset::calculate_featureset(&TickerData { symbol: "SPY".to_string(), price_data: train_data, technicals: Vec::new() });
-        let test_features = analysis::features::featureset::calculate_featureset(&test_data);
-        let model = analysis::strategies::gradient_trees::train_ensemble(&train_features).unwrap();
-        let (test_feats, test_targs) = analysis::strategies::gradient_trees::prepare_dataset(&test_features);
-
-        let test_dataset = linfa::Dataset::new(test_feats, test_targs).with_feature_names(vec![
-            "close/high ratio",
-            "close/low ratio",
-            "daily return",
-            "volatility 5d",
-            "volatility 20d",
-            "volume ratio 5d",
-            "volume ratio 20d",
-            "rsi 14",
-            "sma 5",
-            "sma 20",
-            "macd line",
-            "macd signal",
-            "macd histogram",
-        ]);
+        let train_data = TickerData { symbol: "SPY".to_string(), price_data: train_data, technicals: Vec::new() };
+        let test_features = featureset::calculate_featureset_cached(&test_data);
+        let model = analysis::strategies::gradient_trees::train_ensemble_on(&train_data).unwrap();
+        let (test_feats, test_targs) = model.prepare(&test_features).unwrap();
+
+        let test_dataset = linfa::Dataset::new(test_feats, test_targs).with_feature_names(test_features.features.names.clone());
         
-        let output = model.predict(&test_dataset);
+        let output = model.model.predict(&test_dataset);
 
         println!("Acuracy: {}", output.confusion_matrix(&test_dataset.targets).unwrap().accuracy())
     }
-}
 
+    #[tokio::test]
+    async fn test_price_history_round_trips_through_db() {
+        use crate::data::{series::PriceSeries, types::*};
+
+        data::db_service::init_database().await.unwrap();
+
+        // Pre-market through after-hours across the November DST change
+        let start = chrono::DateTime::parse_from_rfc3339("2024-11-01T08:00:00Z").unwrap().to_utc();
+        let frames = (0..2000)
+            .map(|i| {
+                let t = start + chrono::Duration::minutes(10 * i);
+                TickerDataframe { t, close: 100.0 + i as f64, vol: i, session: MarketSession::of(t), ..Default::default() }
+            })
+            .collect::<Vec<_>>();
+        let stored = TickerData { symbol: "ROUNDTRIP_TEST".to_string(), price_data: PriceSeries::from(frames.as_slice()), technicals: Vec::new() };
+
+        db_service::upsert_price_history(&stored).await.unwrap();
+        let loaded = db_service::get_price_history(&stored.symbol).await;
+        db_service::delete_price_history(&stored.symbol).await.unwrap();
+        assert_eq!(loaded.unwrap().price_data, stored.price_data);
+
+        // A bare date is the New York trading day, not midnight UTC
+        let day = parse_timestamp("2024-11-04").unwrap();
+        assert_eq!(analysis::streaming::vwap_session_day(day.timestamp()), chrono::NaiveDate::from_ymd_opt(2024, 11, 4).unwrap());
+        assert!(parse_timestamp("2024-13-04").is_err());
+
+        // Rows stored without a session take it from their timestamp
+        let legacy = |t: &str| serde_json::from_value::<TickerDataframe>(serde_json::json!({
+            "t": t, "open": 1.0, "high": 1.0, "close": 1.0, "low": 1.0, "vol": 1, "vol_weighted": 1.0
+        })).unwrap();
+        assert_eq!(legacy("2024-11-04T13:00:00Z").session, MarketSession::PreMarket);
+        assert_eq!(legacy("2024-11-04T15:00:00Z").session, MarketSession::Regular);
+        assert_eq!(legacy("2024-11-04").session, MarketSession::FullDay);
+    }
+
+    #[test]
+    fn test_gap_features_match_synthetic_sessions() {
+        use crate::analysis::{features::sessions, strategies::gradient_trees};
+        use crate::data::{calendar, series::PriceSeries, types::*};
+        use chrono_tz::America::New_York;
+
+        // 10-minute bars from 08:00 to 18:00 New York over six weeks, Thanksgiving and its early
+        // close included. Each session opens at a known gap from the prior regular close while
+        // the extended-hours bars drift away from it.
+        let gaps = [0.005, -0.004, 0.001, 0.003, -0.0015, -0.006, 0.0025];
+        let date = |m, d| chrono::NaiveDate::from_ymd_opt(2024, m, d).unwrap();
+
+        let mut frames = Vec::new();
+        let mut expected = Vec::new();
+        let (mut price, mut regular_close) = (100.0, None::<(chrono::NaiveDate, f64)>);
+        let mut day = date(11, 1);
+        while day <= date(12, 13) {
+            let (open_t, close_t) = calendar::regular_session(day).unwrap();
+            let mut first = true;
+            for step in 0..60 {
+                let t = day.and_hms_opt(8, 0, 0).unwrap().and_local_timezone(New_York).unwrap().to_utc()
+                    + chrono::Duration::minutes(10 * step);
+                let regular = t >= open_t && t < close_t;
+
+                let open = match regular_close {
+                    Some((from, prior)) if regular && first => {
+                        let gap = gaps[expected.len() % gaps.len()];
+                        expected.push((from, day, gap));
+                        prior * (1.0 + gap)
+                    }
+                    _ => price,
+                };
+                first &= !regular;
+                price = open * (1.0 + 0.0004 * ((step % 3) as f64 - 1.0));
+
+                frames.push(TickerDataframe {
+                    t,
+                    open,
+                    high: open.max(price) * 1.001,
+                    close: price,
+                    low: open.min(price) * 0.999,
+                    vol: 1_000 + step,
+                    vol_weighted: (open + price) / 2.0,
+                    session: MarketSession::of(t),
+                });
+                if regular {
+                    regular_close = Some((day, price));
+                }
+            }
+            day = calendar::next_trading_day(day);
+        }
+        let data = TickerData { symbol: "GAPS".to_string(), price_data: PriceSeries::from(frames.as_slice()), technicals: Vec::new() };
+        let series = data.series();
+
+        let summaries = sessions::regular_sessions(series);
+        assert!(summaries.iter().all(|session| session.date != date(11, 28)));
+        let early = summaries.iter().find(|session| session.date == date(11, 29)).expect("early close is a complete session");
+        assert_eq!(series.t[early.last_bar] + 600, calendar::regular_session(date(11, 29)).unwrap().1.timestamp());
+
+        let threshold = 0.002;
+        let found = sessions::overnight_gaps(series, &summaries, threshold);
+        assert_eq!(found.len(), expected.len());
+        for (gap, &(from, to, size)) in found.iter().zip(&expected) {
+            assert_eq!((gap.from, gap.to), (from, to));
+            assert!((gap.gap - size).abs() < 1e-12, "{} gap {} != {size}", gap.to, gap.gap);
+            let direction = if size > threshold {
+                PriceDirection::Up
+            } else if size < -threshold {
+                PriceDirection::Down
+            } else {
+                PriceDirection::None
+            };
+            assert_eq!(gap.direction, direction);
+        }
+        assert!(found.iter().any(|gap| gap.from == date(11, 27) && gap.to == date(11, 29)));
+
+        // Rows are the gaps left once the features warm up, taken at each session's last regular bar
+        let dataset = featureset::calculate_gap_featureset(&data, &featureset::gap_pipeline(), threshold).unwrap();
+        let skipped = found.len() - dataset.features.bars.len();
+        assert!(skipped <= 2, "{skipped} sessions lost to warm-up");
+        for (row, gap) in found[skipped..].iter().enumerate() {
+            assert_eq!(dataset.features.bars[row], gap.from_bar);
+            assert_eq!(dataset.label_end[row], gap.to_bar);
+            assert_eq!(dataset.direction[row], gap.direction);
+            assert_eq!(dataset.forward_return[row], gap.gap);
+        }
+
+        let evaluation = gradient_trees::evaluate_gaps(&data, threshold, 0.25).unwrap();
+        assert!(evaluation.train_rows > 0 && evaluation.test_rows > 0);
+    }
+
+    #[test]
+    fn test_streaming_indicators_match_batch() {
+        use crate::analysis::{moving_average, streaming::*, volatility};
+        use crate::data::series::PriceSeries;
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(69);
+        let start = chrono::DateTime::parse_from_rfc3339("2024-01-02T14:30:00Z").unwrap().to_utc();
+
+        let mut close = 100.0;
+        let frames = (0..200_000)
+            .map(|i| {
+                let open = close;
+                close = f64::max(1.0, close * (1.0 + rng.gen_range(-0.01..0.01)));
+                TickerDataframe {
+                    t: start + chrono::Duration::minutes(10 * i),
+                    open,
+                    high: open.max(close) * 1.001,
+                    close,
+                    low: open.min(close) * 0.999,
+                    vol: rng.gen_range(0..50_000),
+                    vol_weighted: (open + close) / 2.0,
+                    session: Default::default(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let series = PriceSeries::from(frames.as_slice());
+        let view = series.view();
+
+        let batch_ema = moving_average::ema_on_values(view.close, 12);
+        let batch_macd = moving_average::macd_on_series(view, 12, 26, 9);
+        let batch_rsi = moving_average::rsi_on_series(view, 14);
+
+        let mut sma = SmaStream::new(20);
+        let mut ema = EmaStream::new(12);
+        let mut macd = MacdStream::new(12, 26, 9);
+        let mut rsi = RsiStream::new(14);
+        let mut std_dev = StdDevStream::new(20);
+        let mut vwap = VwapStream::new(VwapAnchor::Session);
+
+        let same = |a: f64, b: f64| a.to_bits() == b.to_bits() || (a.is_nan() && b.is_nan());
+
+        // Rolling values are checked against a full recomputation of each window, to within
+        // 1e-9 of the price level
+        let tolerance = |price: f64| 1e-9 * price;
+        let mut session_start = 0;
+
+        for (i, bar) in frames.iter().enumerate() {
+            assert!(same(ema.update(bar), batch_ema[i]), "ema differs at bar {i}");
+            let point = macd.update(bar);
+            assert!(same(point.macd, batch_macd[i].macd), "macd differs at bar {i}");
+            assert!(same(point.signal, batch_macd[i].signal), "macd signal differs at bar {i}");
+            assert!(same(rsi.update(bar), batch_rsi[i]), "rsi differs at bar {i}");
+
+            let (sma, std_dev) = (sma.update(bar), std_dev.update(bar));
+            if i < 19 {
+                assert!(sma.is_nan() && std_dev.is_nan(), "rolling values before the window is full at bar {i}");
+            } else {
+                let window = view.slice(i - 19..=i);
+                let naive_sma = window.close.iter().sum::<f64>() / 20.0;
+                let naive_std = volatility::ticker_volatility_n_series(window);
+
+                assert!((sma - naive_sma).abs() <= tolerance(naive_sma), "sma drifted at bar {i}");
+                assert!((std_dev - naive_std).abs() <= tolerance(naive_sma), "std dev drifted at bar {i}");
+            }
+
+            if i > 0 && vwap_session_day(view.t[i]) != vwap_session_day(view.t[i - 1]) {
+                session_start = i;
+            }
+            let session = session_start..=i;
+            let typical = |j: usize| (view.high[j] + view.low[j] + view.close[j]) / 3.0;
+            let volume = session.clone().map(|j| view.vol[j] as f64).sum::<f64>();
+            let naive_vwap = session.map(|j| typical(j) * view.vol[j] as f64).sum::<f64>() / volume;
+
+            let vwap = vwap.update(bar);
+            assert!(same(vwap, naive_vwap) || (vwap - naive_vwap).abs() <= tolerance(naive_vwap), "vwap differs at bar {i}");
+        }
+
+        // Small moves at a high price level, where E[x^2] - mean^2 loses every significant digit
+        let mut std_dev = StdDevStream::new(20);
+        let level = (0..10_000).map(|i| 1e8 + (i % 7) as f64 * 0.01).collect::<Vec<_>>();
+        for i in 0..level.len() {
+            let value = std_dev.push(level[i]);
+            if i >= 19 {
+                let window = &level[i - 19..=i];
+                let mean = window.iter().sum::<f64>() / 20.0;
+                let naive = (window.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / 20.0).sqrt();
+                assert!((value - naive).abs() <= 1e-6, "std dev lost precision at bar {i}: {value} vs {naive}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_window_indicators_match_reference() {
+        use crate::analysis::{momentum, streaming::VwapAnchor, trend, volatility, volume};
+        use crate::data::series::PriceSeries;
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        let bars = |highs: &[f64], lows: &[f64], closes: &[f64], vols: &[i64]| {
+            let start = chrono::DateTime::parse_from_rfc3339("2024-01-02T14:30:00Z").unwrap().to_utc();
+            let frames = (0..closes.len())
+                .map(|i| TickerDataframe {
+                    t: start + chrono::Duration::days(i as i64),
+                    high: highs[i],
+                    low: lows[i],
+                    close: closes[i],
+                    vol: vols[i],
+                    ..Default::default()
+                })
+                .collect::<Vec<_>>();
+            PriceSeries::from(frames.as_slice())
+        };
+        let close_to = |actual: &[f64], expected: &[f64], name: &str| {
+            for (i, (a, e)) in actual.iter().zip(expected).enumerate() {
+                assert!((a.is_nan() && e.is_nan()) || (a - e).abs() <= 1e-9, "{name} at bar {i}: {a} vs {e}");
+            }
+        };
+        let nan = f64::NAN;
+
+        // Reference values worked out by hand from the textbook definitions, over 3 bars
+        let series = bars(
+            &[10.0, 11.0, 12.0, 11.0, 13.0, 12.0, 12.0],
+            &[8.0, 9.0, 10.0, 9.0, 10.0, 10.0, 11.0],
+            &[9.5, 10.0, 11.5, 9.5, 12.0, 10.5, 11.5],
+            &[100, 200, 100, 300, 200, 100, 0],
+        );
+        let view = series.view();
+
+        let donchian = volatility::donchian_on_series(view, 3);
+        close_to(&donchian.iter().map(|p| p.upper).collect::<Vec<_>>(), &[nan, nan, 12.0, 12.0, 13.0, 13.0, 13.0], "donchian upper");
+        close_to(&donchian.iter().map(|p| p.lower).collect::<Vec<_>>(), &[nan, nan, 8.0, 9.0, 9.0, 9.0, 10.0], "donchian lower");
+
+        let stochastic = momentum::stochastic_on_series(view, 3, 3);
+        close_to(&stochastic.iter().map(|p| p.k).collect::<Vec<_>>(), &[nan, nan, 87.5, 50.0 / 3.0, 75.0, 37.5, 50.0], "%K");
+        close_to(&stochastic.iter().map(|p| p.d).collect::<Vec<_>>(), &[nan, nan, nan, nan, 2150.0 / 36.0, 1550.0 / 36.0, 1950.0 / 36.0], "%D");
+
+        close_to(&momentum::williams_r_on_series(view, 3), &[nan, nan, -12.5, -250.0 / 3.0, -25.0, -62.5, -50.0], "williams %r");
+        close_to(&momentum::cci_on_series(view, 3), &[nan, nan, 100.0, -60.0, 1400.0 / 19.0, 100.0 / 17.0, 100.0 / 3.0], "cci");
+        close_to(&volume::cmf_on_series(view, 3), &[nan, nan, 0.25, -1.0 / 6.0, -1.0 / 18.0, -2.0 / 9.0, 1.0 / 18.0], "cmf");
+        close_to(
+            &volume::mfi_on_series(view, 3),
+            &[nan, nan, nan, 51.37362637362638, 53.90624999999999, 36.64921465968586, 68.29268292682926],
+            "mfi",
+        );
+
+        close_to(&volume::obv_on_series(view), &[0.0, 200.0, 300.0, 0.0, 200.0, 100.0, 100.0], "obv");
+        close_to(&momentum::roc_on_series(view, 3), &[nan, nan, nan, 0.0, 20.0, -200.0 / 23.0, 400.0 / 19.0], "roc");
+
+        // Typical prices 27.5, 30, 33.5, 29.5, 35, 32.5, 34.5 thirds weighted by volume since the
+        // first bar, the last trading none. Bars are a day apart, so session VWAPs restart each bar.
+        let bands = volume::vwap_bands_on_series(view, VwapAnchor::Start, 2.0);
+        let vwap = [2750.0 / 300.0, 8750.0 / 900.0, 12100.0 / 1200.0, 20950.0 / 2100.0, 27950.0 / 2700.0, 10.4, 10.4];
+        let std_dev = [0.0, 0.3928371006592047, 0.7120003121098031, 0.5522577859401541, 0.8550663985640946, 0.8239471396205449, 0.8239471396205449];
+        close_to(&bands.iter().map(|p| p.vwap).collect::<Vec<_>>(), &vwap, "anchored vwap");
+        close_to(&bands.iter().map(|p| p.std_dev).collect::<Vec<_>>(), &std_dev, "vwap std dev");
+        close_to(&bands.iter().map(|p| p.upper).collect::<Vec<_>>(), &vwap.iter().zip(&std_dev).map(|(v, s)| v + 2.0 * s).collect::<Vec<_>>(), "vwap upper");
+        close_to(&bands.iter().map(|p| p.lower).collect::<Vec<_>>(), &vwap.iter().zip(&std_dev).map(|(v, s)| v - 2.0 * s).collect::<Vec<_>>(), "vwap lower");
+
+        let session = volume::vwap_bands_on_series(view, VwapAnchor::Session, 2.0);
+        close_to(&session.iter().map(|p| p.vwap).collect::<Vec<_>>(), &[27.5 / 3.0, 10.0, 33.5 / 3.0, 29.5 / 3.0, 35.0 / 3.0, 32.5 / 3.0, nan], "session vwap");
+        assert!(session[..6].iter().all(|p| p.std_dev < 1e-6) && session[6].std_dev.is_nan());
+
+        // True ranges 2, 2, 2, 2.5, 3.5, 2, 1.5 under Wilder's smoothing, seeded with their mean
+        let atr = [nan, nan, 2.0, 6.5 / 3.0, 23.5 / 9.0, 65.0 / 27.0, 170.5 / 81.0];
+        close_to(&volatility::atr_on_series(view, 3), &atr, "atr");
+
+        // EMA of the closes seeded with their first mean, two ATRs either side
+        let middle = [nan, nan, 31.0 / 3.0, 119.0 / 12.0, 263.0 / 24.0, 515.0 / 48.0, 1067.0 / 96.0];
+        let keltner = volatility::keltner_on_series(view, 3, 3, 2.0);
+        close_to(&keltner.iter().map(|p| p.middle).collect::<Vec<_>>(), &middle, "keltner middle");
+        close_to(&keltner.iter().map(|p| p.upper).collect::<Vec<_>>(), &middle.iter().zip(&atr).map(|(m, a)| m + 2.0 * a).collect::<Vec<_>>(), "keltner upper");
+        close_to(&keltner.iter().map(|p| p.lower).collect::<Vec<_>>(), &middle.iter().zip(&atr).map(|(m, a)| m - 2.0 * a).collect::<Vec<_>>(), "keltner lower");
+
+        // +DM 1, 1, 0, 2, 0, 0 and -DM 0, 0, 1, 0, 0, 0. DX is 100 / 3 on the first smoothed bar
+        // and 200 / 3 after, ADX starts as the mean of the first three
+        let dmi = trend::dmi_on_series(view, 3);
+        close_to(&dmi.iter().map(|p| p.plus_di).collect::<Vec<_>>(), &[nan, nan, nan, 400.0 / 13.0, 2000.0 / 47.0, 400.0 / 13.0, 8000.0 / 341.0], "+di");
+        close_to(&dmi.iter().map(|p| p.minus_di).collect::<Vec<_>>(), &[nan, nan, nan, 200.0 / 13.0, 400.0 / 47.0, 80.0 / 13.0, 1600.0 / 341.0], "-di");
+        close_to(&dmi.iter().map(|p| p.adx).collect::<Vec<_>>(), &[nan, nan, nan, nan, nan, 500.0 / 9.0, 1600.0 / 27.0], "adx");
+
+        // Rising from the first low until bar 3 breaks below the SAR, which restarts at the high
+        // of 12, then falling until bar 5 breaks above it and it restarts at the low of 8
+        let reversing = bars(
+            &[10.0, 11.0, 12.0, 11.0, 10.0, 12.0, 13.0],
+            &[9.0, 10.0, 11.0, 9.5, 8.0, 10.0, 11.5],
+            &[9.5, 10.5, 11.5, 10.0, 8.5, 11.5, 12.5],
+            &[100; 7],
+        );
+        close_to(&trend::psar_on_series(reversing.view(), 0.1, 0.2), &[nan, 9.0, 9.0, 12.0, 12.0, 8.0, 8.0], "psar");
+
+        // Flat prices collapse the Bollinger bands, which puts the close in the middle of them
+        let flat = bars(&[10.0; 5], &[10.0; 5], &[10.0; 5], &[100; 5]);
+        let bollinger = volatility::bollinger_on_series(flat.view(), 3, 2.0);
+        close_to(&bollinger.iter().map(|p| p.percent_b).collect::<Vec<_>>(), &[nan, nan, 0.5, 0.5, 0.5], "percent b");
+        close_to(&momentum::cci_on_series(flat.view(), 3), &[nan, nan, 0.0, 0.0, 0.0], "flat cci");
+
+        // The rolling updates must not drift from a recomputation of each window over a long series
+        let mut rng = StdRng::seed_from_u64(7);
+        let closes = (0..100_000).scan(100.0, |close, _| {
+            *close *= 1.0 + rng.gen_range(-0.01..0.01);
+            Some(*close)
+        }).collect::<Vec<f64>>();
+        let highs = closes.iter().map(|c| c * 1.002).collect::<Vec<_>>();
+        let lows = closes.iter().map(|c| c * 0.998).collect::<Vec<_>>();
+        let long = bars(&highs, &lows, &closes, &vec![1_000; closes.len()]);
+        let view = long.view();
+
+        let cci = momentum::cci_on_series(view, 20);
+        let donchian = volatility::donchian_on_series(view, 20);
+        for i in 19..closes.len() {
+            let window = view.slice(i - 19..=i);
+            let typical = (0..20).map(|j| (window.high[j] + window.low[j] + window.close[j]) / 3.0).collect::<Vec<_>>();
+            let mean = typical.iter().sum::<f64>() / 20.0;
+            let mean_deviation = typical.iter().map(|tp| (tp - mean).abs()).sum::<f64>() / 20.0;
+            let naive_cci = (typical[19] - mean) / (0.015 * mean_deviation);
+
+            assert!((cci[i] - naive_cci).abs() <= 1e-6 * naive_cci.abs().max(1.0), "cci drifted at bar {i}: {} vs {naive_cci}", cci[i]);
+            assert_eq!(donchian[i].upper, window.high.iter().copied().fold(f64::MIN, f64::max), "donchian upper at bar {i}");
+            assert_eq!(donchian[i].lower, window.low.iter().copied().fold(f64::MAX, f64::min), "donchian lower at bar {i}");
+        }
+    }
+
+    #[test]
+    fn test_registry_builds_added_factories_by_name() {
+        use crate::analysis::indicator::{Indicator, IndicatorRegistry};
+        use crate::analysis::moving_average;
+        use crate::data::series::{PriceSeries, SeriesView};
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        // Close over its SMA, an indicator the registry doesn't ship with
+        struct SmaRatio {
+            period: usize,
+        }
+
+        impl Indicator for SmaRatio {
+            fn name(&self) -> &'static str { "sma_ratio" }
+            fn params(&self) -> Vec<(&'static str, f64)> { vec![("period", self.period as f64)] }
+            fn outputs(&self) -> &'static [&'static str] { &["ratio"] }
+            fn warm_up(&self) -> usize { self.period - 1 }
+
+            fn compute(&self, series: SeriesView) -> Vec<Vec<f64>> {
+                let sma = moving_average::sma_on_series(series, self.period as i32);
+                vec![series.close.iter().zip(sma).map(|(close, sma)| close / sma).collect()]
+            }
+        }
+
+        let mut rng = StdRng::seed_from_u64(31);
+        let start = chrono::DateTime::parse_from_rfc3339("2024-01-02T14:30:00Z").unwrap().to_utc();
+        let mut close = 100.0;
+        let frames = (0..300)
+            .map(|i| {
+                close *= 1.0 + rng.gen_range(-0.01..0.01);
+                TickerDataframe { t: start + chrono::Duration::minutes(10 * i), close, ..Default::default() }
+            })
+            .collect::<Vec<_>>();
+        let series = PriceSeries::from(frames.as_slice());
+
+        let mut registry = IndicatorRegistry::new();
+        assert!(registry.build("sma_ratio", &[20.0]).is_none());
+        registry.add_factory("sma_ratio", |p| match p {
+            [period] if *period >= 2.0 => Some(Box::new(SmaRatio { period: *period as usize })),
+            _ => None,
+        });
+        assert!(registry.available().any(|name| name == "sma_ratio"));
+        assert!(registry.build("sma_ratio", &[1.0]).is_none());
+        assert!(registry.build("sma_ratio", &[20.0, 2.0]).is_none());
+
+        let built = registry.build("sma_ratio", &[20.0]).unwrap();
+        let sma = moving_average::sma_on_series(series.view(), 20);
+        let ratio = built.compute(series.view()).remove(0);
+        assert_eq!(ratio.len(), series.close.len());
+        assert!(ratio[..built.warm_up()].iter().all(|v| v.is_nan()));
+        for (i, value) in ratio.iter().enumerate().skip(built.warm_up()) {
+            assert_eq!(value.to_bits(), (series.close[i] / sma[i]).to_bits(), "bar {i}");
+        }
+
+        // Configured like a built-in, next to the built-in it wraps
+        assert_eq!(registry.configure("sma_ratio", &[20.0]).as_deref(), Some("sma_ratio(20)"));
+        assert_eq!(registry.configure("sma", &[20.0]).as_deref(), Some("sma(20)"));
+        assert_eq!(registry.get("sma_ratio(20)").unwrap().column_names(), vec!["sma_ratio_20"]);
+        let builtin = registry.get("sma(20)").unwrap().compute(series.view()).remove(0);
+        assert!(builtin.iter().zip(&sma).all(|(a, b)| a.to_bits() == b.to_bits()));
+    }
+
+    #[test]
+    fn test_realized_vol_estimators_match_reference() {
+        use crate::analysis::volatility::{self, RealizedVolEstimator, RealizedVolWindow};
+        use crate::data::series::PriceSeries;
+
+        // Prices are exponentials of the log prices below, so every log ratio is a difference.
+        // The first bar only supplies the previous close to the three bars measured after it.
+        let logs: [[f64; 4]; 4] = [
+            // open, high, low, close
+            [0.00, 0.02, -0.01, 0.01],
+            [0.02, 0.05, 0.01, 0.04],
+            [0.03, 0.04, 0.00, 0.01],
+            [0.01, 0.03, -0.02, 0.02],
+        ];
+        let start = chrono::DateTime::parse_from_rfc3339("2024-01-02T21:00:00Z").unwrap().to_utc();
+        let frames = logs.iter()
+            .enumerate()
+            .map(|(i, [open, high, low, close])| TickerDataframe {
+                t: start + chrono::Duration::days(i as i64),
+                open: open.exp(),
+                high: high.exp(),
+                low: low.exp(),
+                close: close.exp(),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        let series = PriceSeries::from(frames.as_slice());
+
+        // Per-bar variances worked out by hand from the definitions
+        let ln2 = std::f64::consts::LN_2;
+        let k = 0.34 / (1.34 + 4.0 / 2.0);
+        let cases = [
+            // Returns 0.03, -0.03, 0.01 around their mean of 0.01 / 3
+            (RealizedVolEstimator::CloseToClose, 0.0028 / 3.0),
+            // Squared ranges 0.04², 0.04², 0.05²
+            (RealizedVolEstimator::Parkinson, 0.0057 / (12.0 * ln2)),
+            // Half the squared range less (2 ln 2 - 1) of the squared open-to-close 0.02, -0.02, 0.01
+            (RealizedVolEstimator::GarmanKlass, (0.00285 - (2.0 * ln2 - 1.0) * 0.0009) / 3.0),
+            // 0.01 * 0.03 + 0.03 * 0.01, the same again, then 0.01 * 0.02 + 0.04 * 0.03
+            (RealizedVolEstimator::RogersSatchell, 0.0026 / 3.0),
+            // Overnight 0.01, -0.01, 0 and open-to-close 0.02, -0.02, 0.01 sample variances,
+            // then Rogers-Satchell weighted by k
+            (RealizedVolEstimator::YangZhang, 0.0001 + k * 0.0026 / 6.0 + (1.0 - k) * 0.0026 / 3.0),
+        ];
+
+        for (estimator, variance) in cases {
+            let expected = variance.sqrt();
+            let whole = volatility::realized_volatility(series.view(), estimator, 1.0);
+            assert!((whole - expected).abs() < 1e-12, "{estimator:?}: {whole} vs {expected}");
+
+            let mut window = RealizedVolWindow::new(estimator);
+            (1..4).for_each(|i| window.push(series.view(), i));
+            assert!((window.volatility(1.0) - expected).abs() < 1e-12, "{estimator:?} window");
+
+            let rolling = volatility::realized_vol_on_series(series.view(), 3, estimator, 252.0);
+            assert!(rolling[..3].iter().all(|v| v.is_nan()));
+            assert!((rolling[3] - (variance * 252.0).sqrt()).abs() < 1e-12, "{estimator:?} rolling");
+        }
+    }
+
+    #[test]
+    fn test_normalizer_uses_only_training_state() {
+        use crate::analysis::features::pipeline::FeatureMatrix;
+        use crate::analysis::normalization::{Normalizer, Scaling};
+
+        let matrix = |rows: &[[f64; 2]]| FeatureMatrix {
+            names: vec!["a".to_string(), "b".to_string()],
+            values: ndarray::Array2::from_shape_fn((rows.len(), 2), |(r, c)| rows[r][c]),
+            bars: (0..rows.len()).collect(),
+        };
+
+        let train = matrix(&[[1.0, 10.0], [2.0, 20.0], [3.0, 30.0], [4.0, 1000.0]]);
+        let test = matrix(&[[5.0, 40.0], [-100.0, 0.0]]);
+
+        for scaling in [Scaling::ZScore, Scaling::MinMax, Scaling::Robust] {
+            let (normalizer, _) = Normalizer::fit_transform(scaling, &train);
+            let restored: Normalizer = serde_json::from_str(&serde_json::to_string(&normalizer).unwrap()).unwrap();
+            assert_eq!(normalizer, restored);
+
+            // Scaling the test rows must not depend on what else is in the test set
+            let together = normalizer.transform(&test).unwrap();
+            let alone = normalizer.transform(&matrix(&[[5.0, 40.0]])).unwrap();
+            assert_eq!(together.values.row(0), alone.values.row(0));
+
+            // Each column maps back to the values it was scaled from
+            for (c, scaler) in normalizer.columns.iter().enumerate() {
+                for (value, scaled) in test.values.column(c).iter().zip(together.values.column(c)) {
+                    assert_eq!(scaler.transform(*value), *scaled);
+                    assert!((scaler.inverse(*scaled) - value).abs() < 1e-9 * value.abs().max(1.0), "{scaling:?} column {c}");
+                }
+            }
+        }
+
+        let (_, scaled) = Normalizer::fit_transform(Scaling::MinMax, &train);
+        assert_eq!(scaled.values.column(0).to_vec(), vec![0.0, 1.0 / 3.0, 2.0 / 3.0, 1.0]);
+
+        let other = FeatureMatrix { names: vec!["b".to_string(), "a".to_string()], ..test.clone() };
+        assert!(Normalizer::fit(Scaling::ZScore, &train).transform(&other).is_err());
+    }
+
+    #[test]
+    fn test_direction_model_scales_with_training_state() {
+        use crate::analysis::features::{featureset::DirectionDataset, pipeline::FeatureMatrix};
+        use crate::analysis::normalization::{Normalizer, Scaling};
+        use crate::analysis::strategies::gradient_trees;
+
+        // Up whenever the first feature is positive, the second sits on a far larger scale
+        let rows = 300;
+        let features = FeatureMatrix {
+            names: vec!["signal".to_string(), "level".to_string()],
+            values: ndarray::Array2::from_shape_fn((rows, 2), |(r, c)| if c == 0 { (r as f64 * 0.7).sin() } else { 1e4 + (r % 13) as f64 * 100.0 }),
+            bars: (0..rows).collect(),
+        };
+        let direction = features.values.column(0).iter().map(|&v| if v > 0.0 { PriceDirection::Up } else { PriceDirection::Down }).collect::<Vec<_>>();
+        let dataset = DirectionDataset { features, direction, ..Default::default() };
+
+        let model = gradient_trees::train(&dataset).unwrap();
+        assert_eq!(model.normalizer, Normalizer::fit(Scaling::Robust, &dataset.features));
+
+        let (scaled, targets) = model.prepare(&dataset).unwrap();
+        assert!(scaled.column(1).iter().all(|v| v.abs() < 2.0));
+        assert_eq!(model.predict(&dataset).unwrap(), targets);
+
+        // A row is scaled and predicted the same on its own as within the whole set
+        let first = DirectionDataset {
+            features: FeatureMatrix { values: dataset.features.values.slice(ndarray::s![..1, ..]).to_owned(), bars: vec![0], ..dataset.features.clone() },
+            direction: dataset.direction[..1].to_vec(),
+            ..Default::default()
+        };
+        assert_eq!(model.predict(&first).unwrap()[0], model.predict(&dataset).unwrap()[0]);
+
+        let renamed = DirectionDataset { features: FeatureMatrix { names: vec!["a".to_string(), "b".to_string()], ..dataset.features.clone() }, ..dataset.clone() };
+        assert!(model.predict(&renamed).is_err());
+    }
+
+    #[test]
+    fn test_labels_depend_only_on_their_horizon() {
+        use crate::analysis::features::labels::*;
+        use crate::data::series::PriceSeries;
+
+        let start = chrono::DateTime::parse_from_rfc3339("2024-01-02T14:30:00Z").unwrap().to_utc();
+        let frames = (0..2_000i64)
+            .map(|i| {
+                let close = 100.0 * (1.0 + 0.02 * ((i as f64) * 0.7).sin() + 0.0001 * i as f64);
+                TickerDataframe {
+                    t: start + chrono::Duration::minutes(10 * i),
+                    open: close * 0.999,
+                    high: close * 1.002,
+                    close,
+                    low: close * 0.997,
+                    vol: 1_000,
+                    vol_weighted: close,
+                    session: Default::default(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let rules = [
+            LabelRule::Threshold(Threshold::Fixed(0.005)),
+            LabelRule::Threshold(Threshold::VolScaled { window: 20, multiplier: 1.0 }),
+            LabelRule::Threshold(Threshold::Quantile { window: 100, lower: 0.3, upper: 0.7 }),
+            LabelRule::TripleBarrier(TripleBarrier { take_profit: 1.0, stop_loss: 1.0, vol_window: 20 }),
+        ];
+        let shocked_bar = 1_500;
+        let mut shocked = frames.clone();
+        shocked[shocked_bar].close *= 1.5;
+        shocked[shocked_bar].high *= 1.5;
+
+        for horizon in [Horizon::NextBar, Horizon::NextSession, Horizon::Bars(12)] {
+            for rule in rules {
+                let params = LabelParams { horizon, rule };
+                let labels = label_series(PriceSeries::from(frames.as_slice()).view(), params);
+                let after_shock = label_series(PriceSeries::from(shocked.as_slice()).view(), params);
+
+                assert!(labels.iter().flatten().all(|label| label.end > 0), "{params:?}");
+                assert!(labels.iter().flatten().any(|label| label.direction.is_some()), "{params:?}");
+
+                // Changing a bar must not change any label decided before it
+                for (i, (label, other)) in labels.iter().zip(&after_shock).enumerate().take(shocked_bar) {
+                    if let Some(label) = label.filter(|label| label.end < shocked_bar) {
+                        assert_eq!(Some(label), *other, "{params:?} at bar {i}");
+                        assert!(label.end > i, "{params:?} at bar {i}");
+                    }
+                }
+            }
+        }
+
+        // Splitting for a test purges the training rows whose labels reach into it
+        let data = TickerData { symbol: "TEST".to_string(), price_data: PriceSeries::from(frames.as_slice()), technicals: Vec::new() };
+        let params = LabelParams { horizon: Horizon::Bars(12), rule: rules[0] };
+        let dataset = featureset::calculate_featureset_with(&data, &featureset::direction_pipeline(), &Default::default(), params).unwrap();
+        let split_bar = dataset.features.bars[dataset.features.bars.len() / 2];
+        let (train, test) = dataset.split_at_bar(split_bar);
+        assert!(train.label_end.iter().all(|&end| end < split_bar));
+        assert!(test.features.bars.iter().all(|&bar| bar >= split_bar));
+        assert_eq!(train.features.values.nrows() + test.features.values.nrows() + 12, dataset.features.values.nrows());
+        assert_eq!(test.features.values.row(0), dataset.features.values.row(dataset.features.bars.len() / 2));
+    }
+
+    #[test]
+    fn test_features_depend_only_on_past_bars() {
+        use crate::analysis::features::labels::*;
+        use crate::data::series::PriceSeries;
+
+        // Extended-hours 10-minute bars over a few months
+        let start = chrono::DateTime::parse_from_rfc3339("2024-01-02T09:00:00Z").unwrap().to_utc();
+        let frames = (0..12_000i64)
+            .map(|i| {
+                let t = start + chrono::Duration::minutes(10 * i);
+                let close = 100.0 * (1.0 + 0.02 * ((i as f64) * 0.3).sin() + 0.00005 * i as f64);
+                TickerDataframe { t, open: close * 0.999, high: close * 1.002, close, low: close * 0.997, vol: 1_000 + i % 7, vol_weighted: close, session: MarketSession::of(t) }
+            })
+            .collect::<Vec<_>>();
+        let series = PriceSeries::from(frames.as_slice());
+        let cut = 3_000;
+        let (full, prefix) = (series.view(), series.view().slice(..cut));
+
+        let pipeline = featureset::direction_pipeline();
+        let (full_columns, prefix_columns) = (pipeline.columns(full).unwrap(), pipeline.columns(prefix).unwrap());
+        for (name, (full, prefix)) in pipeline.column_names().iter().zip(full_columns.iter().zip(&prefix_columns)) {
+            for i in 0..cut {
+                assert!(full[i].to_bits() == prefix[i].to_bits() || (full[i].is_nan() && prefix[i].is_nan()), "{name} at bar {i} sees later bars");
+            }
+        }
+
+        let params = LabelParams { horizon: Horizon::Bars(6), rule: LabelRule::Threshold(Threshold::VolScaled { window: 20, multiplier: 1.0 }) };
+        let (full_labels, prefix_labels) = (label_series(full, params), label_series(prefix, params));
+        for (i, label) in prefix_labels.iter().enumerate() {
+            if let Some(label) = label {
+                assert_eq!(Some(*label), full_labels[i], "label at bar {i} sees bars past its horizon");
+            }
+        }
+    }
+
+    #[test]
+    fn test_sessions_follow_exchange_calendar() {
+        use crate::analysis::features::sessions;
+        use crate::data::{calendar, series::PriceSeries};
+        use chrono::{Datelike, NaiveDate, TimeZone};
+
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        assert!(calendar::is_holiday(date(2024, 3, 29)), "Good Friday");
+        assert!(calendar::is_holiday(date(2022, 6, 20)), "Juneteenth observed on Monday");
+        assert!(calendar::is_trading_day(date(2021, 12, 31)), "Saturday New Year isn't observed on Friday");
+        assert_eq!(calendar::regular_close(date(2024, 7, 3)), Some(calendar::EARLY_CLOSE));
+        assert_eq!(calendar::previous_trading_day(date(2024, 1, 16)), date(2024, 1, 12));
+        assert_eq!(calendar::next_trading_day(date(2001, 9, 10)), date(2001, 9, 17), "closed after the September 11 attacks");
+        assert!(calendar::is_holiday(date(2004, 6, 11)) && calendar::is_holiday(date(2007, 1, 2)), "days of mourning");
+
+        // 10-minute bars from 04:00 to 20:00 around Thanksgiving, the Friday after closes at 13:00
+        let mut frames = Vec::new();
+        for day in [date(2024, 11, 27), date(2024, 11, 29), date(2024, 12, 2)] {
+            let start = chrono_tz::America::New_York.from_local_datetime(&day.and_hms_opt(4, 0, 0).unwrap()).unwrap().to_utc();
+            for i in 0..96 {
+                let price = 100.0 + day.day0() as f64 + i as f64 * 0.01;
+                frames.push(TickerDataframe {
+                    t: start + chrono::Duration::minutes(10 * i),
+                    open: price,
+                    high: price + 0.05,
+                    close: price + 0.01,
+                    low: price - 0.05,
+                    vol: 100,
+                    vol_weighted: price,
+                    session: Default::default(),
+                });
+            }
+        }
+
+        let series = PriceSeries::from(frames.as_slice());
+        let summaries = sessions::regular_sessions(series.view());
+        assert_eq!(summaries.iter().map(|s| s.date).collect::<Vec<_>>(), vec![date(2024, 11, 27), date(2024, 11, 29), date(2024, 12, 2)]);
+        assert_eq!(summaries[0].last_bar - summaries[0].first_bar + 1, 39);
+        assert_eq!(summaries[1].last_bar - summaries[1].first_bar + 1, 21);
+        assert!((summaries[0].opening_hour_volume - 6.0 / 39.0).abs() < 1e-12);
+
+        let gaps = sessions::overnight_gaps(series.view(), &summaries, 0.01);
+        assert_eq!(gaps.len(), 2);
+        assert_eq!((gaps[0].from, gaps[0].to), (date(2024, 11, 27), date(2024, 11, 29)));
+        assert_eq!(gaps[0].direction, PriceDirection::Up);
+        assert!((gaps[0].gap - (series.open[gaps[0].to_bar] / series.close[gaps[0].from_bar] - 1.0)).abs() < 1e-12);
+
+        // Dropping the Friday leaves no consecutive trading sessions to measure a gap between
+        let without_friday = frames.iter().filter(|bar| bar.local_time().date_naive() != date(2024, 11, 29)).cloned().collect::<Vec<_>>();
+        let series = PriceSeries::from(without_friday.as_slice());
+        assert!(sessions::overnight_gaps(series.view(), &sessions::regular_sessions(series.view()), 0.01).is_empty());
+    }
+
+    #[test]
+    fn test_macro_values_apply_from_the_close() {
+        use crate::analysis::features::pipeline::{DailyInputs, FeaturePipeline, FeatureSource, FeatureSpec};
+        use crate::data::{macro_series::{self, MacroSeries}, series::PriceSeries};
+        use chrono::{NaiveDate, TimeZone};
+
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let values = vec![(date(2024, 11, 26), 1.0), (date(2024, 11, 27), 2.0), (date(2024, 11, 29), 3.0), (date(2024, 12, 2), 4.0)];
+
+        // 10-minute bars from 04:00 to 20:00, except the last day which stops at noon
+        let mut frames = Vec::new();
+        for (day, bars) in [(date(2024, 11, 27), 96), (date(2024, 11, 29), 96), (date(2024, 12, 2), 48)] {
+            let start = chrono_tz::America::New_York.from_local_datetime(&day.and_hms_opt(4, 0, 0).unwrap()).unwrap().to_utc();
+            for i in 0..bars {
+                let t = start + chrono::Duration::minutes(10 * i);
+                frames.push(TickerDataframe { t, close: 100.0, session: MarketSession::of(t), ..Default::default() });
+            }
+        }
+        let series = PriceSeries::from(frames.as_slice());
+        let aligned = macro_series::align_daily(series.view(), &values);
+
+        // The 15:50 bar ends at the close, on the early close it's the 12:50 bar
+        assert_eq!((aligned[70], aligned[71], aligned[95]), (1.0, 2.0, 2.0));
+        assert_eq!((aligned[96 + 52], aligned[96 + 53]), (2.0, 3.0));
+        assert_eq!(*aligned.last().unwrap(), 3.0, "a day cut short before its close");
+
+        // Daily bars span the close of their day
+        let daily = [date(2024, 11, 27), date(2024, 11, 29), date(2024, 12, 2)]
+            .map(|day| TickerDataframe {
+                t: chrono_tz::America::New_York.from_local_datetime(&day.and_hms_opt(0, 0, 0).unwrap()).unwrap().to_utc(),
+                close: 100.0,
+                session: MarketSession::FullDay,
+                ..Default::default()
+            });
+        assert_eq!(macro_series::align_daily(PriceSeries::from(daily.as_slice()).view(), &values), vec![2.0, 3.0, 4.0]);
+
+        let inputs = DailyInputs { macros: vec![(MacroSeries::Vix, values.clone())], ..Default::default() };
+        let pipeline = FeaturePipeline::new(vec![FeatureSpec::new("vix", FeatureSource::Macro(MacroSeries::Vix), 0)]);
+        let column = pipeline.columns_with(series.view(), &inputs).unwrap().remove(0);
+        assert!(column.iter().zip(&aligned).all(|(a, b)| a.to_bits() == b.to_bits()));
+        let missing = pipeline.with(FeatureSpec::new("vix3m", FeatureSource::Macro(MacroSeries::Vix3m), 0));
+        assert!(missing.columns_with(series.view(), &inputs).is_err());
+    }
+
+    #[test]
+    fn test_macro_features_align_to_the_session_close() {
+        use crate::analysis::features::{featureset::{self, FeatureOptions}, labels::LabelParams, pipeline::DailyInputs};
+        use crate::data::{calendar, macro_series::{self, MacroSeries}, series::PriceSeries, types::*};
+        use chrono_tz::America::New_York;
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        // 10-minute bars from 08:00 to 18:00 New York, with a new VIX and 10y yield each day
+        let mut rng = StdRng::seed_from_u64(45);
+        let (mut frames, mut vix, mut yields) = (Vec::new(), Vec::new(), Vec::new());
+        let mut price = 100.0;
+        let mut day = chrono::NaiveDate::from_ymd_opt(2024, 9, 3).unwrap();
+        while day <= chrono::NaiveDate::from_ymd_opt(2024, 12, 31).unwrap() {
+            for step in 0..60 {
+                let t = day.and_hms_opt(8, 0, 0).unwrap().and_local_timezone(New_York).unwrap().to_utc() + chrono::Duration::minutes(10 * step);
+                let open = price;
+                price *= 1.0 + rng.gen_range(-0.002..0.002);
+                frames.push(TickerDataframe {
+                    t,
+                    open,
+                    high: open.max(price) * 1.0005,
+                    low: open.min(price) * 0.9995,
+                    close: price,
+                    vol: 1_000,
+                    vol_weighted: (open + price) / 2.0,
+                    session: MarketSession::of(t),
+                });
+            }
+            vix.push((day, 15.0 + vix.len() as f64));
+            yields.push((day, 4.0 + 0.01 * yields.len() as f64));
+            day = calendar::next_trading_day(day);
+        }
+        let days = vix.iter().map(|&(day, _)| day).collect::<Vec<_>>();
+        let data = TickerData { symbol: "MACRO".to_string(), price_data: PriceSeries::from(frames.as_slice()), technicals: Vec::new() };
+
+        let options = FeatureOptions { macros: vec![MacroSeries::Vix, MacroSeries::Treasury10y], ..Default::default() };
+        let inputs = DailyInputs { macros: vec![(MacroSeries::Vix, vix.clone()), (MacroSeries::Treasury10y, yields.clone())], ..Default::default() };
+        let dataset = featureset::calculate_featuresets(std::slice::from_ref(&data), &options, &inputs).remove(0).unwrap();
+        let names = &dataset.features.names;
+        assert_eq!(names[..names.len() - 2], featureset::direction_pipeline().column_names()[..]);
+        assert_eq!(names[names.len() - 2..], ["vix", "ust_10y"]);
+
+        let direct = featureset::calculate_featureset_with(&data, &featureset::macro_pipeline(&options.macros), &inputs, LabelParams::default()).unwrap();
+        assert_eq!(direct.features.bars, dataset.features.bars);
+        assert!(direct.features.values.iter().zip(dataset.features.values.iter()).all(|(a, b)| a.to_bits() == b.to_bits()));
+
+        // A day's value applies from the bar ending at its close, earlier bars see the prior day's
+        let aligned = [&vix, &yields].map(|values| macro_series::align_daily(data.series(), values));
+        let mut after_close = 0;
+        assert!(!dataset.features.bars.is_empty());
+        for (row, &bar) in dataset.features.bars.iter().enumerate() {
+            let index = bar / 60;
+            let (_, close) = calendar::regular_session(days[index]).unwrap();
+            let end = data.price_data.t[bar] + 600;
+            let expected = if end >= close.timestamp() { index } else { index - 1 };
+            if end >= close.timestamp() {
+                after_close += 1;
+            }
+
+            let vix_column = dataset.features.values[[row, names.len() - 2]];
+            let yield_column = dataset.features.values[[row, names.len() - 1]];
+            assert_eq!(vix_column, vix[expected].1, "bar {bar}");
+            assert_eq!(yield_column, yields[expected].1, "bar {bar}");
+            assert_eq!((vix_column.to_bits(), yield_column.to_bits()), (aligned[0][bar].to_bits(), aligned[1][bar].to_bits()));
+        }
+        assert!(after_close > 0 && after_close < dataset.features.bars.len());
+    }
+
+    #[test]
+    fn test_cache_key_fingerprints_series() {
+        use crate::data::{cache::CacheKey, series::PriceSeries};
+
+        let start = chrono::DateTime::parse_from_rfc3339("2024-01-02T14:30:00Z").unwrap().to_utc();
+        let bars = (0..10)
+            .map(|i| TickerDataframe { t: start + chrono::Duration::minutes(10 * i), close: 100.0 + i as f64, ..Default::default() })
+            .collect::<Vec<_>>();
+        let data = TickerData { symbol: "SPY".to_string(), price_data: PriceSeries::from(bars.as_slice()), technicals: Vec::new() };
+
+        let mut changed = data.clone();
+        changed.price_data.close[5] += 1.0;
+        let mut thinned = bars.clone();
+        thinned.remove(5);
+        let thinned = TickerData { price_data: PriceSeries::from(thinned.as_slice()), ..data.clone() };
+
+        let key = CacheKey::for_series(&data);
+        assert_eq!(key, CacheKey::for_series(&data.clone()));
+        assert_ne!(key, CacheKey::for_series(&changed));
+        assert_ne!(key, CacheKey::for_series(&thinned));
+        assert_eq!(key.timeframe.as_deref(), Some("600s"));
+    }
+
+    #[test]
+    fn test_garch_recovers_simulated_params() {
+        use crate::analysis::garch::{GarchKind, GarchModel, GarchParams};
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(69);
+        let mut simulate = |params: GarchParams, n: usize| {
+            let mut variance = params.long_run_variance();
+            (0..n)
+                .map(|_| {
+                    // Box-Muller
+                    let (u, v) = (rng.gen_range(f64::EPSILON..1.0), rng.gen_range(0.0..1.0));
+                    let shock = variance.sqrt() * (-2.0 * f64::ln(u)).sqrt() * (2.0 * std::f64::consts::PI * v).cos();
+                    let arch = if shock < 0.0 { params.alpha + params.gamma } else { params.alpha };
+                    variance = params.omega + arch * shock * shock + params.beta * variance;
+                    params.mu + shock
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let cases = [
+            (GarchKind::Garch, GarchParams { mu: 5e-4, omega: 2e-6, alpha: 0.08, gamma: 0.0, beta: 0.90 }),
+            (GarchKind::GjrGarch, GarchParams { mu: 5e-4, omega: 2e-6, alpha: 0.03, gamma: 0.10, beta: 0.90 }),
+        ];
+
+        for (kind, truth) in cases {
+            let returns = simulate(truth, 20_000);
+            let model = GarchModel::fit(&returns, kind).unwrap();
+            let fitted = model.params;
+
+            assert!(model.converged, "{kind:?} did not converge");
+            assert!((fitted.mu - truth.mu).abs() < 2.5e-4, "{kind:?} mu {} vs {}", fitted.mu, truth.mu);
+            assert!((fitted.alpha - truth.alpha).abs() < 0.02, "{kind:?} alpha {} vs {}", fitted.alpha, truth.alpha);
+            assert!((fitted.gamma - truth.gamma).abs() < 0.04, "{kind:?} gamma {} vs {}", fitted.gamma, truth.gamma);
+            assert!((fitted.beta - truth.beta).abs() < 0.03, "{kind:?} beta {} vs {}", fitted.beta, truth.beta);
+            assert!((fitted.long_run_variance() / truth.long_run_variance() - 1.0).abs() < 0.2);
+
+            // The likelihood is maximized over mu too, so the sample mean can't do better
+            let sample_mean = GarchParams { mu: returns.iter().sum::<f64>() / returns.len() as f64, ..fitted };
+            assert!(model.log_likelihood >= sample_mean.log_likelihood(&returns) - 1e-6);
+
+            // Multi-step forecasts decay monotonically onto the unconditional variance
+            let forecast = model.forecast(5_000);
+            let long_run = fitted.long_run_variance();
+            let gaps = forecast.iter().map(|v| (v - long_run).abs()).collect::<Vec<_>>();
+            assert!(gaps.windows(2).all(|w| w[1] <= w[0]));
+            assert!(gaps.last().unwrap() / long_run < 1e-9);
+            assert!((model.forecast_volatility(5_000, 252.0) - (long_run * 252.0).sqrt()).abs() / (long_run * 252.0).sqrt() < 0.01);
+        }
+    }
+
+    #[test]
+    fn test_garch_forecasts_track_realized_vol() {
+        use crate::analysis::garch::{GarchKind, GarchParams};
+        use crate::analysis::vol_spread::{self, VolSpreadParams};
+        use crate::data::{calendar, series::PriceSeries, types::*};
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        let truth = GarchParams { mu: 0.0, omega: 4e-6, alpha: 0.08, gamma: 0.0, beta: 0.90 };
+        let mut rng = StdRng::seed_from_u64(69);
+        let mut variance = truth.long_run_variance();
+
+        // Daily closes along a simulated GARCH(1,1) path
+        let mut day = chrono::NaiveDate::from_ymd_opt(2000, 1, 3).unwrap();
+        let mut close = 100.0;
+        let frames = (0..6_000)
+            .map(|_| {
+                let (u, v) = (rng.gen_range(f64::EPSILON..1.0), rng.gen_range(0.0..1.0));
+                let shock = variance.sqrt() * (-2.0 * f64::ln(u)).sqrt() * (2.0 * std::f64::consts::PI * v).cos();
+                variance = truth.omega + truth.alpha * shock * shock + truth.beta * variance;
+                close *= shock.exp();
+                let t = parse_timestamp(&day.to_string()).unwrap();
+                day = calendar::next_trading_day(day);
+                TickerDataframe { t, open: close, high: close, low: close, close, vol: 1, vol_weighted: close, session: MarketSession::FullDay }
+            })
+            .collect::<Vec<_>>();
+        let series = PriceSeries::from(frames.as_slice());
+
+        let params = VolSpreadParams { horizon_days: 30, garch_kind: GarchKind::Garch, garch_fit_bars: 2_000, ..Default::default() };
+        let report = vol_spread::garch_fit_report(series.view(), params).unwrap();
+
+        let fit = &report.diagnostics;
+        assert_eq!(fit.observations, 2_000);
+        assert!((fit.persistence - truth.persistence()).abs() < 0.03, "persistence {}", fit.persistence);
+        assert!((fit.residual_variance - 1.0).abs() < 0.1 && fit.residual_mean.abs() < 0.1);
+        assert!((fit.residual_kurtosis - 3.0).abs() < 0.5, "kurtosis {}", fit.residual_kurtosis);
+
+        // 30 calendar days are 21 trading days, every bar with that many after it is scored
+        let forecast = &report.forecast_vs_realized;
+        assert_eq!(forecast.observations, 6_000 - 2_000 - 21);
+        assert!((forecast.bias - (forecast.mean_forecast - forecast.mean_realized)).abs() < 1e-12);
+        assert!(forecast.mae <= forecast.rmse);
+        assert!((forecast.mean_forecast / forecast.mean_realized - 1.0).abs() < 0.1, "{forecast:?}");
+        assert!(forecast.correlation > 0.3, "{forecast:?}");
+
+        assert!(vol_spread::garch_fit_report(series.view().slice(..2_001), params).is_none());
+    }
+
+    #[test]
+    fn test_black_scholes_parity_greeks_and_iv_round_trip() {
+        use crate::analysis::options::black_scholes::{EuropeanOption, PricingModel};
+        use crate::data::types::OptionType;
+
+        let (spot, rate, dividend_yield) = (100.0, 0.04, 0.02);
+
+        for model in [PricingModel::BlackScholes, PricingModel::Black76] {
+            for strike in [70.0, 95.0, 100.0, 110.0, 130.0] {
+                for time_to_expiry in [0.05, 0.5, 2.0] {
+                    for vol in [0.1, 0.3, 0.8] {
+                        let option = |option_type| EuropeanOption {
+                            option_type,
+                            model,
+                            underlying: spot,
+                            strike,
+                            time_to_expiry,
+                            rate,
+                            dividend_yield,
+                        };
+                        let (call, put) = (option(OptionType::Call), option(OptionType::Put));
+
+                        // C - P is the discounted forward less the discounted strike
+                        let carry = if model == PricingModel::BlackScholes { dividend_yield } else { rate };
+                        let parity = spot * (-carry * time_to_expiry).exp() - strike * (-rate * time_to_expiry).exp();
+                        assert!((call.price(vol) - put.price(vol) - parity).abs() < 1e-10 * spot);
+
+                        for european in [call, put] {
+                            let greeks = european.greeks(vol);
+                            let price_with = |f: &dyn Fn(&mut EuropeanOption)| {
+                                let mut bumped = european;
+                                f(&mut bumped);
+                                bumped.price(vol)
+                            };
+                            let close = |analytic: f64, numeric: f64| (analytic - numeric).abs() < 1e-4 * analytic.abs().max(1e-3);
+
+                            let h = spot * 1e-4;
+                            let up = price_with(&|o| o.underlying += h);
+                            let down = price_with(&|o| o.underlying -= h);
+                            assert!(close(greeks.delta, (up - down) / (2.0 * h)), "{european:?} delta");
+                            assert!(close(greeks.gamma, (up - 2.0 * european.price(vol) + down) / (h * h)), "{european:?} gamma");
+
+                            let dt = 1e-6;
+                            let theta = -(price_with(&|o| o.time_to_expiry += dt) - price_with(&|o| o.time_to_expiry -= dt)) / (2.0 * dt);
+                            assert!(close(greeks.theta, theta / 365.0), "{european:?} theta");
+
+                            let dr = 1e-6;
+                            let rho = (price_with(&|o| o.rate += dr) - price_with(&|o| o.rate -= dr)) / (2.0 * dr);
+                            assert!(close(greeks.rho, rho / 100.0), "{european:?} rho");
+
+                            let dv = 1e-6;
+                            let vega = (european.price(vol + dv) - european.price(vol - dv)) / (2.0 * dv);
+                            assert!(close(greeks.vega, vega / 100.0), "{european:?} vega");
+
+                            // Far from the money the price barely moves with vol, so only solvable quotes round-trip
+                            if greeks.vega > 1e-3 {
+                                let iv = european.implied_vol(european.price(vol)).unwrap();
+                                assert!((iv - vol).abs() < 1e-6, "{european:?} iv {iv} vs {vol}");
+                            }
+                        }
+
+                        let (lower, upper) = call.price_bounds();
+                        assert!(call.implied_vol(lower).is_none() && call.implied_vol(upper).is_none());
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_vendor_discrepancies_flag_only_inconsistent_quotes() {
+        use crate::analysis::options::black_scholes::{self, DiscrepancyTolerance, EuropeanOption};
+        use crate::data::types::{OptionType, StockOption};
+
+        let (spot, rate, dividend_yield, vol) = (100.0, 0.04, 0.01, 0.25);
+        let quote_date = chrono::NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+
+        // Quoted a cent either side of the model price, with the vendor's figures at the true vol
+        let quote = |contract_id: &str, strike: f64| {
+            let mut option = StockOption {
+                contract_id: contract_id.to_string(),
+                expiry_date: quote_date + chrono::Days::new(60),
+                strike_price: strike,
+                option_type: OptionType::Call,
+                ..Default::default()
+            };
+            let european = EuropeanOption::from_quote(&option, quote_date, spot, rate, dividend_yield);
+            let (price, greeks) = (european.price(vol), european.greeks(vol));
+            option.bid = price - 0.01;
+            option.ask = price + 0.01;
+            option.implied_volatility = vol;
+            option.delta = greeks.delta;
+            option.gamma = greeks.gamma;
+            option.theta = greeks.theta;
+            option.vega = greeks.vega;
+            option.rho = greeks.rho;
+            option
+        };
+
+        let consistent = quote("CONSISTENT", 100.0);
+        let mut wrong = quote("WRONG", 105.0);
+        wrong.implied_volatility = 0.35;
+        wrong.delta += 0.1;
+
+        let report = black_scholes::vendor_discrepancies(&[consistent, wrong.clone()], quote_date, spot, rate, dividend_yield, DiscrepancyTolerance::default());
+        assert!(report.iter().all(|d| d.contract_id == "WRONG"), "{report:?}");
+
+        let iv = report.iter().find(|d| d.field == "implied_volatility").unwrap();
+        assert_eq!(iv.vendor, 0.35);
+        assert!((iv.computed - vol).abs() < 1e-3);
+
+        // Greeks are checked at the vendor's IV
+        let at_vendor_iv = EuropeanOption::from_quote(&wrong, quote_date, spot, rate, dividend_yield).greeks(0.35);
+        let delta = report.iter().find(|d| d.field == "delta").unwrap();
+        assert_eq!((delta.vendor, delta.computed), (wrong.delta, at_vendor_iv.delta));
+    }
+
+    #[test]
+    fn test_svi_surface_recovers_slices_and_flags_calendar_arbitrage() {
+        use crate::analysis::surface::{self, SviSlice, VolSurface};
+        use crate::data::types::{OptionType, StockOption};
+
+        let truth = SviSlice { time_to_expiry: 0.25, a: 0.01, b: 0.1, rho: -0.4, m: 0.02, sigma: 0.15, rmse: 0.0 };
+        let quotes = surface::linspace(-0.5, 0.4, 25).into_iter().map(|k| (k, truth.implied_vol(k))).collect::<Vec<_>>();
+
+        let fitted = SviSlice::fit(truth.time_to_expiry, &quotes).unwrap();
+        assert!(fitted.rmse < 1e-4, "rmse {}", fitted.rmse);
+        for k in surface::linspace(-0.5, 0.4, 91) {
+            assert!((fitted.total_variance(k) - truth.total_variance(k)).abs() < 1e-4);
+        }
+
+        // Chains quoted off a forward that carries the dividend yield
+        let quote_date = chrono::NaiveDate::from_ymd_opt(2024, 1, 2).unwrap();
+        let (spot, rate, dividend_yield) = (100.0, 0.04, 0.03);
+        let chain = |slices: &[(i64, SviSlice)]| {
+            slices
+                .iter()
+                .flat_map(|&(days, slice)| {
+                    let forward = spot * ((rate - dividend_yield) * slice.time_to_expiry).exp();
+                    (0..=32).flat_map(move |i| {
+                        let strike = 60.0 + 2.5 * i as f64;
+                        [OptionType::Call, OptionType::Put].map(|option_type| StockOption {
+                            expiry_date: quote_date + chrono::Days::new(days as u64),
+                            strike_price: strike,
+                            option_type,
+                            implied_volatility: slice.implied_vol((strike / forward).ln()),
+                            ..Default::default()
+                        })
+                    })
+                })
+                .collect::<Vec<_>>()
+        };
+        let slice = |days: i64, scale: f64| (days, SviSlice { time_to_expiry: days as f64 / 365.0, a: 0.01 * scale, b: 0.1 * scale, ..truth });
+
+        let calm = chain(&[slice(30, 1.0), slice(90, 3.0)]);
+        let surface = VolSurface::build(&calm, quote_date, spot, rate, dividend_yield).unwrap();
+        assert_eq!(surface.slices.len(), 2);
+        assert!(surface.slices.iter().all(|s| s.rmse < 1e-3));
+        assert!(surface.calendar_violations(1e-4).is_empty());
+
+        // Quotes sit at their moneyness against the dividend-adjusted forward, so the fitted smile
+        // lines up with the one they were drawn from, at the money included
+        for point in &surface.points {
+            let forward = spot * ((rate - dividend_yield) * point.time_to_expiry).exp();
+            assert!((point.log_moneyness - (point.strike / forward).ln()).abs() < 1e-12);
+        }
+        for (days, truth) in [slice(30, 1.0), slice(90, 3.0)] {
+            let atm = surface.implied_vol_at(0.0, truth.time_to_expiry);
+            assert!((atm - truth.implied_vol(0.0)).abs() < 1e-3, "{days}d atm: {atm} vs {}", truth.implied_vol(0.0));
+
+            let forward = spot * ((rate - dividend_yield) * truth.time_to_expiry).exp();
+            for strike in [80.0, 90.0, 100.0, 115.0, 130.0] {
+                let iv = surface.implied_vol(strike, quote_date + chrono::Days::new(days as u64));
+                let expected = truth.implied_vol((strike / forward).ln());
+                assert!((iv - expected).abs() < 1e-3, "{days}d {strike}: {iv} vs {expected}");
+            }
+        }
+
+        // The far expiry carrying less total variance than the near one is a free calendar spread
+        let inverted = chain(&[slice(30, 1.0), slice(90, 0.5)]);
+        let surface = VolSurface::build(&inverted, quote_date, spot, rate, dividend_yield).unwrap();
+        let violations = surface.calendar_violations(1e-4);
+        assert!(!violations.is_empty());
+        assert!(violations.iter().all(|v| v.far_variance < v.near_variance && v.far_expiry > v.near_expiry));
+    }
+
+    #[test]
+    fn test_american_trees_bound_and_converge() {
+        use crate::analysis::options::american::{AmericanOption, Dividend, TreeMethod};
+        use crate::analysis::options::black_scholes::{EuropeanOption, PricingModel};
+        use crate::data::types::OptionType;
+
+        let (spot, rate, vol) = (100.0, 0.05, 0.3);
+
+        for option_type in [OptionType::Call, OptionType::Put] {
+            for strike in [80.0, 100.0, 120.0] {
+                for time_to_expiry in [0.1, 1.0] {
+                    let american = AmericanOption { option_type, spot, strike, time_to_expiry, rate, dividend_yield: 0.0, dividends: Vec::new() };
+                    let european = EuropeanOption {
+                        option_type,
+                        model: PricingModel::BlackScholes,
+                        underlying: spot,
+                        strike,
+                        time_to_expiry,
+                        rate,
+                        dividend_yield: 0.0,
+                    };
+
+                    let binomial = american.price(vol, 1000, TreeMethod::Binomial);
+                    let trinomial = american.price(vol, 1000, TreeMethod::Trinomial);
+                    assert!((binomial - trinomial).abs() < 0.01, "{option_type:?} {strike} {time_to_expiry}: {binomial} vs {trinomial}");
+
+                    match option_type {
+                        // Early exercise is never worth it on a call without dividends
+                        OptionType::Call => assert!((binomial - european.price(vol)).abs() < 0.01),
+                        OptionType::Put => assert!(binomial >= european.price(vol) - 1e-3 && trinomial >= european.price(vol) - 1e-3),
+                    }
+
+                    // Quotes worth exercising right away have no time value to back a volatility out of
+                    for method in [TreeMethod::Binomial, TreeMethod::Trinomial] {
+                        let price = american.price(vol, 200, method);
+                        let iv = american.implied_vol(price, 200, method);
+                        if price - european.intrinsic() < 1e-6 {
+                            assert!(iv.is_none());
+                            continue;
+                        }
+                        assert!((iv.unwrap() - vol).abs() < 1e-6, "{option_type:?} {strike} {method:?} iv {iv:?}");
+                    }
+                }
+            }
+        }
+
+        // A deep in the money put is worth exercising early, and a dividend makes the call worth more American
+        let put = AmericanOption { option_type: OptionType::Put, spot, strike: 140.0, time_to_expiry: 1.0, rate, dividend_yield: 0.0, dividends: Vec::new() };
+        assert!(put.price(vol, 500, TreeMethod::Binomial) > 40.0 * 1.0001);
+        let call = AmericanOption {
+            option_type: OptionType::Call,
+            strike: 80.0,
+            dividends: vec![Dividend { time: 0.5, amount: 5.0 }],
+            ..put.clone()
+        };
+        let european_call = EuropeanOption {
+            option_type: OptionType::Call,
+            model: PricingModel::Black76,
+            underlying: (spot - 5.0 * (-rate * 0.5f64).exp()) * rate.exp(),
+            strike: 80.0,
+            time_to_expiry: 1.0,
+            rate,
+            dividend_yield: 0.0,
+        };
+        assert!(call.price(vol, 500, TreeMethod::Binomial) > european_call.price(vol));
+
+        // The coarsest trees still have nodes for every greek
+        for method in [TreeMethod::Binomial, TreeMethod::Trinomial] {
+            for steps in 0..4 {
+                let greeks = put.greeks(vol, steps, method);
+                assert!([greeks.delta, greeks.gamma, greeks.theta, greeks.vega, greeks.rho].iter().all(|g| g.is_finite()), "{method:?} {steps}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_chain_implied_vols_recover_tree_prices() {
+        use crate::analysis::options::american::{self, AmericanOption, TreeMethod, DEFAULT_STEPS};
+        use crate::analysis::options::strategy::{OptionStrategy, StrategyKind, ValuationParams};
+        use crate::data::types::{OptionType, StockOption};
+
+        let quote_date = chrono::NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let (spot, rate, dividend_yield) = (100.0, 0.05, 0.01);
+        let dividends = vec![(quote_date + chrono::Days::new(20), 1.5)];
+        let smile = |strike: f64, days: u64| 0.2 + 0.5 * (strike / spot).ln().powi(2) + 0.0002 * days as f64;
+
+        // Quoted a tenth of a cent either side of the tree price at a known vol
+        let chain = [30, 90]
+            .into_iter()
+            .flat_map(|days| [85.0, 95.0, 100.0, 105.0, 115.0].map(|strike| (days, strike)))
+            .flat_map(|(days, strike)| {
+                [OptionType::Call, OptionType::Put].map(|option_type| {
+                    let mut option = StockOption {
+                        contract_id: format!("{days}{strike}{option_type:?}"),
+                        expiry_date: quote_date + chrono::Days::new(days),
+                        strike_price: strike,
+                        option_type,
+                        ..Default::default()
+                    };
+                    let tree = AmericanOption { dividend_yield, ..AmericanOption::from_quote(&option, quote_date, spot, rate, &dividends) };
+                    let price = tree.price(smile(strike, days), DEFAULT_STEPS, TreeMethod::Trinomial);
+                    option.bid = price - 0.001;
+                    option.ask = price + 0.001;
+                    option
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let vols = american::chain_implied_vols(&chain, quote_date, spot, rate, dividend_yield, &dividends, TreeMethod::Trinomial);
+        for (option, vol) in chain.iter().zip(&vols) {
+            let days = (option.expiry_date - quote_date).num_days() as u64;
+            let vol = vol.unwrap_or_else(|| panic!("{} has no implied vol", option.contract_id));
+            assert!((vol - smile(option.strike_price, days)).abs() < 1e-4, "{} vol {vol}", option.contract_id);
+        }
+
+        let unquoted = StockOption { bid: 0.0, ..chain[0].clone() };
+        assert_eq!(american::chain_implied_vols(&[unquoted], quote_date, spot, rate, dividend_yield, &dividends, TreeMethod::Trinomial), vec![None]);
+
+        // Strategy legs are valued at those vols, so a position is worth its cost when opened
+        let params = ValuationParams { quote_date, spot, rate, dividend_yield, dividends };
+        let expiry = quote_date + chrono::Days::new(30);
+        let condor = OptionStrategy::from_chain(StrategyKind::IronCondor, &chain, expiry, expiry, &[85.0, 95.0, 105.0, 115.0], 1.0, &params).unwrap();
+        for (leg, strike) in condor.legs.iter().zip([85.0, 95.0, 105.0, 115.0]) {
+            assert!((leg.option.implied_volatility - smile(strike, 30)).abs() < 1e-4);
+        }
+        assert!(condor.pnl(spot, quote_date, &params).abs() < 1.0, "{}", condor.pnl(spot, quote_date, &params));
+    }
+
+    #[test]
+    fn test_parity_bounds_with_dividends() {
+        use crate::analysis::options::arbitrage::{self, ExerciseStyle, ScanParams, ViolationKind};
+        use crate::data::types::{OptionType, StockOption};
+
+        let quote_date = chrono::NaiveDate::from_ymd_opt(2024, 1, 2).unwrap();
+        let expiry = quote_date + chrono::Days::new(90);
+        let params = ScanParams {
+            quote_date,
+            spot: 100.0,
+            rate: 0.05,
+            dividend_yield: 0.0,
+            dividends: vec![(quote_date + chrono::Days::new(30), 2.0)],
+            style: ExerciseStyle::American,
+            cost_per_contract: 0.0065,
+            cost_per_share: 0.005,
+        };
+        let t = 90.0 / 365.0;
+        let dividends = 2.0 * (-params.rate * 30.0 / 365.0).exp();
+
+        // Each strike's synthetic stock is quoted against one side of the band or, for European
+        // exercise, straddling the parity line
+        let chain = |bounds: &dyn Fn(f64) -> (f64, f64), offset: f64| {
+            [90.0, 100.0, 110.0]
+                .iter()
+                .enumerate()
+                .flat_map(|(i, &strike)| {
+                    let (upper, lower) = bounds(strike);
+                    let (put_bid, put_ask) = (4.0, 4.1);
+                    let (call_bid, call_ask) = match i {
+                        0 => (upper + put_ask + offset, upper + put_ask + offset + 0.1),
+                        1 => (lower + put_bid - 0.1 - offset, lower + put_bid - offset),
+                        _ => ((upper + lower) / 2.0 + 4.05 - 0.05, (upper + lower) / 2.0 + 4.05 + 0.05),
+                    };
+                    let option = |option_type, bid: f64, ask: f64| StockOption {
+                        contract_id: format!("{strike}{option_type:?}"),
+                        expiry_date: expiry,
+                        strike_price: strike,
+                        option_type,
+                        bid,
+                        ask,
+                        ..Default::default()
+                    };
+                    [option(OptionType::Call, call_bid, call_ask), option(OptionType::Put, put_bid, put_ask)]
+                })
+                .collect::<Vec<_>>()
+        };
+        let parity = |chain: &[StockOption], params: &ScanParams| {
+            arbitrage::scan_chain(chain, params).into_iter().filter(|v| v.kind == ViolationKind::PutCallParity).collect::<Vec<_>>()
+        };
+
+        // American: S - D - K <= C - P <= S - K e^(-rT), the dividend only lowers the floor
+        let american = |strike: f64| (params.spot - strike * (-params.rate * t).exp(), params.spot - dividends - strike);
+        assert!(parity(&chain(&american, 0.0), &params).is_empty());
+
+        let european_params = ScanParams { style: ExerciseStyle::European, ..params.clone() };
+        let european = |strike: f64| {
+            let line = params.spot - dividends - strike * (-params.rate * t).exp();
+            (line, line)
+        };
+        assert!(parity(&chain(&european, 0.0), &european_params).is_empty());
+
+        // A nickel past either side is caught, net of both option legs and the stock leg
+        let violations = parity(&chain(&american, 0.05), &params);
+        assert_eq!(violations.len(), 2);
+        for violation in &violations {
+            assert!((violation.edge - 0.05).abs() < 1e-9);
+            assert!((violation.edge_after_costs - (0.05 - 2.0 * 0.0065 - 0.005)).abs() < 1e-9);
+            assert!(violation.trade.contains("stock"));
+        }
+    }
+
+    #[test]
+    fn test_strategy_payoffs_at_expiry() {
+        use crate::analysis::options::black_scholes::{EuropeanOption, PricingModel};
+        use crate::analysis::options::strategy::{OptionStrategy, StrategyKind, ValuationParams, CONTRACT_MULTIPLIER};
+        use crate::data::types::{OptionType, StockOption};
+
+        let quote_date = chrono::NaiveDate::from_ymd_opt(2024, 1, 2).unwrap();
+        let expiry = quote_date + chrono::Days::new(30);
+        let params = ValuationParams { quote_date, spot: 100.0, rate: 0.04, dividend_yield: 0.0, dividends: Vec::new() };
+
+        let chain = [90.0, 95.0, 100.0, 105.0, 110.0]
+            .iter()
+            .flat_map(|&strike| {
+                [OptionType::Call, OptionType::Put].map(|option_type| {
+                    let intrinsic = match option_type {
+                        OptionType::Call => f64::max(100.0 - strike, 0.0),
+                        OptionType::Put => f64::max(strike - 100.0, 0.0),
+                    };
+                    StockOption {
+                        expiry_date: expiry,
+                        strike_price: strike,
+                        option_type,
+                        bid: intrinsic + 2.95 - 0.2 * f64::abs(strike - 100.0),
+                        ask: intrinsic + 3.05 - 0.2 * f64::abs(strike - 100.0),
+                        implied_volatility: 0.25,
+                        ..Default::default()
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        let build = |kind, strikes: &[f64]| OptionStrategy::from_chain(kind, &chain, expiry, expiry, strikes, 1.0, &params).unwrap();
+
+        // Long 95 call, short 105 call: a debit capped at the strike width
+        let vertical = build(StrategyKind::CallVertical, &[95.0, 105.0]);
+        let debit = vertical.cost();
+        assert!((debit - 5.0 * CONTRACT_MULTIPLIER).abs() < 1e-9);
+        for (spot, payoff) in [(80.0, 0.0), (95.0, 0.0), (100.0, 5.0), (105.0, 10.0), (130.0, 10.0)] {
+            assert!((vertical.payoff_at_expiry(spot, &params) - (payoff * CONTRACT_MULTIPLIER - debit)).abs() < 1e-9);
+        }
+        let breakevens = vertical.breakevens(70.0, 130.0, &params);
+        assert_eq!(breakevens.len(), 1);
+        assert!((breakevens[0] - (95.0 + debit / CONTRACT_MULTIPLIER)).abs() < 1e-9);
+        let (max_profit, max_loss) = vertical.max_profit_loss(300.0, &params);
+        assert!((max_profit - (10.0 * CONTRACT_MULTIPLIER - debit)).abs() < 1e-9 && (max_loss + debit).abs() < 1e-9);
+
+        // Short 95/105 strangle inside long 90/110 wings: keeps the credit between the short
+        // strikes and loses the wing width less the credit outside the long ones
+        let condor = build(StrategyKind::IronCondor, &[90.0, 95.0, 105.0, 110.0]);
+        let credit = -condor.cost();
+        assert!(credit > 0.0);
+        let (max_profit, max_loss) = condor.max_profit_loss(300.0, &params);
+        assert!((max_profit - credit).abs() < 1e-9);
+        assert!((max_loss - (credit - 5.0 * CONTRACT_MULTIPLIER)).abs() < 1e-9);
+        let breakevens = condor.breakevens(70.0, 130.0, &params);
+        assert_eq!(breakevens.len(), 2);
+        assert!((breakevens[0] - (95.0 - credit / CONTRACT_MULTIPLIER)).abs() < 1e-9);
+        assert!((breakevens[1] - (105.0 + credit / CONTRACT_MULTIPLIER)).abs() < 1e-9);
+
+        // Unquoted legs open at their American value, which for a deep put is never below
+        // exercising it right away, unlike the European price
+        let deep_put = StockOption { expiry_date: expiry, strike_price: 140.0, option_type: OptionType::Put, implied_volatility: 0.25, ..Default::default() };
+        let put = OptionStrategy { name: String::from("Put"), legs: Vec::new() }.with_leg(&deep_put, 1.0, &params);
+        let european = EuropeanOption {
+            option_type: OptionType::Put,
+            model: PricingModel::BlackScholes,
+            underlying: 100.0,
+            strike: 140.0,
+            time_to_expiry: 30.0 / 365.0,
+            rate: params.rate,
+            dividend_yield: 0.0,
+        };
+        assert!(put.legs[0].entry_price >= 40.0);
+        assert!(european.price(0.25) < 40.0);
+        assert!((put.greeks(&params).delta + CONTRACT_MULTIPLIER).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_event_moves_on_synthetic_chains() {
+        use crate::analysis::options::{black_scholes::EuropeanOption, events::{self, EarningsEvent, EventTiming}};
+        use crate::data::{series::PriceSeries, types::*};
+        use chrono_tz::America::New_York;
+
+        let date = |m, d| chrono::NaiveDate::from_ymd_opt(2024, m, d).unwrap();
+        let (rate, dividend_yield) = (0.03, 0.0);
+
+        let amc = "2024-04-25 AMC".parse::<EarningsEvent>().unwrap();
+        assert_eq!((amc.pre_date(), amc.post_date()), (date(4, 25), date(4, 26)));
+        let bmo = "2024-04-25 bmo".parse::<EarningsEvent>().unwrap();
+        assert_eq!((bmo.timing, bmo.pre_date(), bmo.post_date()), (EventTiming::BeforeOpen, date(4, 24), date(4, 25)));
+        assert_eq!("2024-04-25".parse::<EarningsEvent>().unwrap(), amc);
+        assert!("2024-04-25 midday".parse::<EarningsEvent>().is_err());
+
+        // Flat smiles quoted a cent either side of the Black-Scholes price at each expiry's vol
+        let chain = |quote_date, spot: f64, expiries: &[(chrono::NaiveDate, f64)]| OptionChain {
+            data: expiries
+                .iter()
+                .flat_map(|&(expiry, vol)| (0..=16).map(move |i| (expiry, vol, (spot * 0.8 / 2.5).round() * 2.5 + 2.5 * i as f64)))
+                .flat_map(|(expiry, vol, strike)| {
+                    [OptionType::Call, OptionType::Put].map(|option_type| {
+                        let mut option = StockOption {
+                            date: Some(quote_date),
+                            expiry_date: expiry,
+                            strike_price: strike,
+                            option_type,
+                            implied_volatility: vol,
+                            ..Default::default()
+                        };
+                        let price = EuropeanOption::from_quote(&option, quote_date, spot, rate, dividend_yield).price(vol);
+                        option.bid = price - 0.01;
+                        option.ask = price + 0.01;
+                        option
+                    })
+                })
+                .collect(),
+        };
+
+        // The weekly expiring the day after the release doesn't outlive the post-event session,
+        // so the May 3rd expiry prices the event and loses most of its vol once it's out
+        let pre = chain(date(4, 25), 100.0, &[(date(4, 26), 1.2), (date(5, 3), 0.6), (date(5, 17), 0.4)]);
+        let post = chain(date(4, 26), 92.0, &[(date(5, 3), 0.25), (date(5, 17), 0.3)]);
+        let bars = [(date(4, 23), 101.0), (date(4, 24), 99.0), (date(4, 25), 100.0), (date(4, 26), 92.0), (date(4, 29), 93.0)]
+            .map(|(day, close)| TickerDataframe {
+                t: day.and_hms_opt(0, 0, 0).unwrap().and_local_timezone(New_York).unwrap().to_utc(),
+                close,
+                session: MarketSession::FullDay,
+                ..Default::default()
+            });
+        let series = PriceSeries::from(bars.as_slice());
+
+        let moves = events::expected_moves(&pre.data, date(4, 25), 100.0);
+        assert_eq!(moves.iter().map(|m| m.expiry).collect::<Vec<_>>(), vec![date(4, 26), date(5, 3), date(5, 17)]);
+        for straddle in &moves {
+            // Call and put mids are closest at the strike nearest the forward
+            assert_eq!(straddle.strike, 100.0);
+            let leg = |option_type| {
+                let option = pre.data.iter().find(|o| o.expiry_date == straddle.expiry && o.strike_price == 100.0 && o.option_type == option_type).unwrap();
+                (option.bid + option.ask) / 2.0
+            };
+            let straddle_price = leg(OptionType::Call) + leg(OptionType::Put);
+            assert!((straddle.straddle - straddle_price).abs() < 1e-12);
+            assert!((straddle.move_fraction - straddle_price / 100.0).abs() < 1e-12);
+
+            // An at-the-money straddle is worth about sqrt(2 / pi) standard deviations of the move
+            let vol = pre.data.iter().find(|o| o.expiry_date == straddle.expiry).unwrap().implied_volatility;
+            let sd = vol * ((straddle.expiry - date(4, 25)).num_days() as f64 / 365.0).sqrt();
+            assert!((straddle.move_fraction / sd - (2.0 / std::f64::consts::PI).sqrt()).abs() < 0.02, "{}", straddle.expiry);
+            assert_eq!(straddle.implied_volatility, vol);
+        }
+
+        let event = events::analyze_event(amc, &pre, &post, series.view(), rate, dividend_yield).unwrap();
+        assert_eq!((event.pre_date, event.post_date, event.expiry), (date(4, 25), date(4, 26), date(5, 3)));
+        assert_eq!((event.pre_spot, event.post_spot), (100.0, 92.0));
+        assert_eq!(event.expected_move, moves[1].move_fraction);
+        assert!((event.realized_move + 0.08).abs() < 1e-12);
+        assert!((event.move_ratio() - 0.08 / moves[1].move_fraction).abs() < 1e-12);
+        assert!((event.pre_iv - 0.6).abs() < 1e-3, "pre iv {}", event.pre_iv);
+        assert!((event.post_iv - 0.25).abs() < 1e-3, "post iv {}", event.post_iv);
+        assert!((event.iv_crush() - 0.35).abs() < 2e-3);
+        assert!((event.relative_iv_crush() - 0.35 / 0.6).abs() < 5e-3);
+
+        // Too thin to fit a surface, the post-event IV comes from the straddle legs' own quotes
+        let thin = OptionChain {
+            data: post.data.iter().filter(|o| o.expiry_date == date(5, 3) && o.strike_price == 92.5).cloned().collect(),
+        };
+        let thin_event = events::analyze_event(amc, &pre, &thin, series.view(), rate, dividend_yield).unwrap();
+        assert_eq!(thin_event.post_iv, 0.25);
+
+        // No quote date on the chain, or no bar after the pre-event session
+        let undated = OptionChain { data: post.data.iter().map(|o| StockOption { date: None, ..o.clone() }).collect() };
+        assert!(events::analyze_event(amc, &pre, &undated, series.view(), rate, dividend_yield).is_none());
+        assert!(events::analyze_event(amc, &pre, &post, series.view().slice(..3), rate, dividend_yield).is_none());
+
+        let summary = events::summarize(&[event.clone(), thin_event]);
+        assert_eq!(summary.events, 2);
+        assert!((summary.mean_realized_move - 0.08).abs() < 1e-12);
+        assert_eq!(summary.exceeded, if event.move_ratio() > 1.0 { 1.0 } else { 0.0 });
+    }
+
+    #[test]
+    fn test_vol_spread_source_matches_series() {
+        use crate::analysis::features::pipeline::{DailyInputs, FeaturePipeline, FeatureSource, FeatureSpec};
+        use crate::analysis::normalization::rolling_zscore_on_values;
+        use crate::analysis::vol_spread::{self, VolSpreadParams, VolSpreadStat};
+        use crate::analysis::volatility::{self, RealizedVolEstimator};
+        use crate::data::series::PriceSeries;
+        use chrono::Datelike;
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        // Three years of daily closes with a VIX-like implied vol over every calendar day
+        let mut rng = StdRng::seed_from_u64(43);
+        let start = chrono::DateTime::parse_from_rfc3339("2021-01-04T21:00:00Z").unwrap().to_utc();
+        let mut close = 100.0;
+        let frames = (0..1_100i64)
+            .map(|day| start + chrono::Duration::days(day))
+            .filter(|t| t.weekday().number_from_monday() <= 5)
+            .map(|t| {
+                close *= 1.0 + rng.gen_range(-0.006..0.006);
+                TickerDataframe { t, open: close, high: close * 1.005, close, low: close * 0.995, vol: 1_000, vol_weighted: close, session: MarketSession::Regular }
+            })
+            .collect::<Vec<_>>();
+        let series = PriceSeries::from(frames.as_slice());
+        let inputs = DailyInputs {
+            implied: frames.iter().map(|frame| (frame.t.date_naive(), rng.gen_range(0.15..0.2))).collect(),
+            ..Default::default()
+        };
+
+        let params = VolSpreadParams { garch_fit_bars: 250, ..Default::default() };
+        let stats = [VolSpreadStat::Implied, VolSpreadStat::ExpectedPremium, VolSpreadStat::GarchSpread, VolSpreadStat::PremiumZScore];
+        let pipeline = FeaturePipeline::new(
+            stats.iter().map(|&stat| FeatureSpec::new(format!("{stat:?}"), FeatureSource::VolSpread { stat, params }, 0)).collect(),
+        );
+
+        let points = vol_spread::vol_spread_on_series(series.view(), &inputs.implied, &inputs.long_implied, params).unwrap();
+        let columns = pipeline.columns_with(series.view(), &inputs).unwrap();
+        for (stat, column) in stats.iter().zip(&columns) {
+            for (point, &value) in points.iter().zip(column) {
+                assert!(value.to_bits() == stat.of(point).to_bits(), "{stat:?}");
+            }
+            assert!(column.iter().any(|value| !value.is_nan()), "{stat:?} never defined");
+        }
+
+        assert!(pipeline.columns(series.view()).is_err());
+
+        // Rolled windows match realized vol recomputed over each span of bars
+        let ppy = volatility::periods_per_year(series.view());
+        for estimator in [RealizedVolEstimator::CloseToClose, RealizedVolEstimator::Parkinson, RealizedVolEstimator::YangZhang] {
+            let params = VolSpreadParams { estimator, garch_fit_bars: usize::MAX, ..params };
+            let points = vol_spread::vol_spread_on_series(series.view(), &inputs.implied, &[], params).unwrap();
+            let span = params.horizon_days * 86_400;
+            for (i, point) in points.iter().enumerate() {
+                let t = series.t[i];
+                let trailing_start = series.t.iter().position(|&bar| bar > t - span).unwrap();
+                let forward_end = series.t.iter().rposition(|&bar| bar <= t + span).unwrap();
+                let realized = volatility::realized_volatility(series.view().slice(trailing_start.saturating_sub(1)..=i), estimator, ppy);
+                let forward = volatility::realized_volatility(series.view().slice(i..=forward_end), estimator, ppy);
+
+                assert!((point.realized - realized).abs() < 1e-9 || (point.realized.is_nan() && realized.is_nan()), "{estimator:?} at bar {i}");
+                if series.t[series.len() - 1] >= t + span {
+                    assert!((point.forward_realized - forward).abs() < 1e-9 || (point.forward_realized.is_nan() && forward.is_nan()), "{estimator:?} at bar {i}");
+                } else {
+                    assert!(point.forward_realized.is_nan());
+                }
+            }
+        }
+
+        // As a feature set option the spread columns follow the direction columns on every row
+        let inputs = DailyInputs { long_implied: inputs.implied.iter().map(|&(date, iv)| (date, iv + 0.02)).collect(), ..inputs };
+        let data = TickerData { symbol: "SPREAD".to_string(), price_data: series.clone(), technicals: Vec::new() };
+        let direction_columns = featureset::direction_pipeline().column_names();
+        let plain = featureset::calculate_featuresets(std::slice::from_ref(&data), &Default::default(), &DailyInputs::default()).remove(0).unwrap();
+        assert_eq!(plain.features.names, direction_columns);
+
+        let options = featureset::FeatureOptions { vol_spread: Some(params), ..Default::default() };
+        let spread = featureset::calculate_featuresets(std::slice::from_ref(&data), &options, &inputs).remove(0).unwrap();
+        let names = &spread.features.names;
+        assert_eq!(names[..direction_columns.len()], direction_columns[..]);
+        assert_eq!(names.last().map(String::as_str), Some("variance_premium_zscore"));
+        assert!(!spread.features.bars.is_empty());
+        for (row, &bar) in spread.features.bars.iter().enumerate() {
+            assert_eq!(spread.features.values[[row, names.len() - 1]].to_bits(), points[bar].premium_zscore.to_bits());
+        }
+
+        for horizon_days in [0, -30] {
+            let params = VolSpreadParams { horizon_days, ..params };
+            assert!(vol_spread::vol_spread_on_series(series.view(), &inputs.implied, &[], params).is_err());
+        }
+
+        // Running moments stay exact next to a two-pass z-score over each window
+        let values = (0..5_000).map(|i| if i % 97 == 0 { f64::NAN } else { rng.gen_range(1e6..1e6 + 1.0) }).collect::<Vec<_>>();
+        let window = 50;
+        for (i, zscore) in rolling_zscore_on_values(&values, window).into_iter().enumerate().skip(window) {
+            let trailing = values[i + 1 - window..=i].iter().copied().filter(|v| !v.is_nan()).collect::<Vec<_>>();
+            if trailing.len() < window {
+                assert!(zscore.is_nan());
+                continue;
+            }
+            let mean = trailing.iter().sum::<f64>() / trailing.len() as f64;
+            let std = (trailing.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (trailing.len() - 1) as f64).sqrt();
+            assert!((zscore - (values[i] - mean) / std).abs() < 1e-6, "bar {i}");
+        }
+    }
+}
@@ -1,8 +1,12 @@
 
 use chrono::NaiveDate;
+use egui_plot::PlotPoint;
+use ndarray::Array2;
 use serde::{Deserialize, Serialize};
 
-use crate::{data::{self, types::TickerData}, ui::widgets::{self, data_controller_widget}};
+use surrealdb::RecordId;
+
+use crate::{analysis::{options::{arbitrage::{ArbitrageViolation, ExerciseStyle}, events::EventMove, black_scholes::{Discrepancy, Greeks}, strategy::{OptionStrategy, StrategyKind}}, strategies::gradient_trees::Evaluation, vol_spread::{GarchFitReport, VolSpreadPoint}}, data::{self, macro_series::MacroSeries, types::{EtfHolding, StockOption, TickerData, Trade}}, ui::widgets::{self, data_controller_widget}};
 
 #[derive(Debug, Clone)]
 pub struct DataPageState {
@@ -10,14 +14,237 @@ pub struct DataPageState {
     pub from_date: NaiveDate,
     pub to_date: NaiveDate,
     pub ticker_data: Option<TickerData>,
+    /// Registry keys of the indicators drawn over the chart
+    pub overlays: Vec<String>,
+    pub chart: DataChart,
 }
 
+impl DataPageState {
+    /// Replaces the loaded data and rebuilds the chart from it.
+    pub fn set_ticker_data(&mut self, data: Option<TickerData>) {
+        self.ticker_data = data;
+        self.refresh_chart();
+    }
+
+    /// Rebuilds the chart after the data or the overlay selection changed.
+    pub fn refresh_chart(&mut self) {
+        self.chart = widgets::data_chart(self.ticker_data.as_ref(), &self.overlays);
+    }
+}
+
+/// Lines of the data viewer's chart, kept between frames so the indicators are only computed
+/// when something changes.
 #[derive(Debug, Clone, Default)]
+pub struct DataChart {
+    pub price: Vec<PlotPoint>,
+    /// Lines drawn over the price
+    pub price_overlays: Vec<(String, Vec<PlotPoint>)>,
+    /// Lines drawn in the indicator plot below the price
+    pub indicator_lines: Vec<(String, Vec<PlotPoint>)>,
+}
+
+#[derive(Debug, Clone)]
 pub struct TrainTestPageState {
     pub use_stored_data: bool,
     pub train_time_series_corr: bool,
     pub get_sma: bool,
+    /// Share of each symbol's latest rows held out for testing
+    pub test_fraction: f64,
+    /// Add the implied vs realized volatility columns, with VIX as the implied vol
+    pub vol_spread_features: bool,
+    /// Macro series added as feature columns
+    pub macro_features: Vec<MacroSeries>,
+    /// Symbol whose stored intraday history the overnight gap model is trained on
+    pub gap_symbol: String,
+    /// Smallest overnight gap, as a fraction, labeled up or down
+    pub gap_threshold: f64,
+    pub training: bool,
+    pub evaluations: Vec<(String, Result<Evaluation, String>)>,
+}
+
+impl Default for TrainTestPageState {
+    fn default() -> Self {
+        Self {
+            use_stored_data: false,
+            train_time_series_corr: false,
+            get_sma: false,
+            test_fraction: 0.2,
+            vol_spread_features: false,
+            macro_features: Vec::new(),
+            gap_symbol: String::from("SPY"),
+            gap_threshold: 0.002,
+            training: false,
+            evaluations: Vec::new(),
+        }
+    }
+}
 
+#[derive(Debug, Clone)]
+pub struct ArbitragePageState {
+    pub symbol: String,
+    pub quote_date: NaiveDate,
+    pub spot: f64,
+    pub rate: f64,
+    pub dividend_yield: f64,
+    pub style: ExerciseStyle,
+    pub cost_per_contract: f64,
+    pub cost_per_share: f64,
+    /// Hide violations that costs would eat
+    pub only_profitable: bool,
+    pub scanning: bool,
+    pub violations: Vec<ArbitrageViolation>,
+    /// Vendor IVs and greeks of the scanned chain that disagree with ours
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+impl Default for ArbitragePageState {
+    fn default() -> Self {
+        Self {
+            symbol: String::from("SPY"),
+            quote_date: chrono::Utc::now().date_naive(),
+            spot: 0.0,
+            rate: 0.04,
+            dividend_yield: 0.0,
+            style: ExerciseStyle::American,
+            cost_per_contract: 0.0065,
+            cost_per_share: 0.005,
+            only_profitable: true,
+            scanning: false,
+            violations: Vec::new(),
+            discrepancies: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StrategyPageState {
+    pub symbol: String,
+    pub quote_date: NaiveDate,
+    pub spot: f64,
+    pub rate: f64,
+    pub dividend_yield: f64,
+    pub kind: StrategyKind,
+    /// Contracts per leg, negative to sell the structure
+    pub quantity: f64,
+    pub expiry: Option<NaiveDate>,
+    /// Long leg of a calendar
+    pub far_expiry: Option<NaiveDate>,
+    /// Only the first `kind.strike_count()` are used
+    pub strikes: [f64; 4],
+    pub loading: bool,
+    pub chain: Vec<StockOption>,
+    pub analysis: Option<StrategyAnalysis>,
+}
+
+/// Everything the strategy page's valuations depend on besides the chain
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrategyInputs {
+    pub kind: StrategyKind,
+    pub quantity: f64,
+    pub expiry: NaiveDate,
+    pub far_expiry: NaiveDate,
+    pub strikes: [f64; 4],
+    pub quote_date: NaiveDate,
+    pub spot: f64,
+    pub rate: f64,
+    pub dividend_yield: f64,
+}
+
+/// Valuations of the strategy page's position, kept between frames since each one rolls back an
+/// American tree per leg. Rebuilt when its inputs or the chain change.
+#[derive(Debug, Clone)]
+pub struct StrategyAnalysis {
+    pub inputs: StrategyInputs,
+    /// None when a strike isn't quoted in the chain
+    pub strategy: Option<OptionStrategy>,
+    pub max_profit: f64,
+    pub max_loss: f64,
+    pub breakevens: Vec<f64>,
+    pub greeks: Greeks,
+    pub spots: Vec<f64>,
+    /// Dates of the rows of `surface`, from the quote date to the first expiry
+    pub dates: Vec<NaiveDate>,
+    /// P&L indexed by `[date, spot]`
+    pub surface: Array2<f64>,
+}
+
+impl Default for StrategyPageState {
+    fn default() -> Self {
+        Self {
+            symbol: String::from("SPY"),
+            quote_date: chrono::Utc::now().date_naive(),
+            spot: 0.0,
+            rate: 0.04,
+            dividend_yield: 0.0,
+            kind: StrategyKind::CallVertical,
+            quantity: 1.0,
+            expiry: None,
+            far_expiry: None,
+            strikes: [0.0; 4],
+            loading: false,
+            chain: Vec::new(),
+            analysis: None,
+        }
+    }
+}
+
+/// Where the vol spread page takes implied volatility from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImpliedSource {
+    /// ATM IV of option chain snapshots stored in the DB
+    StoredChains,
+    Vix,
+}
+
+#[derive(Debug, Clone)]
+pub struct VolSpreadPageState {
+    pub symbol: String,
+    pub from_date: NaiveDate,
+    pub to_date: NaiveDate,
+    pub source: ImpliedSource,
+    pub horizon_days: i64,
+    pub loading: bool,
+    pub points: Vec<VolSpreadPoint>,
+    pub garch: Option<GarchFitReport>,
+}
+
+impl Default for VolSpreadPageState {
+    fn default() -> Self {
+        Self {
+            symbol: String::from("SPY"),
+            from_date: NaiveDate::from_ymd_opt(2020, 01, 01).unwrap(),
+            to_date: chrono::Utc::now().date_naive(),
+            source: ImpliedSource::Vix,
+            horizon_days: 30,
+            loading: false,
+            points: Vec::new(),
+            garch: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EventVolPageState {
+    pub symbol: String,
+    /// Comma separated event dates, each optionally followed by `bmo` or `amc`
+    pub events: String,
+    pub rate: f64,
+    pub dividend_yield: f64,
+    pub loading: bool,
+    pub moves: Vec<EventMove>,
+}
+
+impl Default for EventVolPageState {
+    fn default() -> Self {
+        Self {
+            symbol: String::from("AAPL"),
+            events: String::new(),
+            rate: 0.04,
+            dividend_yield: 0.0,
+            loading: false,
+            moves: Vec::new(),
+        }
+    }
 }
 
 impl Default for DataPageState {
@@ -27,6 +254,8 @@ impl Default for DataPageState {
             from_date: NaiveDate::from_ymd_opt(2016, 01, 01).unwrap(),
             to_date: NaiveDate::from_ymd_opt(2016, 01, 01).unwrap(),
             ticker_data: None,
+            overlays: Vec::new(),
+            chart: DataChart::default(),
         }
     }
 }
@@ -44,6 +273,10 @@ pub enum AppPage {
     Home,
     DataViewer(DataPageState),
     TrainTest(TrainTestPageState),
+    Arbitrage(ArbitragePageState),
+    Strategy(StrategyPageState),
+    VolSpread(VolSpreadPageState),
+    EventVol(EventVolPageState),
     TradingTerminal,
 }
 
@@ -58,7 +291,12 @@ pub struct App {
     pub queued_operation: QueuedOperation,
 
     pub page: AppPage,
-    
+
+    /// Trades kept in sync with the DB by the live query listener
+    pub trades: Vec<(RecordId, Trade)>,
+
+    /// ETF holdings kept in sync with the DB by the live query listener
+    pub holdings: Vec<(RecordId, EtfHolding)>,
 
     // pub database: &'static LazyLock<Surreal<Client>>
 }
@@ -70,11 +308,21 @@ impl Default for App {
             messages: Vec::new(),
             queued_operation: QueuedOperation::NOOP,
             page: AppPage::Home,
+            trades: Vec::new(),
+            holdings: Vec::new(),
             // database: &crate::DB
         }
     }
 }
 
+impl App {
+    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
+        widgets::spawn_live_listener(cc.egui_ctx.clone());
+
+        Self::default()
+    }
+}
+
 impl eframe::App for App {
     fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
         egui::CentralPanel::default().show(ctx, |ui| {
@@ -84,13 +332,15 @@ impl eframe::App for App {
             };
             ctx.set_visuals(visuals);
             
+            widgets::apply_live_events(self);
             widgets::navbar(self, ui);
 
             let page = std::mem::replace(&mut self.page, AppPage::Home);
             
             match page {
                 AppPage::Home => {
-                    ui.heading(format!("Total Value: ${}", 500));
+                    let total_value: f64 = self.trades.iter().map(|(_, trade)| trade.market_value).sum();
+                    ui.heading(format!("Total Value: ${:.2}", total_value));
 
                     ui.add_space(2.5);
             
@@ -105,6 +355,22 @@ impl eframe::App for App {
                     widgets::train_test_widget(self, ui, &mut data);
                     self.page = AppPage::TrainTest(data);
                 }
+                AppPage::Arbitrage(mut data) => {
+                    widgets::arbitrage_scanner_widget(ui, &mut data);
+                    self.page = AppPage::Arbitrage(data);
+                }
+                AppPage::Strategy(mut data) => {
+                    widgets::strategy_widget(ui, &mut data);
+                    self.page = AppPage::Strategy(data);
+                }
+                AppPage::VolSpread(mut data) => {
+                    widgets::vol_spread_widget(ui, &mut data);
+                    self.page = AppPage::VolSpread(data);
+                }
+                AppPage::EventVol(mut data) => {
+                    widgets::event_vol_widget(ui, &mut data);
+                    self.page = AppPage::EventVol(data);
+                }
                 AppPage::TradingTerminal => {
                     ui.label("Trading Terminal page");
                     self.page = AppPage::TradingTerminal;
@@ -3,9 +3,11 @@ use std::{str::FromStr, sync::Arc};
 
 use egui::{mutex::Mutex, Ui};
 use egui_extras::{Column, TableBuilder};
-use egui_plot::{Line, Plot, PlotPoints};
+use egui_plot::{Legend, Line, Plot, PlotPoint, PlotPoints};
+use futures::StreamExt;
+use surrealdb::RecordId;
 use tokio::sync::mpsc;
-use crate::{data::{self, db_service, types::{Etf, TickerData, TickerDatatype}}, ui::renderer::{AppPage, DataPageState, TrainTestPageState}};
+use crate::{analysis::{features::{featureset::FeatureOptions, pipeline::DailyInputs}, indicator::IndicatorRegistry, options::{arbitrage::{self, ArbitrageViolation, ExerciseStyle, ScanParams}, black_scholes::{self, Discrepancy, DiscrepancyTolerance}, events::{self, EarningsEvent, EventMove}, strategy::{OptionStrategy, StrategyKind, ValuationParams}}, strategies::gradient_trees::{self, Evaluation}, surface::linspace, vol_spread::{self, GarchFitReport, VolSpreadParams, VolSpreadPoint}}, data::{self, db_service::{self, DbEvent}, macro_series::{self, MacroSeries}, types::{Etf, LiveAction, StockOption, TickerData, TickerDatatype}}, ui::renderer::{AppPage, ArbitragePageState, DataChart, DataPageState, EventVolPageState, ImpliedSource, StrategyAnalysis, StrategyInputs, StrategyPageState, TrainTestPageState, VolSpreadPageState}};
 
 use super::renderer::App;
 
@@ -14,6 +16,107 @@ lazy_static::lazy_static! {
         let (tx, rx) = mpsc::unbounded_channel::<TickerData>();
         (tx, Arc::new(Mutex::new(rx)))
     };
+
+    static ref INDICATORS: IndicatorRegistry = IndicatorRegistry::with_defaults();
+
+    static ref ARBITRAGE_CHANNEL: (mpsc::UnboundedSender<(Vec<ArbitrageViolation>, Vec<Discrepancy>)>, Arc<Mutex<mpsc::UnboundedReceiver<(Vec<ArbitrageViolation>, Vec<Discrepancy>)>>>) = {
+        let (tx, rx) = mpsc::unbounded_channel::<(Vec<ArbitrageViolation>, Vec<Discrepancy>)>();
+        (tx, Arc::new(Mutex::new(rx)))
+    };
+
+    static ref STRATEGY_CHANNEL: (mpsc::UnboundedSender<Vec<StockOption>>, Arc<Mutex<mpsc::UnboundedReceiver<Vec<StockOption>>>>) = {
+        let (tx, rx) = mpsc::unbounded_channel::<Vec<StockOption>>();
+        (tx, Arc::new(Mutex::new(rx)))
+    };
+
+    static ref VOL_SPREAD_CHANNEL: (mpsc::UnboundedSender<(Vec<VolSpreadPoint>, Option<GarchFitReport>)>, Arc<Mutex<mpsc::UnboundedReceiver<(Vec<VolSpreadPoint>, Option<GarchFitReport>)>>>) = {
+        let (tx, rx) = mpsc::unbounded_channel::<(Vec<VolSpreadPoint>, Option<GarchFitReport>)>();
+        (tx, Arc::new(Mutex::new(rx)))
+    };
+
+    static ref EVENT_VOL_CHANNEL: (mpsc::UnboundedSender<Vec<EventMove>>, Arc<Mutex<mpsc::UnboundedReceiver<Vec<EventMove>>>>) = {
+        let (tx, rx) = mpsc::unbounded_channel::<Vec<EventMove>>();
+        (tx, Arc::new(Mutex::new(rx)))
+    };
+
+    static ref TRAIN_TEST_CHANNEL: (mpsc::UnboundedSender<Vec<(String, Result<Evaluation, String>)>>, Arc<Mutex<mpsc::UnboundedReceiver<Vec<(String, Result<Evaluation, String>)>>>>) = {
+        let (tx, rx) = mpsc::unbounded_channel::<Vec<(String, Result<Evaluation, String>)>>();
+        (tx, Arc::new(Mutex::new(rx)))
+    };
+
+    static ref LIVE_EVENT_CHANNEL: (mpsc::UnboundedSender<DbEvent>, Arc<Mutex<mpsc::UnboundedReceiver<DbEvent>>>) = {
+        let (tx, rx) = mpsc::unbounded_channel::<DbEvent>();
+        (tx, Arc::new(Mutex::new(rx)))
+    };
+}
+
+/// Loads the current trades and holdings, then forwards DB live query notifications to the UI
+/// and wakes it up so open views refresh without user input.
+pub fn spawn_live_listener(ctx: egui::Context) {
+    tokio::task::spawn(async move {
+        match db_service::get_trades().await {
+            Ok(trades) => trades.into_iter().for_each(|(id, trade)| {
+                let _ = LIVE_EVENT_CHANNEL.0.send(DbEvent::Trade(LiveAction::Create, id, trade));
+            }),
+            Err(e) => eprintln!("[ERROR] Could not load trades: {e}"),
+        }
+        match db_service::get_holdings().await {
+            Ok(holdings) => holdings.into_iter().for_each(|(id, holding)| {
+                let _ = LIVE_EVENT_CHANNEL.0.send(DbEvent::Holding(LiveAction::Create, id, holding));
+            }),
+            Err(e) => eprintln!("[ERROR] Could not load holdings: {e}"),
+        }
+        ctx.request_repaint();
+
+        let mut events = match db_service::live_events().await {
+            Ok(events) => events,
+            Err(e) => {
+                eprintln!("[ERROR] Could not start live queries: {e}");
+                return;
+            }
+        };
+
+        while let Some(event) = events.next().await {
+            if LIVE_EVENT_CHANNEL.0.send(event).is_err() {
+                break;
+            }
+            ctx.request_repaint();
+        }
+    });
+}
+
+pub fn apply_live_events(app: &mut App) {
+    let mut rx = LIVE_EVENT_CHANNEL.1.as_ref().lock();
+
+    while let Ok(event) = rx.try_recv() {
+        match event {
+            DbEvent::PriceHistory(action, data) => {
+                if let AppPage::DataViewer(state) = &mut app.page {
+                    if data.symbol == app.input {
+                        state.set_ticker_data(match action {
+                            LiveAction::Delete => None,
+                            _ => Some(data),
+                        });
+                    }
+                }
+            }
+            DbEvent::Holding(action, id, holding) => apply_live_record(&mut app.holdings, action, id, holding),
+            DbEvent::Trade(action, id, trade) => apply_live_record(&mut app.trades, action, id, trade),
+        }
+    }
+}
+
+fn apply_live_record<T>(records: &mut Vec<(RecordId, T)>, action: LiveAction, id: RecordId, record: T) {
+    let existing = records.iter().position(|(el_id, _)| *el_id == id);
+
+    match (action, existing) {
+        (LiveAction::Delete, Some(idx)) => {
+            records.remove(idx);
+        }
+        (LiveAction::Delete, None) => {}
+        (_, Some(idx)) => records[idx] = (id, record),
+        (_, None) => records.push((id, record)),
+    }
 }
 
 
@@ -30,6 +133,18 @@ pub fn navbar(app: &mut App, ui: &mut Ui) {
         if ui.button("Train/Test").clicked() {
             app.page = AppPage::TrainTest(TrainTestPageState::default());
         }
+        if ui.button("Arbitrage Scanner").clicked() {
+            app.page = AppPage::Arbitrage(ArbitragePageState::default());
+        }
+        if ui.button("Strategy Analyzer").clicked() {
+            app.page = AppPage::Strategy(StrategyPageState::default());
+        }
+        if ui.button("Vol Spread").clicked() {
+            app.page = AppPage::VolSpread(VolSpreadPageState::default());
+        }
+        if ui.button("Event Vol").clicked() {
+            app.page = AppPage::EventVol(EventVolPageState::default());
+        }
         if ui.button("Trading Terminal").clicked() {
             app.page = AppPage::TradingTerminal;
         }
@@ -55,48 +170,94 @@ pub fn portfolio_data_viewer(app: &mut App, ui: &mut Ui) {
                 }
             );
 
-            portfolio_holdings_table(app, ui);
+            ui.vertical(|ui| {
+                portfolio_holdings_table(app, ui);
+                ui.separator();
+                portfolio_trades_table(app, ui);
+            });
         }
     );
 }
 
 
-fn portfolio_holdings_table(app: &mut App, ui: &mut Ui) {
-    ui.vertical(|ui| {
-        ui.label("Portfolio Holdings");
-        ui.allocate_ui_with_layout(
-            ui.available_size() * egui::vec2(0.3, 1.0),
-            egui::Layout::top_down(egui::Align::Min),
-            |ui| {
-                egui::Grid::new("portfolio_table")
-                    .striped(true)
-                    .show(ui, |ui| {
-                        ui.heading("Symbol");
-                        ui.heading("QTY");
-                        ui.heading("Price");
-                        ui.heading("Weight");
+/// Weight of each ETF component, grouped by ETF with the largest weights first
+fn portfolio_holdings_table(app: &App, ui: &mut Ui) {
+    ui.label("ETF Holdings");
+    egui::ScrollArea::vertical()
+        .id_salt("portfolio_holdings_scroll")
+        .max_height(160.0)
+        .show(ui, |ui| {
+            egui::Grid::new("portfolio_holdings_table")
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.heading("ETF");
+                    ui.heading("Symbol");
+                    ui.heading("Weight");
+                    ui.end_row();
+
+                    if app.holdings.is_empty() {
+                        ui.label("No holdings recorded...");
+                        ui.end_row();
+                    }
+
+                    let mut holdings = app.holdings.iter().map(|(_, holding)| holding).collect::<Vec<_>>();
+                    holdings.sort_by(|a, b| a.holding_of.as_ref().cmp(b.holding_of.as_ref()).then(b.weight.total_cmp(&a.weight)));
+
+                    for holding in holdings {
+                        // Edges run from the ETF's ticker to the component's stock record
+                        let component = holding.out.to_string();
+                        let symbol = component.split_once(':').map_or(component.as_str(), |(_, key)| key);
+
+                        ui.label(holding.holding_of.as_ref());
+                        ui.label(symbol.trim_matches(['⟨', '⟩', '`']));
+                        ui.label(format!("{:.2}", holding.weight));
+                        ui.end_row();
+                    }
+                }
+            );
+        });
+}
+
+fn portfolio_trades_table(app: &App, ui: &mut Ui) {
+    ui.label("Portfolio Trades");
+    egui::ScrollArea::vertical()
+        .id_salt("portfolio_trades_scroll")
+        .max_height(160.0)
+        .show(ui, |ui| {
+            egui::Grid::new("portfolio_table")
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.heading("Symbol");
+                    ui.heading("QTY");
+                    ui.heading("Price");
+                    ui.heading("Weight");
+                    ui.end_row();
+
+                    if app.trades.is_empty() {
+                        ui.label("No trades recorded...");
                         ui.end_row();
+                    }
 
-                        // Example static data; replace with real data as needed
-                        let holdings = vec![
-                            ("AAPL", 10, 190.0),
-                            ("GOOG", 5, 2800.0),
-                            ("TSLA", 2, 700.0),
-                        ];
-
-                        for (symbol, shares, price) in holdings {
-                            let value = shares as f64 * price;
-                            ui.label(symbol);
-                            ui.label(shares.to_string());
-                            ui.label(format!("{:.2}", price));
-                            ui.label(format!("{:.2}", value));
-                            ui.end_row();
+                    let total_value: f64 = app.trades.iter().map(|(_, trade)| trade.market_value).sum();
+
+                    for (_, trade) in &app.trades {
+                        ui.label(&trade.symbol);
+                        ui.label(format!("{}", trade.num_shares));
+                        if trade.num_shares != 0.0 {
+                            ui.label(format!("{:.2}", trade.market_value / trade.num_shares));
+                        } else {
+                            ui.label("-");
+                        }
+                        if total_value != 0.0 {
+                            ui.label(format!("{:.2}", trade.market_value / total_value));
+                        } else {
+                            ui.label("-");
                         }
+                        ui.end_row();
                     }
-                );
-            }
-        );
-    });
+                }
+            );
+        });
 }
 
 
@@ -111,7 +272,7 @@ pub fn data_controller_widget(app: &mut App, state: &mut DataPageState, ui: &mut
         Ok(d) => {
             // println!("got data");
             // println!("{:#?}", d);
-            state.ticker_data = Some(d);
+            state.set_ticker_data(Some(d));
         },
     }
 
@@ -152,6 +313,23 @@ pub fn data_controller_widget(app: &mut App, state: &mut DataPageState, ui: &mut
         write_data_clicked = ui.button("Send Data to DB").clicked();
     });
 
+    ui.add_space(5.0);
+
+    ui.horizontal(|ui| {
+        ui.label("Overlays: ");
+        for (key, _) in INDICATORS.iter() {
+            let mut enabled = state.overlays.iter().any(|el| el == key);
+            if ui.checkbox(&mut enabled, key).changed() {
+                if enabled {
+                    state.overlays.push(key.to_string());
+                } else {
+                    state.overlays.retain(|el| el != key);
+                }
+                state.refresh_chart();
+            }
+        }
+    });
+
     if state.symbol_is_etf {
         ui.add_space(5.0);
         if ui.button("Recursively Populate Current ETF Holdings").clicked() {
@@ -224,7 +402,7 @@ fn data_table_widget(app: &mut App, state: &mut DataPageState, ui: &mut Ui) {
                     }
                     TableBuilder::new(ui)
                         .column(Column::auto().at_most(table_width / 3.0).clip(true).resizable(true))
-                        .columns(Column::remainder().clip(true), 5)
+                        .columns(Column::remainder().clip(true), 6)
                         .striped(true)
                         .header(20.0, |mut header| {
                             header.col(|ui| {
@@ -244,14 +422,17 @@ fn data_table_widget(app: &mut App, state: &mut DataPageState, ui: &mut Ui) {
                             });
                             header.col(|ui| {
                                 ui.heading("Volume");
+                            });
+                            header.col(|ui| {
+                                ui.heading("Session");
                             });                            
                         })
                         .body(|body| {
                             if let Some(ticker_data) = &state.ticker_data {
                                 body.rows(20.0, ticker_data.price_data.len(), |mut row| {
-                                    let val = ticker_data.price_data.get(row.index()).unwrap();
+                                    let val = ticker_data.price_data.frame(row.index());
                                     row.col(|ui| {
-                                        ui.label(format!("{}", val.t));
+                                        ui.label(format!("{}", val.local_time().format("%Y-%m-%d %H:%M %Z")));
                                     });
                                     row.col(|ui| {
                                         ui.label(format!("{:.2}", val.open));
@@ -268,6 +449,9 @@ fn data_table_widget(app: &mut App, state: &mut DataPageState, ui: &mut Ui) {
                                     row.col(|ui| {
                                         ui.label(format!("{:.2}", val.vol));
                                     });
+                                    row.col(|ui| {
+                                        ui.label(format!("{:?}", val.session));
+                                    });
                                 });
                             }
                         }
@@ -279,31 +463,78 @@ fn data_table_widget(app: &mut App, state: &mut DataPageState, ui: &mut Ui) {
 
         // Right: Plot (75% width)
         ui.vertical(|graph_ui| {
-            let mut datapoints = PlotPoints::new(vec![]);
-            let sma_points = PlotPoints::new(vec![]);
-
-            if let Some(ticker_data) = &state.ticker_data {
-                datapoints = PlotPoints::from_iter({
-                    ticker_data.price_data
-                        .iter()
-                        .enumerate()
-                        .map(|(date, data)| {
-                            [date as f64, data.close as f64]
-                        })
+            let chart = &state.chart;
+            let line = Line::new("ticker_price_over_time", PlotPoints::Borrowed(&chart.price));
+
+            let price_plot_height = if chart.indicator_lines.is_empty() {
+                graph_ui.available_height()
+            } else {
+                graph_ui.available_height() * 0.65
+            };
+
+            Plot::new("ticker_data_plot")
+                .height(price_plot_height)
+                .legend(Legend::default())
+                .show(graph_ui, |graph_inner_ui| {
+                    graph_inner_ui.line(line);
+                    for (name, points) in &chart.price_overlays {
+                        graph_inner_ui.line(Line::new(name.as_str(), PlotPoints::Borrowed(points)));
                     }
-                );
-            }
-            
-            let line = Line::new("ticker_price_over_time", datapoints);
+                });
 
-            Plot::new("ticker_data_plot").show(graph_ui, |graph_inner_ui| {
-                graph_inner_ui.line(line);
-            });
+            if !chart.indicator_lines.is_empty() {
+                Plot::new("ticker_indicator_plot")
+                    .legend(Legend::default())
+                    .show(graph_ui, |graph_inner_ui| {
+                        for (name, points) in &chart.indicator_lines {
+                            graph_inner_ui.line(Line::new(name.as_str(), PlotPoints::Borrowed(points)));
+                        }
+                    });
+            }
         });
     });
 }
 
+/// Price line of `data` and a line per output of each indicator in `overlays`, indexed by bar.
+pub fn data_chart(data: Option<&TickerData>, overlays: &[String]) -> DataChart {
+    let Some(data) = data else {
+        return DataChart::default();
+    };
+
+    let points = |values: &[f64]| {
+        values.iter()
+            .enumerate()
+            .filter(|(_, v)| !v.is_nan())
+            .map(|(idx, v)| PlotPoint::new(idx as f64, *v))
+            .collect::<Vec<_>>()
+    };
+
+    let mut chart = DataChart { price: points(&data.price_data.close), ..Default::default() };
+
+    let series = data.series();
+    for key in overlays {
+        let Some(indicator) = INDICATORS.get(key) else { continue };
+
+        let outputs = indicator.compute(series);
+        let columns = indicator.outputs().iter().zip(indicator.column_names());
+        for ((output, name), values) in columns.zip(outputs) {
+            if indicator.on_price_scale(output) {
+                chart.price_overlays.push((name, points(&values)));
+            } else {
+                chart.indicator_lines.push((name, points(&values)));
+            }
+        }
+    }
+
+    chart
+}
+
 pub fn train_test_widget(app: &mut App, ui: &mut Ui, state: &mut TrainTestPageState) {
+    if let Ok(evaluations) = TRAIN_TEST_CHANNEL.1.as_ref().lock().try_recv() {
+        state.evaluations = evaluations;
+        state.training = false;
+    }
+
     ui.heading("Train/Test Configuration");
 
     ui.horizontal(|ui| {
@@ -311,4 +542,887 @@ pub fn train_test_widget(app: &mut App, ui: &mut Ui, state: &mut TrainTestPageSt
         ui.checkbox(&mut state.train_time_series_corr, "Find Correlations");
         // ui.checkbox(checked, text)
     });
+
+    ui.add_space(5.0);
+
+    ui.horizontal(|ui| {
+        ui.label("Test Fraction: ");
+        ui.add(egui::DragValue::new(&mut state.test_fraction).speed(0.01).range(0.05..=0.5));
+
+        ui.checkbox(&mut state.vol_spread_features, "VIX Vol Spread Features");
+
+        ui.menu_button(format!("Macro Features ({})", state.macro_features.len()), |ui| {
+            for series in MacroSeries::ALL {
+                let mut selected = state.macro_features.contains(&series);
+                if ui.checkbox(&mut selected, series.name()).changed() {
+                    state.macro_features.retain(|&s| s != series);
+                    if selected {
+                        state.macro_features.push(series);
+                    }
+                }
+            }
+        });
+
+        let train_clicked = ui.add_enabled(!state.training, egui::Button::new("Train on Stored ETFs")).clicked();
+        if train_clicked {
+            state.training = true;
+            let test_fraction = state.test_fraction;
+            let options = FeatureOptions {
+                vol_spread: state.vol_spread_features.then(VolSpreadParams::default),
+                macros: state.macro_features.clone(),
+            };
+
+            tokio::task::spawn(async move {
+                let mut universe = Vec::new();
+                for etf in [Etf::SPY, Etf::QQQ, Etf::QQQM, Etf::DIA] {
+                    match db_service::get_etf(etf).await {
+                        Ok(data) if !data.price_data.is_empty() => universe.push((*data).clone()),
+                        Ok(_) => {}
+                        Err(e) => eprintln!("[ERROR] Could not load {}: {e}", etf.as_ref()),
+                    }
+                }
+
+                let inputs = match feature_inputs(&universe, &options).await {
+                    Ok(inputs) => inputs,
+                    Err(e) => {
+                        let _ = TRAIN_TEST_CHANNEL.0.send(vec![(String::from("Feature inputs"), Err(e))]);
+                        return;
+                    }
+                };
+
+                let evaluations = tokio::task::spawn_blocking(move || gradient_trees::evaluate_universe(&universe, &options, &inputs, test_fraction))
+                    .await
+                    .unwrap_or_else(|e| {
+                        eprintln!("[ERROR] Training failed: {e}");
+                        Vec::new()
+                    });
+                let _ = TRAIN_TEST_CHANNEL.0.send(evaluations);
+            });
+        }
+
+        if state.training {
+            ui.spinner();
+        }
+    });
+
+    ui.horizontal(|ui| {
+        ui.label("Gap Symbol: ");
+        ui.add(egui::TextEdit::singleline(&mut state.gap_symbol).desired_width(80.0));
+
+        ui.label("Gap Threshold: ");
+        ui.add(egui::DragValue::new(&mut state.gap_threshold).speed(0.0001).range(0.0..=0.1));
+
+        let train_clicked = ui.add_enabled(!state.training, egui::Button::new("Train Gap Model")).clicked();
+        if train_clicked {
+            state.training = true;
+            let (symbol, threshold, test_fraction) = (state.gap_symbol.clone(), state.gap_threshold, state.test_fraction);
+
+            tokio::task::spawn(async move {
+                // Trained on the stored 10-minute bars, which hold every regular session
+                let history = db_service::get_price_history(&symbol).await.map_err(|e| e.to_string());
+                let evaluation = match history {
+                    Ok(data) if data.price_data.is_empty() => Err(format!("No stored history for {symbol}")),
+                    Ok(data) => tokio::task::spawn_blocking(move || gradient_trees::evaluate_gaps(&data, threshold, test_fraction))
+                        .await
+                        .unwrap_or_else(|e| Err(e.to_string())),
+                    Err(e) => Err(e),
+                };
+                let _ = TRAIN_TEST_CHANNEL.0.send(vec![(format!("{symbol} gaps"), evaluation)]);
+            });
+        }
+    });
+
+    ui.separator();
+
+    if state.evaluations.is_empty() {
+        ui.label("No models trained...");
+        return;
+    }
+
+    egui::Grid::new("train_test_results").striped(true).show(ui, |ui| {
+        for title in ["Symbol", "Train Rows", "Test Rows", "Accuracy"] {
+            ui.strong(title);
+        }
+        ui.end_row();
+
+        for (symbol, evaluation) in &state.evaluations {
+            ui.label(symbol);
+            match evaluation {
+                Ok(evaluation) => {
+                    ui.label(evaluation.train_rows.to_string());
+                    ui.label(evaluation.test_rows.to_string());
+                    ui.label(format!("{:.1}%", evaluation.accuracy * 100.0));
+                }
+                Err(e) => {
+                    ui.label(e);
+                }
+            }
+            ui.end_row();
+        }
+    });
+}
+
+/// Daily series the columns of `options` are drawn from, fetched over the span of `universe`.
+async fn feature_inputs(universe: &[TickerData], options: &FeatureOptions) -> Result<DailyInputs, String> {
+    let mut inputs = DailyInputs::default();
+    if options.vol_spread.is_none() && options.macros.is_empty() {
+        return Ok(inputs);
+    }
+
+    let dates = universe
+        .iter()
+        .filter(|data| !data.price_data.is_empty())
+        .flat_map(|data| [data.price_data.datetime(0), data.price_data.datetime(data.price_data.len() - 1)])
+        .map(|t| t.date_naive());
+    let (Some(from), Some(to)) = (dates.clone().min(), dates.max()) else {
+        return Ok(inputs);
+    };
+
+    // VIX3M is the long tenor for VIX, as on the vol spread page
+    if options.vol_spread.is_some() {
+        let vix = macro_series::fetch_macro_series(MacroSeries::Vix, from, to).await.map_err(|e| e.to_string())?;
+        let vix3m = macro_series::fetch_macro_series(MacroSeries::Vix3m, from, to).await.map_err(|e| e.to_string())?;
+        inputs.implied = vol_spread::vix_implied(&vix);
+        inputs.long_implied = vol_spread::vix_implied(&vix3m);
+    }
+
+    for &series in &options.macros {
+        let values = macro_series::fetch_macro_series(series, from, to).await.map_err(|e| format!("{}: {e}", series.name()))?;
+        inputs.macros.push((series, values));
+    }
+
+    Ok(inputs)
+}
+
+pub fn arbitrage_scanner_widget(ui: &mut Ui, state: &mut ArbitragePageState) {
+    if let Ok((violations, discrepancies)) = ARBITRAGE_CHANNEL.1.as_ref().lock().try_recv() {
+        state.violations = violations;
+        state.discrepancies = discrepancies;
+        state.scanning = false;
+    }
+
+    ui.heading("Option Chain Arbitrage Scanner");
+    ui.add_space(7.5);
+
+    ui.horizontal(|ui| {
+        ui.label("Symbol: ");
+        ui.add(egui::TextEdit::singleline(&mut state.symbol).desired_width(80.0));
+
+        ui.label("Quote Date: ");
+        ui.add(egui_extras::DatePickerButton::new(&mut state.quote_date).id_salt("arbitrage_date_picker"));
+
+        ui.label("Spot: ");
+        ui.add(egui::DragValue::new(&mut state.spot).speed(0.01).range(0.0..=f64::MAX));
+
+        ui.label("Rate: ");
+        ui.add(egui::DragValue::new(&mut state.rate).speed(0.0001).range(0.0..=1.0));
+
+        ui.label("Div. Yield: ");
+        ui.add(egui::DragValue::new(&mut state.dividend_yield).speed(0.0001).range(0.0..=1.0));
+
+        ui.label("Cost/Contract: ");
+        ui.add(egui::DragValue::new(&mut state.cost_per_contract).speed(0.0001).range(0.0..=1.0));
+
+        ui.label("Cost/Share: ");
+        ui.add(egui::DragValue::new(&mut state.cost_per_share).speed(0.0001).range(0.0..=1.0));
+    });
+
+    ui.add_space(5.0);
+
+    ui.horizontal(|ui| {
+        egui::containers::ComboBox::from_label("Exercise").selected_text(format!("{:?}", state.style)).show_ui(ui, |box_ui| {
+            box_ui.selectable_value(&mut state.style, ExerciseStyle::American, "American");
+            box_ui.selectable_value(&mut state.style, ExerciseStyle::European, "European");
+        });
+
+        ui.checkbox(&mut state.only_profitable, "Only profitable after costs");
+
+        let scan_clicked = ui.add_enabled(!state.scanning && state.spot > 0.0, egui::Button::new("Scan Chain")).clicked();
+        if scan_clicked {
+            state.scanning = true;
+
+            let symbol = state.symbol.clone();
+            let params = ScanParams {
+                quote_date: state.quote_date,
+                spot: state.spot,
+                rate: state.rate,
+                dividend_yield: state.dividend_yield,
+                dividends: Vec::new(),
+                style: state.style,
+                cost_per_contract: state.cost_per_contract,
+                cost_per_share: state.cost_per_share,
+            };
+
+            // Stored snapshots first, as on the strategy page, so past dates scan offline
+            tokio::task::spawn(async move {
+                let stored = db_service::get_option_chain_as_of(symbol.clone(), params.quote_date)
+                    .await
+                    .map(|chain| chain.data)
+                    .unwrap_or_default();
+
+                let chain = if !stored.is_empty() {
+                    stored
+                } else {
+                    let date = params.quote_date
+                        .and_hms_opt(12, 0, 0)
+                        .and_then(|dt| dt.and_local_timezone(chrono_tz::America::New_York).single());
+
+                    match data::collection::get_options_chain(symbol, date).await {
+                        Ok(chain) => chain.data,
+                        Err(e) => {
+                            eprintln!("[ERROR] Could not fetch option chain: {e}");
+                            Vec::new()
+                        }
+                    }
+                };
+
+                // An older snapshot is priced as of the day it was quoted
+                let quote_date = chain.iter().find_map(|option| option.date).unwrap_or(params.quote_date);
+                let params = ScanParams { quote_date, ..params };
+
+                let scan = (
+                    arbitrage::scan_chain(&chain, &params),
+                    black_scholes::vendor_discrepancies(&chain, params.quote_date, params.spot, params.rate, params.dividend_yield, DiscrepancyTolerance::default()),
+                );
+                let _ = ARBITRAGE_CHANNEL.0.send(scan);
+            });
+        }
+
+        if state.scanning {
+            ui.spinner();
+        }
+    });
+
+    ui.separator();
+
+    if !state.discrepancies.is_empty() {
+        egui::CollapsingHeader::new(format!("Vendor Discrepancies ({})", state.discrepancies.len()))
+            .id_salt("vendor_discrepancies")
+            .show(ui, |ui| vendor_discrepancy_table(ui, &state.discrepancies));
+        ui.separator();
+    }
+
+    let violations = state.violations
+        .iter()
+        .filter(|el| !state.only_profitable || el.edge_after_costs > 0.0)
+        .collect::<Vec<_>>();
+
+    if violations.is_empty() {
+        ui.label("No violations found...");
+        return;
+    }
+
+    TableBuilder::new(ui)
+        .column(Column::auto().resizable(true))
+        .column(Column::auto().resizable(true))
+        .column(Column::remainder().clip(true))
+        .columns(Column::auto(), 2)
+        .striped(true)
+        .header(20.0, |mut header| {
+            header.col(|ui| {
+                ui.heading("Check");
+            });
+            header.col(|ui| {
+                ui.heading("Expiry");
+            });
+            header.col(|ui| {
+                ui.heading("Trade");
+            });
+            header.col(|ui| {
+                ui.heading("Edge");
+            });
+            header.col(|ui| {
+                ui.heading("After Costs");
+            });
+        })
+        .body(|body| {
+            body.rows(20.0, violations.len(), |mut row| {
+                let violation = violations[row.index()];
+                row.col(|ui| {
+                    ui.label(format!("{:?}", violation.kind));
+                });
+                row.col(|ui| {
+                    ui.label(violation.expiry.to_string());
+                });
+                row.col(|ui| {
+                    ui.label(&violation.trade);
+                });
+                row.col(|ui| {
+                    ui.label(format!("{:.4}", violation.edge));
+                });
+                row.col(|ui| {
+                    ui.label(format!("{:.4}", violation.edge_after_costs));
+                });
+            });
+        });
+}
+
+/// Vendor figures that disagree with ours, one row per contract and field.
+fn vendor_discrepancy_table(ui: &mut Ui, discrepancies: &[Discrepancy]) {
+    TableBuilder::new(ui)
+        .id_salt("vendor_discrepancy_table")
+        .column(Column::auto().resizable(true))
+        .columns(Column::auto(), 3)
+        .striped(true)
+        .max_scroll_height(200.0)
+        .header(20.0, |mut header| {
+            header.col(|ui| {
+                ui.heading("Contract");
+            });
+            header.col(|ui| {
+                ui.heading("Field");
+            });
+            header.col(|ui| {
+                ui.heading("Vendor");
+            });
+            header.col(|ui| {
+                ui.heading("Computed");
+            });
+        })
+        .body(|body| {
+            body.rows(20.0, discrepancies.len(), |mut row| {
+                let discrepancy = &discrepancies[row.index()];
+                row.col(|ui| {
+                    ui.label(&discrepancy.contract_id);
+                });
+                row.col(|ui| {
+                    ui.label(discrepancy.field);
+                });
+                row.col(|ui| {
+                    ui.label(format!("{:.4}", discrepancy.vendor));
+                });
+                row.col(|ui| {
+                    ui.label(format!("{:.4}", discrepancy.computed));
+                });
+            });
+        });
+}
+
+pub fn strategy_widget(ui: &mut Ui, state: &mut StrategyPageState) {
+    if let Ok(chain) = STRATEGY_CHANNEL.1.as_ref().lock().try_recv() {
+        state.chain = chain;
+        state.expiry = None;
+        state.far_expiry = None;
+        state.analysis = None;
+        state.loading = false;
+    }
+
+    ui.heading("Option Strategy Analyzer");
+    ui.add_space(7.5);
+
+    ui.horizontal(|ui| {
+        ui.label("Symbol: ");
+        ui.add(egui::TextEdit::singleline(&mut state.symbol).desired_width(80.0));
+
+        ui.label("Quote Date: ");
+        ui.add(egui_extras::DatePickerButton::new(&mut state.quote_date).id_salt("strategy_date_picker"));
+
+        ui.label("Spot: ");
+        ui.add(egui::DragValue::new(&mut state.spot).speed(0.01).range(0.0..=f64::MAX));
+
+        ui.label("Rate: ");
+        ui.add(egui::DragValue::new(&mut state.rate).speed(0.0001).range(0.0..=1.0));
+
+        ui.label("Div. Yield: ");
+        ui.add(egui::DragValue::new(&mut state.dividend_yield).speed(0.0001).range(0.0..=1.0));
+
+        let load_clicked = ui.add_enabled(!state.loading, egui::Button::new("Load Chain")).clicked();
+        if load_clicked {
+            state.loading = true;
+
+            let symbol = state.symbol.clone();
+            let quote_date = state.quote_date;
+
+            // Stored snapshots first, so past dates work without hitting the vendor
+            tokio::task::spawn(async move {
+                let stored = db_service::get_option_chain_as_of(symbol.clone(), quote_date)
+                    .await
+                    .map(|chain| chain.data)
+                    .unwrap_or_default();
+
+                let chain = if !stored.is_empty() {
+                    stored
+                } else {
+                    let date = quote_date
+                        .and_hms_opt(12, 0, 0)
+                        .and_then(|dt| dt.and_local_timezone(chrono_tz::America::New_York).single());
+
+                    match data::collection::get_options_chain(symbol, date).await {
+                        Ok(chain) => chain.data,
+                        Err(e) => {
+                            eprintln!("[ERROR] Could not fetch option chain: {e}");
+                            Vec::new()
+                        }
+                    }
+                };
+                let _ = STRATEGY_CHANNEL.0.send(chain);
+            });
+        }
+
+        if state.loading {
+            ui.spinner();
+        }
+    });
+
+    if state.chain.is_empty() {
+        ui.separator();
+        ui.label("No option chain loaded...");
+        return;
+    }
+
+    let mut expiries = state.chain.iter().map(|option| option.expiry_date).filter(|expiry| *expiry > state.quote_date).collect::<Vec<_>>();
+    expiries.sort();
+    expiries.dedup();
+
+    ui.add_space(5.0);
+
+    ui.horizontal(|ui| {
+        egui::containers::ComboBox::from_label("Strategy").selected_text(format!("{:?}", state.kind)).show_ui(ui, |box_ui| {
+            for kind in StrategyKind::ALL {
+                box_ui.selectable_value(&mut state.kind, kind, format!("{kind:?}"));
+            }
+        });
+
+        ui.label("Quantity: ");
+        ui.add(egui::DragValue::new(&mut state.quantity).speed(1.0).range(-100.0..=100.0));
+
+        let expiry_text = state.expiry.map_or(String::from("-"), |expiry| expiry.to_string());
+        egui::containers::ComboBox::from_label("Expiry").selected_text(expiry_text).show_ui(ui, |box_ui| {
+            for expiry in &expiries {
+                box_ui.selectable_value(&mut state.expiry, Some(*expiry), expiry.to_string());
+            }
+        });
+
+        if state.kind == StrategyKind::Calendar {
+            let far_text = state.far_expiry.map_or(String::from("-"), |expiry| expiry.to_string());
+            egui::containers::ComboBox::from_label("Far Expiry").selected_text(far_text).show_ui(ui, |box_ui| {
+                for expiry in expiries.iter().filter(|expiry| Some(**expiry) > state.expiry) {
+                    box_ui.selectable_value(&mut state.far_expiry, Some(*expiry), expiry.to_string());
+                }
+            });
+        }
+    });
+
+    let Some(expiry) = state.expiry else {
+        return;
+    };
+
+    let mut strikes = state.chain.iter().filter(|option| option.expiry_date == expiry).map(|option| option.strike_price).collect::<Vec<_>>();
+    strikes.sort_by(f64::total_cmp);
+    strikes.dedup();
+
+    ui.horizontal(|ui| {
+        for idx in 0..state.kind.strike_count() {
+            egui::containers::ComboBox::from_id_salt(("strategy_strike", idx))
+                .selected_text(format!("Strike {}: {}", idx + 1, state.strikes[idx]))
+                .show_ui(ui, |box_ui| {
+                    for strike in &strikes {
+                        box_ui.selectable_value(&mut state.strikes[idx], *strike, strike.to_string());
+                    }
+                });
+        }
+    });
+
+    ui.separator();
+
+    if state.spot <= 0.0 {
+        ui.label("Set the spot price...");
+        return;
+    }
+
+    let inputs = StrategyInputs {
+        kind: state.kind,
+        quantity: state.quantity,
+        expiry,
+        far_expiry: state.far_expiry.unwrap_or(expiry),
+        strikes: state.strikes,
+        quote_date: state.quote_date,
+        spot: state.spot,
+        rate: state.rate,
+        dividend_yield: state.dividend_yield,
+    };
+    if state.analysis.as_ref().is_none_or(|analysis| analysis.inputs != inputs) {
+        state.analysis = Some(strategy_analysis(&state.chain, inputs));
+    }
+    let analysis = state.analysis.as_ref().unwrap();
+
+    let Some(strategy) = &analysis.strategy else {
+        ui.label("Pick strikes quoted in the chain...");
+        return;
+    };
+    let breakevens = analysis.breakevens
+        .iter()
+        .map(|spot| format!("{spot:.2}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let greeks = analysis.greeks;
+
+    ui.horizontal(|ui| {
+        ui.label(format!("Cost: {:.2}", strategy.cost()));
+        ui.label(format!("Max Profit: {:.2}", analysis.max_profit));
+        ui.label(format!("Max Loss: {:.2}", analysis.max_loss));
+        ui.label(format!("Breakevens: {breakevens}"));
+    });
+    ui.horizontal(|ui| {
+        ui.label(format!("Delta: {:.2}", greeks.delta));
+        ui.label(format!("Gamma: {:.4}", greeks.gamma));
+        ui.label(format!("Theta/day: {:.2}", greeks.theta));
+        ui.label(format!("Vega/pt: {:.2}", greeks.vega));
+        ui.label(format!("Rho/pt: {:.2}", greeks.rho));
+    });
+
+    ui.add_space(5.0);
+
+    Plot::new("strategy_pnl_plot")
+        .legend(Legend::default())
+        .show(ui, |plot_ui| {
+            for (date, row) in analysis.dates.iter().zip(analysis.surface.rows()) {
+                let points = PlotPoints::from_iter(analysis.spots.iter().zip(row).map(|(spot, pnl)| [*spot, *pnl]));
+                plot_ui.line(Line::new(date.to_string(), points));
+            }
+        });
+}
+
+/// Values the position described by `inputs` on `chain`: P&L curves from the quote date to the
+/// first expiry, the last one being the payoff at expiry, plus its extremes, breakevens and greeks.
+pub fn strategy_analysis(chain: &[StockOption], inputs: StrategyInputs) -> StrategyAnalysis {
+    let params = ValuationParams {
+        quote_date: inputs.quote_date,
+        spot: inputs.spot,
+        rate: inputs.rate,
+        dividend_yield: inputs.dividend_yield,
+        dividends: Vec::new(),
+    };
+    let strategy = OptionStrategy::from_chain(inputs.kind, chain, inputs.expiry, inputs.far_expiry, &inputs.strikes, inputs.quantity, &params);
+
+    let mut analysis = StrategyAnalysis {
+        inputs,
+        strategy: None,
+        max_profit: f64::NAN,
+        max_loss: f64::NAN,
+        breakevens: Vec::new(),
+        greeks: Default::default(),
+        spots: Vec::new(),
+        dates: Vec::new(),
+        surface: Default::default(),
+    };
+    let Some(strategy) = strategy else {
+        return analysis;
+    };
+
+    let first_expiry = strategy.first_expiry().unwrap_or(analysis.inputs.expiry);
+    let (low, high) = (params.spot * 0.7, params.spot * 1.3);
+    let days = (first_expiry - params.quote_date).num_days().max(0);
+
+    (analysis.max_profit, analysis.max_loss) = strategy.max_profit_loss(params.spot * 3.0, &params);
+    analysis.breakevens = strategy.breakevens(low, high, &params);
+    analysis.greeks = strategy.greeks(&params);
+    analysis.spots = linspace(low, high, 201);
+    analysis.dates = [0, days / 3, 2 * days / 3, days].iter().map(|&offset| params.quote_date + chrono::Duration::days(offset)).collect();
+    analysis.surface = strategy.pnl_surface(&analysis.spots, &analysis.dates, &params);
+    analysis.strategy = Some(strategy);
+
+    analysis
+}
+
+pub fn vol_spread_widget(ui: &mut Ui, state: &mut VolSpreadPageState) {
+    if let Ok((points, garch)) = VOL_SPREAD_CHANNEL.1.as_ref().lock().try_recv() {
+        state.points = points;
+        state.garch = garch;
+        state.loading = false;
+    }
+
+    ui.heading("Implied vs Realized Volatility");
+    ui.add_space(7.5);
+
+    ui.horizontal(|ui| {
+        ui.label("Symbol: ");
+        ui.add(egui::TextEdit::singleline(&mut state.symbol).desired_width(80.0));
+
+        ui.label("From: ");
+        ui.add(egui_extras::DatePickerButton::new(&mut state.from_date).id_salt("vol_spread_from_picker"));
+
+        ui.label("To: ");
+        ui.add(egui_extras::DatePickerButton::new(&mut state.to_date).id_salt("vol_spread_to_picker"));
+
+        ui.label("Horizon (days): ");
+        ui.add(egui::DragValue::new(&mut state.horizon_days).range(5..=365));
+
+        egui::containers::ComboBox::from_label("Implied").selected_text(format!("{:?}", state.source)).show_ui(ui, |box_ui| {
+            box_ui.selectable_value(&mut state.source, ImpliedSource::Vix, "VIX");
+            box_ui.selectable_value(&mut state.source, ImpliedSource::StoredChains, "Stored Chains");
+        });
+
+        let load_clicked = ui.add_enabled(!state.loading, egui::Button::new("Load")).clicked();
+        if load_clicked {
+            state.loading = true;
+
+            let symbol = state.symbol.clone();
+            let (from, to) = (state.from_date, state.to_date);
+            let source = state.source;
+            let params = VolSpreadParams {
+                horizon_days: state.horizon_days,
+                long_horizon_days: state.horizon_days * 3,
+                ..Default::default()
+            };
+
+            tokio::task::spawn(async move {
+                let bars = data::collection::get_ticker_data(
+                    symbol.clone(),
+                    TickerDatatype::HistOHCL(from.to_string(), to.to_string()),
+                    data::types::PointTimeDelta::Day
+                ).await;
+
+                // VIX3M is the long tenor for VIX, stored chains use three times the horizon
+                let implied = match source {
+                    ImpliedSource::Vix => {
+                        let vix = macro_series::fetch_macro_series(MacroSeries::Vix, from, to).await;
+                        let vix3m = macro_series::fetch_macro_series(MacroSeries::Vix3m, from, to).await;
+                        vix.and_then(|vix| Ok((vol_spread::vix_implied(&vix), vol_spread::vix_implied(&vix3m?))))
+                            .map_err(|e| e.to_string())
+                    }
+                    ImpliedSource::StoredChains => {
+                        let short = db_service::get_atm_iv_series(symbol.clone(), from, to, params.horizon_days)
+                            .await
+                            .map_err(|e| e.to_string());
+                        let long = db_service::get_atm_iv_series(symbol, from, to, params.long_horizon_days)
+                            .await
+                            .map_err(|e| e.to_string());
+                        short.and_then(|short| Ok((short, long?)))
+                    }
+                };
+
+                let loaded = match (bars, implied) {
+                    (Ok(bars), Ok((implied, long_implied))) => {
+                        let points = vol_spread::vol_spread_on_series(bars.series(), &implied, &long_implied, params).unwrap_or_else(|e| {
+                            eprintln!("[ERROR] {e}");
+                            Vec::new()
+                        });
+                        (points, vol_spread::garch_fit_report(bars.series(), params))
+                    }
+                    (Err(e), _) => {
+                        eprintln!("[ERROR] Could not fetch bars: {e}");
+                        (Vec::new(), None)
+                    }
+                    (_, Err(e)) => {
+                        eprintln!("[ERROR] Could not load implied volatility: {e}");
+                        (Vec::new(), None)
+                    }
+                };
+                let _ = VOL_SPREAD_CHANNEL.0.send(loaded);
+            });
+        }
+
+        if state.loading {
+            ui.spinner();
+        }
+    });
+
+    ui.separator();
+
+    if state.points.is_empty() {
+        ui.label("No data loaded...");
+        return;
+    }
+
+    if let Some(report) = &state.garch {
+        egui::CollapsingHeader::new("GARCH Fit")
+            .id_salt("vol_spread_garch")
+            .show(ui, |ui| garch_report_grid(ui, report));
+        ui.separator();
+    }
+
+    let line = |name: &str, value: fn(&VolSpreadPoint) -> f64| {
+        let points = PlotPoints::from_iter(
+            state.points.iter()
+                .enumerate()
+                .filter(|(_, p)| !value(p).is_nan())
+                .map(|(idx, p)| [idx as f64, value(p)])
+        );
+        Line::new(name, points)
+    };
+
+    let plot_height = ui.available_height() / 3.0;
+
+    Plot::new("vol_spread_vol_plot")
+        .height(plot_height)
+        .legend(Legend::default())
+        .show(ui, |plot_ui| {
+            plot_ui.line(line("Implied", |p| p.implied));
+            plot_ui.line(line("Realized", |p| p.realized));
+            plot_ui.line(line("Forward Realized", |p| p.forward_realized));
+            plot_ui.line(line("GARCH", |p| p.garch));
+            plot_ui.line(line("Term Slope", |p| p.term_slope));
+            plot_ui.line(line("GARCH Term Slope", |p| p.garch_term_slope));
+        });
+
+    Plot::new("vol_spread_premium_plot")
+        .height(plot_height)
+        .legend(Legend::default())
+        .show(ui, |plot_ui| {
+            plot_ui.line(line("Variance Premium", |p| p.variance_premium));
+            plot_ui.line(line("Expected Premium", |p| p.expected_premium));
+            plot_ui.line(line("Implied - GARCH", |p| p.garch_spread));
+        });
+
+    Plot::new("vol_spread_zscore_plot")
+        .legend(Legend::default())
+        .show(ui, |plot_ui| {
+            plot_ui.line(line("Premium Z-Score", |p| p.premium_zscore));
+        });
+}
+
+/// Fit diagnostics of the vol spread page's GARCH model next to how its forecasts did.
+fn garch_report_grid(ui: &mut Ui, report: &GarchFitReport) {
+    let (fit, forecast) = (&report.diagnostics, &report.forecast_vs_realized);
+
+    egui::Grid::new("vol_spread_garch_grid").striped(true).show(ui, |ui| {
+        let mut row = |name: &str, value: String, compared: &str, compared_value: String| {
+            ui.label(name);
+            ui.label(value);
+            ui.label(compared);
+            ui.label(compared_value);
+            ui.end_row();
+        };
+
+        row("Fitted Returns", fit.observations.to_string(), "Forecasts Scored", forecast.observations.to_string());
+        row("Log Likelihood", format!("{:.1}", fit.log_likelihood), "Mean Forecast", format!("{:.4}", forecast.mean_forecast));
+        row("AIC / BIC", format!("{:.1} / {:.1}", fit.aic, fit.bic), "Mean Realized", format!("{:.4}", forecast.mean_realized));
+        row("Persistence", format!("{:.4}", fit.persistence), "Bias", format!("{:.4}", forecast.bias));
+        row("Half-Life (bars)", format!("{:.1}", fit.half_life), "MAE", format!("{:.4}", forecast.mae));
+        row("Residual Kurtosis", format!("{:.2}", fit.residual_kurtosis), "RMSE", format!("{:.4}", forecast.rmse));
+        row(
+            &format!("Ljung-Box Q² ({} lags)", fit.ljung_box_lags),
+            format!("{:.2}", fit.ljung_box_squared),
+            "Correlation",
+            format!("{:.3}", forecast.correlation),
+        );
+    });
+}
+
+pub fn event_vol_widget(ui: &mut Ui, state: &mut EventVolPageState) {
+    if let Ok(moves) = EVENT_VOL_CHANNEL.1.as_ref().lock().try_recv() {
+        state.moves = moves;
+        state.loading = false;
+    }
+
+    ui.heading("Expected Move & IV Crush");
+    ui.add_space(7.5);
+
+    ui.horizontal(|ui| {
+        ui.label("Symbol: ");
+        ui.add(egui::TextEdit::singleline(&mut state.symbol).desired_width(80.0));
+
+        ui.label("Rate: ");
+        ui.add(egui::DragValue::new(&mut state.rate).speed(0.0001).range(0.0..=1.0));
+
+        ui.label("Div. Yield: ");
+        ui.add(egui::DragValue::new(&mut state.dividend_yield).speed(0.0001).range(0.0..=1.0));
+    });
+
+    ui.horizontal(|ui| {
+        ui.label("Events: ");
+        ui.add(egui::TextEdit::singleline(&mut state.events).hint_text("2024-05-02 amc, 2024-08-01 amc").desired_width(400.0));
+
+        let parsed = state.events
+            .split(',')
+            .filter(|el| !el.trim().is_empty())
+            .map(EarningsEvent::from_str)
+            .collect::<Result<Vec<_>, _>>();
+
+        let load_clicked = ui.add_enabled(!state.loading && matches!(&parsed, Ok(events) if !events.is_empty()), egui::Button::new("Analyze")).clicked();
+        if let Err(e) = &parsed {
+            ui.label(e);
+        }
+
+        if let (true, Ok(events)) = (load_clicked, parsed) {
+            state.loading = true;
+
+            let symbol = state.symbol.clone();
+            let (rate, dividend_yield) = (state.rate, state.dividend_yield);
+
+            tokio::task::spawn(async move {
+                let from = events.iter().map(|event| event.pre_date()).min().unwrap() - chrono::Days::new(7);
+                let to = events.iter().map(|event| event.post_date()).max().unwrap() + chrono::Days::new(1);
+
+                let bars = match data::collection::get_ticker_data(
+                    symbol.clone(),
+                    TickerDatatype::HistOHCL(from.to_string(), to.to_string()),
+                    data::types::PointTimeDelta::Day
+                ).await {
+                    Ok(bars) => bars.price_data,
+                    Err(e) => {
+                        eprintln!("[ERROR] Could not fetch bars: {e}");
+                        let _ = EVENT_VOL_CHANNEL.0.send(Vec::new());
+                        return;
+                    }
+                };
+
+                let mut moves = Vec::new();
+                for event in events {
+                    let chains = db_service::get_event_chains(symbol.clone(), event).await.map_err(|e| e.to_string());
+
+                    match chains {
+                        Ok((pre, post)) => moves.extend(events::analyze_event(event, &pre, &post, bars.view(), rate, dividend_yield)),
+                        Err(e) => eprintln!("[ERROR] Could not load chains around {}: {e}", event.date),
+                    }
+                }
+                let _ = EVENT_VOL_CHANNEL.0.send(moves);
+            });
+        }
+
+        if state.loading {
+            ui.spinner();
+        }
+    });
+
+    ui.separator();
+
+    if state.moves.is_empty() {
+        ui.label("No events analyzed...");
+        return;
+    }
+
+    let summary = events::summarize(&state.moves);
+    ui.horizontal(|ui| {
+        ui.label(format!("Events: {}", summary.events));
+        ui.label(format!("Avg Expected: {:.2}%", summary.mean_expected_move * 100.0));
+        ui.label(format!("Avg Realized: {:.2}%", summary.mean_realized_move * 100.0));
+        ui.label(format!("Avg Ratio: {:.2}", summary.mean_move_ratio));
+        ui.label(format!("Exceeded: {:.0}%", summary.exceeded * 100.0));
+        ui.label(format!("Avg IV Crush: {:.2} pts ({:.1}%)", summary.mean_iv_crush * 100.0, summary.mean_relative_iv_crush * 100.0));
+    });
+
+    ui.add_space(5.0);
+
+    TableBuilder::new(ui)
+        .columns(Column::auto().resizable(true), 8)
+        .striped(true)
+        .header(20.0, |mut header| {
+            for title in ["Event", "Expiry", "Expected", "Realized", "Ratio", "Pre IV", "Post IV", "Crush"] {
+                header.col(|ui| {
+                    ui.heading(title);
+                });
+            }
+        })
+        .body(|body| {
+            body.rows(20.0, state.moves.len(), |mut row| {
+                let event = &state.moves[row.index()];
+                let cells = [
+                    format!("{} {:?}", event.event.date, event.event.timing),
+                    event.expiry.to_string(),
+                    format!("{:.2}%", event.expected_move * 100.0),
+                    format!("{:+.2}%", event.realized_move * 100.0),
+                    format!("{:.2}", event.move_ratio()),
+                    format!("{:.1}", event.pre_iv * 100.0),
+                    format!("{:.1}", event.post_iv * 100.0),
+                    format!("{:.1}", event.iv_crush() * 100.0),
+                ];
+                for cell in cells {
+                    row.col(|ui| {
+                        ui.label(cell);
+                    });
+                }
+            });
+        });
 }